@@ -0,0 +1,5 @@
+drop table revoked_tokens;
+
+alter table users
+drop column tokens_revoked_at,
+drop column deregistered_at;
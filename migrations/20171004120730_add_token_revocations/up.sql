@@ -0,0 +1,9 @@
+alter table users
+add column tokens_revoked_at timestamptz,
+add column deregistered_at timestamptz;
+
+create table revoked_tokens (
+	jti varchar(64) primary key not null,
+	expires_at timestamptz,
+	created_at timestamptz not null
+);
@@ -0,0 +1,12 @@
+create table inventory_seeds (
+	seed_id integer primary key not null,
+	user_id integer not null,
+	dna_id integer not null,
+	setting_id integer not null,
+	cell_id integer not null,
+	prefab varchar(255) not null,
+	point geometry(POINT, 4326) not null,
+	picked_at timestamptz not null
+);
+
+create index inventory_seeds_user_id_idx on inventory_seeds (user_id);
@@ -0,0 +1,4 @@
+drop table dead_entities;
+
+alter table entities
+drop column owner_id;
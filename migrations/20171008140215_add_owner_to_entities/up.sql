@@ -0,0 +1,21 @@
+alter table entities
+add column owner_id integer;
+
+create index entities_owner_id_idx on entities (owner_id);
+
+create table dead_entities (
+	id integer primary key not null,
+	owner_id integer not null,
+	point geometry(POINT, 4326) not null,
+	prefab varchar(255) not null,
+	cell_id integer not null,
+	setting_id integer not null,
+	nickname varchar not null,
+	fitness real not null,
+	age real not null,
+	size real not null,
+	life_expectancy real not null,
+	died_at timestamptz not null
+);
+
+create index dead_entities_owner_id_idx on dead_entities (owner_id);
@@ -0,0 +1,4 @@
+create table heartbeats (
+	name varchar(64) primary key not null,
+	beat_at timestamptz not null
+);
@@ -0,0 +1,10 @@
+create table app_config (
+	id integer primary key not null default 1 check (id = 1),
+	min_client_version varchar(32) not null default '0.0.0',
+	latest_client_version varchar(32) not null default '0.0.0',
+	features jsonb not null default '{}',
+	motd text,
+	updated_at timestamptz not null default now()
+);
+
+insert into app_config (id) values (1);
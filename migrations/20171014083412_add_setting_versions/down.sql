@@ -0,0 +1 @@
+drop table setting_versions;
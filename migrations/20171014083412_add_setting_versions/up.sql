@@ -0,0 +1,14 @@
+create table setting_versions (
+	id serial not null primary key,
+	setting_id integer not null references settings (id) on delete cascade,
+	version integer not null,
+	data jsonb not null,
+	author_id integer references users (id) on delete set null,
+	restored_version integer,
+	created_at timestamptz not null default now(),
+	unique (setting_id, version)
+);
+
+-- Current settings are the first version
+insert into setting_versions (setting_id, version, data)
+select id, 1, to_jsonb(settings) from settings;
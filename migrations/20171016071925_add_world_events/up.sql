@@ -0,0 +1,16 @@
+create table world_events (
+	id serial not null primary key,
+	kind varchar(32) not null,
+	entity_id integer,
+	seed_id integer,
+	dna_id integer,
+	user_id integer,
+	point geometry(POINT, 4326),
+	cell_id integer,
+	payload jsonb not null default '{}',
+	created_at timestamptz not null default now()
+);
+
+create index world_events_created_at_idx on world_events (created_at);
+create index world_events_cell_id_idx on world_events (cell_id);
+create index world_events_kind_idx on world_events (kind);
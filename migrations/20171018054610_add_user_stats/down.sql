@@ -0,0 +1,2 @@
+drop table user_cell_visits;
+drop table user_daily_stats;
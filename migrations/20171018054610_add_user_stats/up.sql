@@ -0,0 +1,61 @@
+create table user_daily_stats (
+	user_id integer not null,
+	day date not null,
+	wifi_readings integer not null default 0,
+	sound_readings integer not null default 0,
+	light_readings integer not null default 0,
+	gps_readings integer not null default 0,
+	seeds_picked integer not null default 0,
+	seeds_spread integer not null default 0,
+	primary key (user_id, day)
+);
+
+create index user_daily_stats_day_idx on user_daily_stats (day);
+
+create table user_cell_visits (
+	user_id integer not null,
+	cell_id integer not null,
+	day date not null,
+	primary key (user_id, cell_id, day)
+);
+
+create index user_cell_visits_day_idx on user_cell_visits (day);
+
+-- Activity before the counters, from the readings, the inventory and the
+-- world events. Seeds picked before the world events were added are only
+-- known while they are still in an inventory.
+insert into user_daily_stats (user_id, day, wifi_readings, sound_readings, light_readings, gps_readings, seeds_picked, seeds_spread)
+select user_id, day, sum(wifi)::integer, sum(sound)::integer, sum(light)::integer, sum(gps)::integer, sum(picked)::integer, sum(spread)::integer
+from (
+	select user_id, (created_at at time zone 'utc')::date as day, count(*) as wifi, 0 as sound, 0 as light, 0 as gps, 0 as picked, 0 as spread
+	from wifi_readings group by 1, 2
+	union all
+	select user_id, (created_at at time zone 'utc')::date, 0, count(*), 0, 0, 0, 0
+	from sound_readings group by 1, 2
+	union all
+	select user_id, (created_at at time zone 'utc')::date, 0, 0, count(*), 0, 0, 0
+	from light_readings group by 1, 2
+	union all
+	select user_id, (created_at at time zone 'utc')::date, 0, 0, 0, count(*), 0, 0
+	from gps_readings group by 1, 2
+	union all
+	select user_id, (created_at at time zone 'utc')::date, 0, 0, 0, 0, count(*), 0
+	from world_events where kind = 'pickup' and user_id is not null group by 1, 2
+	union all
+	select user_id, (picked_at at time zone 'utc')::date, 0, 0, 0, 0, count(*), 0
+	from inventory_seeds
+	where not exists (
+		select 1 from world_events
+		where world_events.kind = 'pickup' and world_events.seed_id = inventory_seeds.seed_id and world_events.user_id = inventory_seeds.user_id
+	)
+	group by 1, 2
+	union all
+	select user_id, (created_at at time zone 'utc')::date, 0, 0, 0, 0, 0, count(*)
+	from world_events where kind = 'spread' and user_id is not null group by 1, 2
+) as counts
+group by user_id, day;
+
+insert into user_cell_visits (user_id, cell_id, day)
+select distinct gps_readings.user_id, cells.id, (gps_readings.created_at at time zone 'utc')::date
+from gps_readings
+join cells on ST_Contains(cells.geom, gps_readings.point);
@@ -0,0 +1,7 @@
+drop table device_link_codes;
+drop table devices;
+
+alter table users
+drop column display_name,
+drop column avatar_color,
+drop column language;
@@ -0,0 +1,20 @@
+alter table users
+add column display_name varchar(32),
+add column avatar_color varchar(7),
+add column language varchar(16);
+
+create table devices (
+	id serial not null primary key,
+	user_id integer not null references users (id) on delete cascade,
+	device_id varchar(128) not null unique,
+	created_at timestamptz not null default now(),
+	last_seen_at timestamptz not null default now()
+);
+
+create index devices_user_id_idx on devices (user_id);
+
+create table device_link_codes (
+	code varchar(16) not null primary key,
+	user_id integer not null references users (id) on delete cascade,
+	expires_at timestamptz not null
+);
@@ -0,0 +1,2 @@
+alter table users
+add column hidden boolean not null default false;
@@ -0,0 +1,8 @@
+create table user_presence (
+	user_id integer not null primary key references users (id) on delete cascade,
+	point geometry(POINT, 4326) not null,
+	cell_id integer not null,
+	seen_at timestamptz not null
+);
+
+create index user_presence_seen_at_idx on user_presence (seen_at);
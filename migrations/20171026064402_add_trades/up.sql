@@ -0,0 +1,15 @@
+create table trades (
+	id serial not null primary key,
+	from_user_id integer not null references users (id) on delete cascade,
+	to_user_id integer not null references users (id) on delete cascade,
+	offered_seed_ids integer[] not null,
+	requested_seed_ids integer[] not null,
+	status varchar(16) not null default 'pending',
+	created_at timestamptz not null default now(),
+	expires_at timestamptz not null,
+	resolved_at timestamptz
+);
+
+create index trades_from_user_id_idx on trades (from_user_id);
+create index trades_to_user_id_idx on trades (to_user_id);
+create index trades_pending_idx on trades (expires_at) where status = 'pending';
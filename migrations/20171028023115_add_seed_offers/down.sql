@@ -0,0 +1 @@
+drop table seed_offers;
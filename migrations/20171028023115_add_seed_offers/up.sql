@@ -0,0 +1,15 @@
+-- Ids are shared with the seeds, `/seeds/pickup` takes either
+create table seed_offers (
+	id integer not null primary key default nextval('seeds_id_seq'),
+	user_id integer not null references users (id) on delete cascade,
+	cell_id integer not null,
+	dna_id integer not null,
+	setting_id integer not null,
+	point geometry(POINT, 4326) not null,
+	prefab varchar(255) not null,
+	created_at timestamptz not null,
+	expires_at timestamptz not null
+);
+
+create index seed_offers_user_id_idx on seed_offers (user_id);
+create index seed_offers_expires_at_idx on seed_offers (expires_at);
@@ -8,6 +8,8 @@ extern crate serde;
 #[macro_use]
 extern crate serde_derive;
 
+use std::time::Duration;
+
 use rocket_jwt::Jwt;
 use rocket_jwt::JwtConfig;
 
@@ -28,7 +30,7 @@ pub struct LoginForm {
 #[post("/user-only-endpoint")]
 pub fn user_only_endpoint(cred: Jwt<AuthCredentials>) -> String {
     // JWT Token validated and payload (AuthCredentials) is parsed and extracted
-    format!("User id is: {}", cred.0.user_id)
+    format!("User id is: {}", cred.payload.user_id)
 }
 
 #[post("/login", data="<login_form>")]
@@ -37,13 +39,15 @@ pub fn login(login_form: Form<LoginForm>) -> Result<Jwt<AuthCredentials>, Status
 
     // Uber secure authentication...
     match (login_form.username.as_str(), login_form.password.as_str()) {
-        ("admin", "admin") => Ok(Jwt(AuthCredentials { user_id: "adminid".to_string() })),
+        ("admin", "admin") => Ok(Jwt::new(AuthCredentials { user_id: "adminid".to_string() })),
         _                  => Err(Status::Unauthorized)
     }
 }
 
 pub fn main() {
-    let jwt_config = JwtConfig { secret: "my jwt secret".to_string() };
+    let mut jwt_config = JwtConfig::new("my jwt secret");
+    jwt_config.lifetime = Some(Duration::from_secs(15 * 60));
+
     rocket::ignite()
         .mount("/", routes![user_only_endpoint, login])
         .manage(jwt_config)
@@ -0,0 +1,195 @@
+use rocket::Request;
+use rocket::request::FormItems;
+
+/// Where guards look for a token, in the order given in `JwtConfig::sources`
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenSource {
+    /// `Authorization: Bearer <token>` header
+    Bearer,
+    /// Cookie with the given name
+    Cookie(String),
+    /// Query parameter with the given name, for clients which can't set
+    /// headers such as browsers opening a WebSocket
+    Query(String)
+}
+
+impl TokenSource {
+    /// Parses `bearer`, `cookie:<name>` or `query:<name>`
+    pub fn parse(source: &str) -> Option<TokenSource> {
+        let mut parts = source.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some("bearer"), None)                         => Some(TokenSource::Bearer),
+            (Some("cookie"), Some(name)) if name.len() > 0 => Some(TokenSource::Cookie(name.to_string())),
+            (Some("query"), Some(name)) if name.len() > 0  => Some(TokenSource::Query(name.to_string())),
+            _                                              => None
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Extracted {
+    Missing,
+    /// Something was sent, but it isn't a token, e.g. a `Basic` authorization
+    Malformed,
+    Token(String)
+}
+
+pub fn extract(request: &Request, sources: &[TokenSource]) -> Extracted {
+    for source in sources.iter() {
+        let extracted = match *source {
+            TokenSource::Bearer         => from_bearer(request),
+            TokenSource::Cookie(ref n)  => from_cookie(request, n),
+            TokenSource::Query(ref n)   => from_query(request, n)
+        };
+
+        if extracted != Extracted::Missing {
+            return extracted;
+        }
+    }
+
+    Extracted::Missing
+}
+
+fn from_bearer(request: &Request) -> Extracted {
+    let values: Vec<_> = request.headers().get("Authorization").collect();
+    match values.len() {
+        0 => return Extracted::Missing,
+        1 => {},
+        _ => return Extracted::Malformed
+    }
+
+    let parts: Vec<_> = values[0].split_whitespace().collect();
+    if parts.len() != 2 || !parts[0].eq_ignore_ascii_case("bearer") {
+        return Extracted::Malformed;
+    }
+
+    Extracted::Token(parts[1].to_string())
+}
+
+fn from_cookie(request: &Request, name: &str) -> Extracted {
+    match request.cookies().get(name) {
+        Some(cookie) if cookie.value().len() > 0 => Extracted::Token(cookie.value().to_string()),
+        Some(_)                                  => Extracted::Malformed,
+        None                                     => Extracted::Missing
+    }
+}
+
+fn from_query(request: &Request, name: &str) -> Extracted {
+    let query = match request.uri().query() {
+        Some(query) => query,
+        None        => return Extracted::Missing
+    };
+
+    for (key, value) in FormItems::from(query) {
+        if key.as_str() != name {
+            continue;
+        }
+
+        return match value.url_decode() {
+            Ok(ref token) if token.len() > 0 => Extracted::Token(token.clone()),
+            _                                => Extracted::Malformed
+        };
+    }
+
+    Extracted::Missing
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket;
+    use rocket::http::Cookie;
+    use rocket::http::Header;
+    use rocket::local::Client;
+
+    use super::*;
+
+    fn client() -> Client {
+        Client::new(rocket::ignite()).unwrap()
+    }
+
+    fn authorization(value: &str) -> Header<'static> {
+        Header::new("Authorization", value.to_string())
+    }
+
+    #[test]
+    fn sources_are_parsed() {
+        assert_eq!(TokenSource::parse("bearer"), Some(TokenSource::Bearer));
+        assert_eq!(TokenSource::parse("cookie:token"), Some(TokenSource::Cookie("token".to_string())));
+        assert_eq!(TokenSource::parse("query:access_token"), Some(TokenSource::Query("access_token".to_string())));
+        assert_eq!(TokenSource::parse("cookie:a:b"), Some(TokenSource::Cookie("a:b".to_string())));
+    }
+
+    #[test]
+    fn invalid_sources_are_rejected() {
+        assert_eq!(TokenSource::parse(""), None);
+        assert_eq!(TokenSource::parse("bearer:token"), None);
+        assert_eq!(TokenSource::parse("Bearer"), None);
+        assert_eq!(TokenSource::parse("cookie"), None);
+        assert_eq!(TokenSource::parse("cookie:"), None);
+        assert_eq!(TokenSource::parse("query:"), None);
+        assert_eq!(TokenSource::parse("header:X-Token"), None);
+    }
+
+    #[test]
+    fn bearer_tokens_are_extracted() {
+        let client = client();
+
+        let request = client.get("/").header(authorization("Bearer abc.def.ghi"));
+        assert_eq!(from_bearer(request.inner()), Extracted::Token("abc.def.ghi".to_string()));
+
+        let request = client.get("/").header(authorization("bearer   abc.def.ghi "));
+        assert_eq!(from_bearer(request.inner()), Extracted::Token("abc.def.ghi".to_string()));
+
+        let request = client.get("/");
+        assert_eq!(from_bearer(request.inner()), Extracted::Missing);
+    }
+
+    #[test]
+    fn other_authorizations_are_malformed() {
+        let client = client();
+
+        for value in &["Basic dXNlcjpwYXNz", "Bearer", "Bearer a b", "abc.def.ghi", "Bearerabc", ""] {
+            let request = client.get("/").header(authorization(value));
+            assert_eq!(from_bearer(request.inner()), Extracted::Malformed, "Authorization: {}", value);
+        }
+
+        let request = client.get("/")
+            .header(authorization("Bearer abc"))
+            .header(authorization("Bearer def"));
+        assert_eq!(from_bearer(request.inner()), Extracted::Malformed);
+    }
+
+    #[test]
+    fn cookie_and_query_tokens_are_extracted() {
+        let client = client();
+
+        let request = client.get("/").cookie(Cookie::new("token", "abc"));
+        assert_eq!(from_cookie(request.inner(), "token"), Extracted::Token("abc".to_string()));
+        assert_eq!(from_cookie(request.inner(), "other"), Extracted::Missing);
+
+        let request = client.get("/?page=2&access_token=a%2Eb");
+        assert_eq!(from_query(request.inner(), "access_token"), Extracted::Token("a.b".to_string()));
+        assert_eq!(from_query(request.inner(), "token"), Extracted::Missing);
+
+        let request = client.get("/?access_token=");
+        assert_eq!(from_query(request.inner(), "access_token"), Extracted::Malformed);
+    }
+
+    #[test]
+    fn sources_are_tried_in_order() {
+        let client = client();
+        let sources = [TokenSource::Query("access_token".to_string()), TokenSource::Bearer];
+
+        let request = client.get("/?access_token=query").header(authorization("Bearer header"));
+        assert_eq!(extract(request.inner(), &sources), Extracted::Token("query".to_string()));
+
+        let request = client.get("/").header(authorization("Bearer header"));
+        assert_eq!(extract(request.inner(), &sources), Extracted::Token("header".to_string()));
+
+        // A malformed token isn't skipped for the next source
+        let request = client.get("/?access_token=").header(authorization("Bearer header"));
+        assert_eq!(extract(request.inner(), &sources), Extracted::Malformed);
+
+        assert_eq!(extract(client.get("/").inner(), &sources), Extracted::Missing);
+    }
+}
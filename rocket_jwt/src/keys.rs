@@ -0,0 +1,413 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::error::Error;
+use std::path::Path;
+
+use base64;
+use pem;
+
+use jwt::Algorithm;
+
+#[derive(Debug)]
+pub enum KeyError {
+    Io(io::Error),
+    InvalidKey(String),
+    UnsupportedAlgorithm(Algorithm)
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            KeyError::Io(ref err)                => write!(f, "Failed to read key: {}", err),
+            KeyError::InvalidKey(ref reason)     => write!(f, "Invalid key: {}", reason),
+            KeyError::UnsupportedAlgorithm(alg)  => write!(f, "Unsupported algorithm: {:?}", alg)
+        }
+    }
+}
+
+impl Error for KeyError {
+    fn description(&self) -> &str {
+        match *self {
+            KeyError::Io(_)                   => "Failed to read key",
+            KeyError::InvalidKey(_)           => "Invalid key",
+            KeyError::UnsupportedAlgorithm(_) => "Unsupported algorithm"
+        }
+    }
+}
+
+impl From<io::Error> for KeyError {
+    fn from(err: io::Error) -> Self {
+        KeyError::Io(err)
+    }
+}
+
+/// Public parameters of an RSA key as described by RFC 7517
+#[derive(Debug, Clone, Serialize)]
+pub struct Jwk {
+    pub kty: &'static str,
+    #[serde(rename = "use")]
+    pub use_: &'static str,
+    pub alg: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kid: Option<String>,
+    pub n: String,
+    pub e: String
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JwkSet {
+    pub keys: Vec<Jwk>
+}
+
+pub struct JwtKey {
+    pub kid: Option<String>,
+    pub algorithm: Algorithm,
+    /// Shared secret or PKCS#1 DER private key
+    signing: Option<Vec<u8>>,
+    /// Shared secret or PKCS#1 DER public key
+    verifying: Vec<u8>,
+    jwk: Option<Jwk>
+}
+
+impl JwtKey {
+    /// HS256 key from a shared secret
+    pub fn hmac(kid: Option<String>, secret: &[u8]) -> Self {
+        JwtKey {
+            kid,
+            algorithm: Algorithm::HS256,
+            signing: Some(secret.to_vec()),
+            verifying: secret.to_vec(),
+            jwk: None
+        }
+    }
+
+    /// RSA key from PEM files. Keys without a private key can only validate
+    /// tokens, which is what retired keys are kept for.
+    pub fn from_pem_files<P, Q>(kid: String, algorithm: Algorithm, private_key: Option<P>, public_key: Q) -> Result<Self, KeyError>
+        where P: AsRef<Path>, Q: AsRef<Path>
+    {
+        let private_pem = match private_key {
+            Some(path) => Some(fs::read(path)?),
+            None       => None
+        };
+
+        let public_pem = fs::read(public_key)?;
+        Self::from_pem(kid, algorithm, private_pem.as_ref().map(Vec::as_slice), &public_pem)
+    }
+
+    /// Private keys should be PKCS#1 (`BEGIN RSA PRIVATE KEY`), public keys
+    /// either PKCS#1 or SubjectPublicKeyInfo (`BEGIN PUBLIC KEY`)
+    pub fn from_pem(kid: String, algorithm: Algorithm, private_pem: Option<&[u8]>, public_pem: &[u8]) -> Result<Self, KeyError> {
+        match algorithm {
+            Algorithm::RS256 | Algorithm::RS384 | Algorithm::RS512 => {},
+            _ => return Err(KeyError::UnsupportedAlgorithm(algorithm))
+        }
+
+        let signing = match private_pem {
+            Some(private_pem) => {
+                let pem = parse_pem(private_pem)?;
+                if pem.tag != "RSA PRIVATE KEY" {
+                    return Err(KeyError::InvalidKey("Private key should be a PKCS#1 RSA PRIVATE KEY".to_string()));
+                }
+
+                Some(pem.contents)
+            },
+            None => None
+        };
+
+        let verifying = rsa_public_key(public_pem)?;
+        let jwk = public_jwk(&kid, algorithm, &verifying)?;
+
+        Ok(JwtKey { kid: Some(kid), algorithm, signing, verifying, jwk: Some(jwk) })
+    }
+
+    pub fn can_sign(&self) -> bool {
+        self.signing.is_some()
+    }
+
+    pub fn signing_key(&self) -> Option<&[u8]> {
+        self.signing.as_ref().map(Vec::as_slice)
+    }
+
+    pub fn verifying_key(&self) -> &[u8] {
+        &self.verifying
+    }
+}
+
+/// Keys used for signing and validating tokens. Tokens are signed with the
+/// signing key and carry its `kid` in their header, so that tokens signed with
+/// an older key keep validating as long as it stays in the set.
+pub struct KeySet {
+    signing: JwtKey,
+    others: Vec<JwtKey>
+}
+
+impl KeySet {
+    pub fn new(signing: JwtKey) -> Self {
+        assert!(signing.can_sign(), "Signing key should have a private key");
+        KeySet { signing, others: vec![] }
+    }
+
+    /// Adds a key only used for validating tokens
+    pub fn add(&mut self, key: JwtKey) {
+        self.others.push(key);
+    }
+
+    pub fn signing_key(&self) -> &JwtKey {
+        &self.signing
+    }
+
+    /// Finds the key for a token's `kid`. Tokens without a `kid` are only
+    /// validated with keys without one.
+    pub fn find(&self, kid: Option<&str>) -> Option<&JwtKey> {
+        self.iter().find(|key| key.kid.as_ref().map(String::as_str) == kid)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&JwtKey> {
+        Some(&self.signing).into_iter().chain(self.others.iter())
+    }
+
+    /// Public keys in the set, shared secrets are never exposed
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet { keys: self.iter().filter_map(|key| key.jwk.clone()).collect() }
+    }
+}
+
+fn algorithm_name(algorithm: Algorithm) -> &'static str {
+    match algorithm {
+        Algorithm::HS256 => "HS256",
+        Algorithm::HS384 => "HS384",
+        Algorithm::HS512 => "HS512",
+        Algorithm::RS256 => "RS256",
+        Algorithm::RS384 => "RS384",
+        Algorithm::RS512 => "RS512"
+    }
+}
+
+fn base64url(bytes: &[u8]) -> String {
+    base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+}
+
+fn parse_pem(input: &[u8]) -> Result<pem::Pem, KeyError> {
+    pem::parse(input).map_err(|err| KeyError::InvalidKey(format!("{:?}", err)))
+}
+
+/// PKCS#1 RSAPublicKey DER of a public key PEM
+fn rsa_public_key(public_pem: &[u8]) -> Result<Vec<u8>, KeyError> {
+    let pem = parse_pem(public_pem)?;
+
+    // PKCS#1 keys are the RSAPublicKey itself, anything else is expected to be
+    // a SubjectPublicKeyInfo
+    if pem.tag == "RSA PUBLIC KEY" {
+        Ok(pem.contents)
+    } else {
+        der::subject_public_key(&pem.contents)
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| KeyError::InvalidKey("Failed to parse public key".to_string()))
+    }
+}
+
+fn public_jwk(kid: &str, algorithm: Algorithm, public_key: &[u8]) -> Result<Jwk, KeyError> {
+    let (n, e) = der::rsa_public_key(public_key)
+        .ok_or_else(|| KeyError::InvalidKey("Failed to parse public key".to_string()))?;
+
+    Ok(Jwk {
+        kty: "RSA",
+        use_: "sig",
+        alg: algorithm_name(algorithm),
+        kid: Some(kid.to_string()),
+        n: base64url(n),
+        e: base64url(e)
+    })
+}
+
+/// Just enough DER for reading public keys
+mod der {
+    const INTEGER: u8 = 0x02;
+    const BIT_STRING: u8 = 0x03;
+    const SEQUENCE: u8 = 0x30;
+
+    /// Reads the element at the start of `input`, returns its tag, contents
+    /// and the rest of the input
+    fn read(input: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+        let (&tag, rest) = input.split_first()?;
+        let (&len, mut rest) = rest.split_first()?;
+
+        let len = if len & 0x80 == 0 {
+            len as usize
+        } else {
+            let len_bytes = (len & 0x7f) as usize;
+            if len_bytes == 0 || len_bytes > 4 || rest.len() < len_bytes {
+                return None;
+            }
+
+            let len = rest[..len_bytes].iter().fold(0, |len, &b| (len << 8) | b as usize);
+            rest = &rest[len_bytes..];
+            len
+        };
+
+        if rest.len() < len {
+            return None;
+        }
+
+        Some((tag, &rest[..len], &rest[len..]))
+    }
+
+    fn expect(input: &[u8], expected: u8) -> Option<(&[u8], &[u8])> {
+        match read(input)? {
+            (tag, contents, rest) if tag == expected => Some((contents, rest)),
+            _                                        => None
+        }
+    }
+
+    /// SubjectPublicKeyInfo ::= SEQUENCE { algorithm SEQUENCE, subjectPublicKey BIT STRING }
+    pub fn subject_public_key(spki: &[u8]) -> Option<&[u8]> {
+        let (spki, _) = expect(spki, SEQUENCE)?;
+        let (_, rest) = expect(spki, SEQUENCE)?;
+        let (bits, _) = expect(rest, BIT_STRING)?;
+
+        // First byte is the number of unused bits, always 0 for keys
+        match bits.split_first()? {
+            (&0, key) => Some(key),
+            _         => None
+        }
+    }
+
+    /// RSAPublicKey ::= SEQUENCE { modulus INTEGER, publicExponent INTEGER }
+    pub fn rsa_public_key(key: &[u8]) -> Option<(&[u8], &[u8])> {
+        let (key, _) = expect(key, SEQUENCE)?;
+        let (n, rest) = expect(key, INTEGER)?;
+        let (e, _) = expect(rest, INTEGER)?;
+
+        Some((unsigned(n), unsigned(e)))
+    }
+
+    // Integers are signed, positive ones might be prefixed with zeros
+    fn unsigned(int: &[u8]) -> &[u8] {
+        let zeros = int.iter().take_while(|&&b| b == 0).count();
+        &int[zeros.min(int.len().saturating_sub(1))..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json;
+    use serde_json::Value;
+
+    use super::*;
+
+    const PRIVATE_PEM: &'static [u8] = include_bytes!("../fixtures/rsa.pem");
+    const PUBLIC_PEM: &'static [u8] = include_bytes!("../fixtures/rsa.pub.pem");
+    const PKCS1_PUBLIC_PEM: &'static [u8] = include_bytes!("../fixtures/rsa.pkcs1.pub.pem");
+    const JWK: &'static str = include_str!("../fixtures/rsa.jwk.json");
+
+    fn spki() -> Vec<u8> {
+        pem::parse(PUBLIC_PEM).unwrap().contents
+    }
+
+    #[test]
+    fn pem_keys_publish_their_jwk() {
+        let key = JwtKey::from_pem("fixture".to_string(), Algorithm::RS256, Some(PRIVATE_PEM), PUBLIC_PEM).unwrap();
+        assert!(key.can_sign());
+
+        let mut keys = KeySet::new(key);
+        keys.add(JwtKey::hmac(None, b"secret"));
+
+        let jwks = serde_json::to_value(&keys.jwks()).unwrap();
+        let expected: Value = serde_json::from_str(JWK).unwrap();
+        assert_eq!(jwks["keys"], Value::Array(vec![expected]));
+    }
+
+    #[test]
+    fn pkcs1_and_spki_public_keys_are_the_same_key() {
+        let spki = JwtKey::from_pem("a".to_string(), Algorithm::RS384, None, PUBLIC_PEM).unwrap();
+        let pkcs1 = JwtKey::from_pem("a".to_string(), Algorithm::RS384, None, PKCS1_PUBLIC_PEM).unwrap();
+
+        assert!(!spki.can_sign());
+        assert_eq!(spki.verifying_key(), pkcs1.verifying_key());
+    }
+
+    #[test]
+    fn only_rsa_keys_are_loaded_from_pem() {
+        for algorithm in &[Algorithm::HS256, Algorithm::HS384, Algorithm::HS512] {
+            match JwtKey::from_pem("a".to_string(), *algorithm, None, PUBLIC_PEM) {
+                Err(KeyError::UnsupportedAlgorithm(rejected)) => assert_eq!(rejected, *algorithm),
+                _ => panic!("{:?} keys shouldn't load from PEM", algorithm)
+            }
+        }
+
+        // The private key has to be PKCS#1
+        match JwtKey::from_pem("a".to_string(), Algorithm::RS256, Some(PUBLIC_PEM), PUBLIC_PEM) {
+            Err(KeyError::InvalidKey(_)) => {},
+            _ => panic!("A public key was accepted as the private key")
+        }
+
+        match JwtKey::from_pem("a".to_string(), Algorithm::RS256, None, b"not a pem") {
+            Err(KeyError::InvalidKey(_)) => {},
+            _ => panic!("Garbage was accepted as a public key")
+        }
+    }
+
+    #[test]
+    fn algorithm_names_are_the_jwa_ones() {
+        let names = [
+            (Algorithm::HS256, "HS256"), (Algorithm::HS384, "HS384"), (Algorithm::HS512, "HS512"),
+            (Algorithm::RS256, "RS256"), (Algorithm::RS384, "RS384"), (Algorithm::RS512, "RS512")
+        ];
+
+        for &(algorithm, name) in names.iter() {
+            assert_eq!(algorithm_name(algorithm), name);
+            // Tokens name their algorithm the same way in their header
+            assert_eq!(serde_json::to_value(&algorithm).unwrap(), Value::String(name.to_string()));
+        }
+    }
+
+    #[test]
+    fn der_reads_the_public_key_of_an_spki() {
+        let spki = spki();
+        let key = der::subject_public_key(&spki).unwrap();
+        let (n, e) = der::rsa_public_key(key).unwrap();
+
+        assert_eq!(n.len(), 256);
+        assert!(n[0] != 0);
+        assert_eq!(e, &[0x01, 0x00, 0x01]);
+    }
+
+    #[test]
+    fn der_rejects_truncated_input() {
+        let spki = spki();
+        for len in 0..spki.len() {
+            assert!(der::subject_public_key(&spki[..len]).is_none(), "Truncated to {} bytes", len);
+        }
+
+        let key = der::subject_public_key(&spki).unwrap().to_vec();
+        for len in 0..key.len() {
+            assert!(der::rsa_public_key(&key[..len]).is_none(), "Truncated to {} bytes", len);
+        }
+    }
+
+    #[test]
+    fn der_rejects_malformed_input() {
+        // Not a sequence
+        assert!(der::subject_public_key(&[0x02, 0x01, 0x00]).is_none());
+        // Long form length without length bytes, or with more than 4
+        assert!(der::subject_public_key(&[0x30, 0x80]).is_none());
+        assert!(der::subject_public_key(&[0x30, 0x85, 0, 0, 0, 0, 1, 0]).is_none());
+        // Length past the end of the input
+        assert!(der::subject_public_key(&[0x30, 0x82, 0xff, 0xff, 0x30]).is_none());
+        // Bit string with unused bits
+        assert!(der::subject_public_key(&[0x30, 0x07, 0x30, 0x00, 0x03, 0x03, 0x01, 0x30, 0x00]).is_none());
+        // RSA key with a missing exponent
+        assert!(der::rsa_public_key(&[0x30, 0x03, 0x02, 0x01, 0x05]).is_none());
+    }
+
+    #[test]
+    fn der_strips_the_sign_byte_of_integers() {
+        let key = [0x30, 0x08, 0x02, 0x03, 0x00, 0x80, 0x01, 0x02, 0x01, 0x00];
+        let (n, e) = der::rsa_public_key(&key).unwrap();
+
+        assert_eq!(n, &[0x80, 0x01]);
+        assert_eq!(e, &[0x00]);
+    }
+}
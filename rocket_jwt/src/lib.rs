@@ -1,5 +1,10 @@
 //! JWT Token guard & state for rocket web server library
 //!
+//! Tokens are signed with an HMAC secret or with RSA keys (`RS256`, `RS384`
+//! and `RS512`) loaded from PEM files, see `KeySet` for key rotation. ECDSA
+//! and EdDSA keys are not supported by the `jsonwebtoken` version this crate
+//! is built with.
+//!
 //! # Example
 //!
 //! ```
@@ -13,6 +18,8 @@
 //! #[macro_use]
 //! extern crate serde_derive;
 //!
+//! use std::time::Duration;
+//!
 //! use rocket_jwt::Jwt;
 //! use rocket_jwt::JwtConfig;
 //!
@@ -35,7 +42,7 @@
 //!     let credentials = credentials.into_inner();
 //!
 //!     // JWT Token validated and payload (AuthCredentials) is parsed and extracted
-//!     format!("User id is: {}", cred.user_id)
+//!     format!("User id is: {}", credentials.user_id)
 //! }
 //!
 //! #[post("/login", data="<login_form>")]
@@ -44,13 +51,15 @@
 //!
 //!     // Uber secure authentication...
 //!     match (login_form.username, login_form.password) {
-//!         ("admin", "admin") => Jwt(AuthCredentials { user_id: "adminid" }),
+//!         ("admin", "admin") => Jwt::new(AuthCredentials { user_id: "adminid" }),
 //!         _                  => Status::Unauthorized
 //!     }
 //! }
 //!
 //! pub fn main() {
-//!     let jwt_config = JwtConfig { secret: "my jwt secret" };
+//!     let mut jwt_config = JwtConfig::new("my jwt secret");
+//!     jwt_config.lifetime = Some(Duration::from_secs(15 * 60));
+//!
 //!     rocket::ignite()
 //!         .mount("/", routes![user_only_endpoint, login])
 //!         .manage(jwt_config)
@@ -59,13 +68,35 @@
 //! ```
 extern crate rocket;
 extern crate jsonwebtoken as jwt;
+extern crate base64;
+extern crate pem;
+extern crate rand;
 extern crate serde;
+extern crate serde_json;
+#[macro_use]
+extern crate serde_derive;
+
+mod keys;
+mod extract;
+mod revocation;
+
+pub use keys::*;
+pub use revocation::*;
+pub use extract::TokenSource;
+
+use extract::extract;
+use extract::Extracted;
+pub use jwt::Algorithm;
 
 use std::io::Cursor;
+use std::time::Duration;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
 
 use rocket::State;
 use rocket::Request;
 use rocket::http::Status;
+use rocket::http::ContentType;
 use rocket::outcome::Outcome;
 use rocket::request;
 use rocket::request::FromRequest;
@@ -82,49 +113,248 @@ use serde::Serialize;
 use serde::de::DeserializeOwned;
 
 pub struct JwtConfig {
-    pub secret: String
+    pub keys: KeySet,
+    /// Written to the `iss` claim and required when decoding if set
+    pub issuer: Option<String>,
+    /// Written to the `aud` claim and required when decoding if set
+    pub audience: Option<String>,
+    /// Lifetime of access tokens, they never expire when it is not set
+    pub lifetime: Option<Duration>,
+    /// Lifetime of refresh tokens, they never expire when it is not set
+    pub refresh_lifetime: Option<Duration>,
+    /// Clock skew tolerated while checking `exp` and `nbf`, in seconds
+    pub leeway: i64,
+    /// Decoded tokens are rejected when revoked in the store if set
+    pub revocations: Option<Box<RevocationStore>>,
+    /// Where guards look for tokens, the first source with a token is used
+    pub sources: Vec<TokenSource>
+}
+
+impl JwtConfig {
+    /// Config signing and validating tokens with a single HS256 secret
+    pub fn new<S: AsRef<[u8]>>(secret: S) -> Self {
+        Self::with_keys(KeySet::new(JwtKey::hmac(None, secret.as_ref())))
+    }
+
+    pub fn with_keys(keys: KeySet) -> Self {
+        JwtConfig {
+            keys,
+            issuer: None,
+            audience: None,
+            lifetime: None,
+            refresh_lifetime: None,
+            leeway: 60,
+            revocations: None,
+            sources: vec![TokenSource::Bearer]
+        }
+    }
+}
+
+/// Registered claim names of RFC 7519, all of them are optional. Timestamps
+/// are seconds since the unix epoch.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RegisteredClaims {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exp: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iat: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nbf: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub iss: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sub: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aud: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jti: Option<String>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenKind {
+    Access,
+    Refresh
 }
 
-pub struct Jwt<T: Serialize + DeserializeOwned>(pub T);
+impl Default for TokenKind {
+    fn default() -> Self {
+        TokenKind::Access
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Claims<T> {
+    #[serde(flatten)]
+    registered: RegisteredClaims,
+    #[serde(default)]
+    typ: TokenKind,
+    #[serde(flatten)]
+    payload: T
+}
+
+/// The part of the header needed to pick the key a token is validated with
+#[derive(Deserialize)]
+struct TokenHeader {
+    alg: Algorithm,
+    #[serde(default)]
+    kid: Option<String>
+}
+
+impl TokenHeader {
+    fn decode(token: &str) -> Option<Self> {
+        let encoded = token.split('.').next()?;
+        let json = base64::decode_config(encoded, base64::URL_SAFE_NO_PAD).ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+}
+
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)
+}
+
+/// Access token guard. Responding with it encodes the payload into a new
+/// access token.
+pub struct Jwt<T: Serialize + DeserializeOwned> {
+    pub payload: T,
+    pub claims: RegisteredClaims,
+    /// Id of the key the token is validated with
+    pub kid: Option<String>
+}
 
 impl<T: Serialize + DeserializeOwned> Jwt<T> {
+    pub fn new(payload: T) -> Self {
+        Jwt { payload, claims: RegisteredClaims::default(), kid: None }
+    }
+
+    /// Sets the `sub` claim of the tokens encoded from this, which is what
+    /// `RevocationStore::revoke_subject` revokes by
+    pub fn subject<S: Into<String>>(mut self, sub: S) -> Self {
+        self.claims.sub = Some(sub.into());
+        self
+    }
+
     pub fn into_inner(self) -> T {
-        self.0
+        self.payload
+    }
+
+    /// Revokes this token in the revocation store of the config
+    pub fn revoke(&self, jwt_config: &JwtConfig) -> RevocationResult {
+        let jti = self.claims.jti.as_ref().ok_or("Token has no jti")?;
+        match jwt_config.revocations {
+            Some(ref store) => store.revoke_token(jti, self.claims.exp),
+            None            => Err("No revocation store is configured".into())
+        }
     }
 
+    /// Encodes the payload into an access token
     pub fn encode(&self, jwt_config: &JwtConfig) -> Result<String, Status> {
-        encode::<T>(&Header::default(), &self.0, jwt_config.secret.as_bytes())
+        self.encode_as(TokenKind::Access, jwt_config.lifetime, jwt_config)
+    }
+
+    /// Encodes the payload into a refresh token, which is only accepted by the
+    /// `RefreshJwt` guard
+    pub fn encode_refresh(&self, jwt_config: &JwtConfig) -> Result<String, Status> {
+        self.encode_as(TokenKind::Refresh, jwt_config.refresh_lifetime, jwt_config)
+    }
+
+    fn encode_as(&self, typ: TokenKind, lifetime: Option<Duration>, jwt_config: &JwtConfig) -> Result<String, Status> {
+        let issued_at = now();
+        let registered = RegisteredClaims {
+            exp: lifetime.map(|lifetime| issued_at + lifetime.as_secs() as i64),
+            iat: Some(issued_at),
+            nbf: Some(issued_at),
+            iss: jwt_config.issuer.clone(),
+            sub: self.claims.sub.clone(),
+            aud: jwt_config.audience.clone(),
+            jti: Some(format!("{:016x}", rand::random::<u64>()))
+        };
+
+        let key = jwt_config.keys.signing_key();
+        let mut header = Header::default();
+        header.alg = key.algorithm;
+        header.kid = key.kid.clone();
+
+        let claims = Claims { registered, typ, payload: &self.payload };
+        let signing_key = key.signing_key().ok_or(Status::InternalServerError)?;
+        encode(&header, &claims, signing_key)
             .map_err(|_| Status::InternalServerError)
     }
-}
 
-impl<'a, 'r, T: Serialize + DeserializeOwned> FromRequest<'a, 'r> for Jwt<T> {
-    type Error = ();
+    /// Decodes and validates a token of the given kind
+    pub fn decode(token: &str, typ: TokenKind, jwt_config: &JwtConfig) -> Option<Self> {
+        let header = TokenHeader::decode(token)?;
+        let key = jwt_config.keys.find(header.kid.as_ref().map(String::as_str))?;
 
-    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
-        // Get rocket state for accessing jwt secret
-        let jwt_config = request.guard::<State<JwtConfig>>().expect("JwtConfig state");
+        // Only the key's own algorithm is accepted, otherwise a public key
+        // could be used as an HMAC secret
+        if header.alg != key.algorithm {
+            return None;
+        }
 
-        // If authorization header is not set return failure
-        let keys: Vec<_> = request.headers().get("Authorization").collect();
-        if keys.len() != 1 {
-            return Outcome::Failure((Status::Unauthorized, ()));
+        // `exp` is validated when present but not required since clients sign
+        // register tokens themselves
+        let validation = Validation {
+            leeway: jwt_config.leeway,
+            validate_nbf: true,
+            ..Validation::default()
+        };
+
+        let claims = decode::<Claims<T>>(token, key.verifying_key(), &validation).ok()?.claims;
+        if claims.typ != typ {
+            return None;
         }
 
-        // Extract token from Authorization header
-        let auth_parts: Vec<_> = keys[0].split_whitespace().collect();
-        if auth_parts.len() != 2 {
-            return Outcome::Failure((Status::Unauthorized, ()));
+        if jwt_config.issuer.is_some() && claims.registered.iss != jwt_config.issuer {
+            return None;
         }
 
-        // Decode jwt token
-        let key = auth_parts[1];
-        let token = decode::<T>(&key, jwt_config.secret.as_bytes(), &Validation::default());
+        if jwt_config.audience.is_some() && claims.registered.aud != jwt_config.audience {
+            return None;
+        }
 
-        match token {
-            Ok(token) => Outcome::Success(Jwt(token.claims)),
-            _         => Outcome::Failure((Status::Unauthorized, ()))
+        if let Some(ref store) = jwt_config.revocations {
+            if store.is_revoked(&claims.registered) {
+                return None;
+            }
         }
+
+        Some(Jwt { payload: claims.payload, claims: claims.registered, kid: key.kid.clone() })
+    }
+}
+
+fn guard<T>(request: &Request, typ: TokenKind) -> request::Outcome<Option<Jwt<T>>, ()>
+    where T: Serialize + DeserializeOwned
+{
+    // Get rocket state for accessing jwt secret
+    let jwt_config = request.guard::<State<JwtConfig>>().expect("JwtConfig state");
+
+    let token = match extract(request, &jwt_config.sources) {
+        Extracted::Token(token) => token,
+        Extracted::Missing      => return Outcome::Success(None),
+        Extracted::Malformed    => return Outcome::Failure((Status::Unauthorized, ()))
+    };
+
+    match Jwt::decode(&token, typ, &jwt_config) {
+        Some(token) => Outcome::Success(Some(token)),
+        None        => Outcome::Failure((Status::Unauthorized, ()))
+    }
+}
+
+fn required<T>(outcome: request::Outcome<Option<T>, ()>) -> request::Outcome<T, ()> {
+    match outcome {
+        Outcome::Success(Some(token)) => Outcome::Success(token),
+        Outcome::Success(None)        => Outcome::Failure((Status::Unauthorized, ())),
+        Outcome::Failure(failure)     => Outcome::Failure(failure),
+        Outcome::Forward(forward)     => Outcome::Forward(forward)
+    }
+}
+
+impl<'a, 'r, T: Serialize + DeserializeOwned> FromRequest<'a, 'r> for Jwt<T> {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        required(guard(request, TokenKind::Access))
     }
 }
 
@@ -134,8 +364,7 @@ impl<'r, T: Serialize + DeserializeOwned> Responder<'r> for Jwt<T> {
         let jwt_config = request.guard::<State<JwtConfig>>().expect("JwtConfig state not set!");
 
         // Encode the payload into JWT
-        let token = encode::<T>(&Header::default(), &self.0, jwt_config.secret.as_bytes())
-            .map_err(|_| Status::InternalServerError)?;
+        let token = self.encode(&jwt_config)?;
 
         Response::build()
             .status(Status::Ok)
@@ -143,3 +372,141 @@ impl<'r, T: Serialize + DeserializeOwned> Responder<'r> for Jwt<T> {
             .ok()
     }
 }
+
+/// Refresh token guard, access tokens are rejected by it
+pub struct RefreshJwt<T: Serialize + DeserializeOwned>(pub Jwt<T>);
+
+impl<T: Serialize + DeserializeOwned> RefreshJwt<T> {
+    pub fn into_inner(self) -> T {
+        self.0.into_inner()
+    }
+}
+
+impl<'a, 'r, T: Serialize + DeserializeOwned> FromRequest<'a, 'r> for RefreshJwt<T> {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        required(guard(request, TokenKind::Refresh)).map(RefreshJwt)
+    }
+}
+
+/// Responds with the public keys of the `JwtConfig` state as a JWK set, so
+/// other services can validate tokens without knowing any secret
+pub struct Jwks;
+
+impl<'r> Responder<'r> for Jwks {
+    fn respond_to(self, request: &Request) -> response::Result<'r> {
+        let jwt_config = request.guard::<State<JwtConfig>>().expect("JwtConfig state not set!");
+        let jwks = serde_json::to_string(&jwt_config.keys.jwks())
+            .map_err(|_| Status::InternalServerError)?;
+
+        Response::build()
+            .status(Status::Ok)
+            .header(ContentType::JSON)
+            .sized_body(Cursor::new(jwks))
+            .ok()
+    }
+}
+
+/// Access token guard for endpoints open to anonymous clients. It is `None`
+/// when no token is sent, but an invalid token still fails with `Unauthorized`
+/// so clients know they should refresh it.
+pub struct OptionalJwt<T: Serialize + DeserializeOwned>(pub Option<Jwt<T>>);
+
+impl<T: Serialize + DeserializeOwned> OptionalJwt<T> {
+    pub fn into_inner(self) -> Option<T> {
+        self.0.map(Jwt::into_inner)
+    }
+}
+
+impl<'a, 'r, T: Serialize + DeserializeOwned> FromRequest<'a, 'r> for OptionalJwt<T> {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        guard(request, TokenKind::Access).map(OptionalJwt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRIVATE_PEM: &'static [u8] = include_bytes!("../fixtures/rsa.pem");
+    const PUBLIC_PEM: &'static [u8] = include_bytes!("../fixtures/rsa.pub.pem");
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Payload {
+        user_id: i32
+    }
+
+    fn encode_header(header: &str) -> String {
+        format!("{}.e30.c2ln", base64::encode_config(header, base64::URL_SAFE_NO_PAD))
+    }
+
+    fn rsa_config() -> JwtConfig {
+        let key = JwtKey::from_pem("current".to_string(), Algorithm::RS256, Some(PRIVATE_PEM), PUBLIC_PEM).unwrap();
+        JwtConfig::with_keys(KeySet::new(key))
+    }
+
+    #[test]
+    fn token_headers_name_the_algorithm_and_key() {
+        let header = TokenHeader::decode(&encode_header(r#"{"alg":"RS256","typ":"JWT","kid":"2017-10"}"#)).unwrap();
+        assert_eq!(header.alg, Algorithm::RS256);
+        assert_eq!(header.kid, Some("2017-10".to_string()));
+
+        let header = TokenHeader::decode(&encode_header(r#"{"alg":"HS256"}"#)).unwrap();
+        assert_eq!(header.alg, Algorithm::HS256);
+        assert_eq!(header.kid, None);
+    }
+
+    #[test]
+    fn malformed_token_headers_are_rejected() {
+        assert!(TokenHeader::decode("").is_none());
+        assert!(TokenHeader::decode("not base64!.e30.c2ln").is_none());
+        assert!(TokenHeader::decode(&encode_header("not json")).is_none());
+        assert!(TokenHeader::decode(&encode_header(r#"{"kid":"2017-10"}"#)).is_none());
+        assert!(TokenHeader::decode(&encode_header(r#"{"alg":"none"}"#)).is_none());
+        assert!(TokenHeader::decode(&encode_header(r#"{"alg":"ES256"}"#)).is_none());
+    }
+
+    #[test]
+    fn rsa_tokens_round_trip() {
+        let jwt_config = rsa_config();
+        let token = Jwt::new(Payload { user_id: 1 }).subject("1").encode(&jwt_config).unwrap();
+
+        let header = TokenHeader::decode(&token).unwrap();
+        assert_eq!(header.alg, Algorithm::RS256);
+        assert_eq!(header.kid, Some("current".to_string()));
+
+        let decoded = Jwt::<Payload>::decode(&token, TokenKind::Access, &jwt_config).unwrap();
+        assert_eq!(decoded.payload, Payload { user_id: 1 });
+        assert_eq!(decoded.claims.sub, Some("1".to_string()));
+        assert!(Jwt::<Payload>::decode(&token, TokenKind::Refresh, &jwt_config).is_none());
+    }
+
+    #[test]
+    fn tokens_of_retired_keys_keep_validating() {
+        let old_config = rsa_config();
+        let token = Jwt::new(Payload { user_id: 1 }).encode(&old_config).unwrap();
+
+        let mut keys = KeySet::new(JwtKey::hmac(Some("new".to_string()), b"secret"));
+        keys.add(JwtKey::from_pem("current".to_string(), Algorithm::RS256, None, PUBLIC_PEM).unwrap());
+        let jwt_config = JwtConfig::with_keys(keys);
+
+        assert!(Jwt::<Payload>::decode(&token, TokenKind::Access, &jwt_config).is_some());
+        assert!(Jwt::<Payload>::decode(&token, TokenKind::Access, &JwtConfig::new("secret")).is_none());
+    }
+
+    #[test]
+    fn public_keys_are_not_accepted_as_hmac_secrets() {
+        let jwt_config = rsa_config();
+        let public_key = jwt_config.keys.signing_key().verifying_key().to_vec();
+
+        let mut header = Header::default();
+        header.kid = Some("current".to_string());
+        let claims = Claims { registered: RegisteredClaims::default(), typ: TokenKind::Access, payload: Payload { user_id: 1 } };
+        let forged = encode(&header, &claims, &public_key).unwrap();
+
+        assert!(Jwt::<Payload>::decode(&forged, TokenKind::Access, &jwt_config).is_none());
+    }
+}
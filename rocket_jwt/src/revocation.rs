@@ -0,0 +1,167 @@
+use std::error::Error;
+use std::sync::Mutex;
+use std::collections::HashMap;
+
+use RegisteredClaims;
+
+pub type RevocationResult = Result<(), Box<Error + Send + Sync>>;
+
+/// Checked for every decoded token when set in `JwtConfig`. Tokens can be
+/// revoked one by one with their `jti` claim, or all tokens of a subject (`sub`
+/// claim) issued before a moment at once.
+pub trait RevocationStore: Send + Sync {
+    fn is_revoked(&self, claims: &RegisteredClaims) -> bool;
+
+    /// `expires_at` is the token's `exp`, the revocation can be forgotten after it
+    fn revoke_token(&self, jti: &str, expires_at: Option<i64>) -> RevocationResult;
+
+    /// Revokes the tokens of the subject issued before `before`, or in the
+    /// same second since `iat` is in seconds
+    fn revoke_subject(&self, sub: &str, before: i64) -> RevocationResult;
+}
+
+/// Keeps revocations in memory, they are lost when the process exits
+#[derive(Default)]
+pub struct MemoryRevocationStore {
+    tokens: Mutex<HashMap<String, Option<i64>>>,
+    subjects: Mutex<HashMap<String, i64>>
+}
+
+impl MemoryRevocationStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forgets revoked tokens which are expired anyway
+    pub fn prune(&self, now: i64) {
+        if let Ok(mut tokens) = self.tokens.lock() {
+            tokens.retain(|_, expires_at| expires_at.map(|exp| exp > now).unwrap_or(true));
+        }
+    }
+}
+
+impl RevocationStore for MemoryRevocationStore {
+    fn is_revoked(&self, claims: &RegisteredClaims) -> bool {
+        let (tokens, subjects) = match (self.tokens.lock(), self.subjects.lock()) {
+            (Ok(tokens), Ok(subjects)) => (tokens, subjects),
+            // Poisoned lock, better be safe
+            _                          => return true
+        };
+
+        if let Some(ref jti) = claims.jti {
+            if tokens.contains_key(jti) {
+                return true;
+            }
+        }
+
+        let revoked_before = claims.sub.as_ref().and_then(|sub| subjects.get(sub)).cloned();
+        is_subject_revoked(claims, revoked_before)
+    }
+
+    fn revoke_token(&self, jti: &str, expires_at: Option<i64>) -> RevocationResult {
+        let mut tokens = self.tokens.lock().map_err(|_| "Revoked tokens lock is poisoned")?;
+        tokens.insert(jti.to_string(), expires_at);
+        Ok(())
+    }
+
+    fn revoke_subject(&self, sub: &str, before: i64) -> RevocationResult {
+        let mut subjects = self.subjects.lock().map_err(|_| "Revoked subjects lock is poisoned")?;
+        subjects.insert(sub.to_string(), before);
+        Ok(())
+    }
+}
+
+/// Shared by stores: whether a token is revoked by its subject being revoked
+/// at `revoked_before`. Tokens without `iat` can't be proven to be newer, and
+/// neither can tokens issued in the same second as the revocation, which are
+/// revoked too.
+pub fn is_subject_revoked(claims: &RegisteredClaims, revoked_before: Option<i64>) -> bool {
+    match (revoked_before, claims.iat) {
+        (Some(before), Some(iat)) => iat <= before,
+        (Some(_), None)           => true,
+        (None, _)                 => false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn claims(sub: Option<&str>, iat: Option<i64>, jti: Option<&str>) -> RegisteredClaims {
+        RegisteredClaims {
+            sub: sub.map(str::to_string),
+            iat,
+            jti: jti.map(str::to_string),
+            ..RegisteredClaims::default()
+        }
+    }
+
+    #[test]
+    fn revoked_tokens_are_rejected_by_jti() {
+        let store = MemoryRevocationStore::new();
+        store.revoke_token("a", Some(200)).unwrap();
+
+        assert!(store.is_revoked(&claims(None, Some(100), Some("a"))));
+        assert!(!store.is_revoked(&claims(None, Some(100), Some("b"))));
+        assert!(!store.is_revoked(&claims(None, Some(100), None)));
+    }
+
+    #[test]
+    fn expired_revocations_are_pruned() {
+        let store = MemoryRevocationStore::new();
+        store.revoke_token("expiring", Some(200)).unwrap();
+        store.revoke_token("forever", None).unwrap();
+
+        store.prune(199);
+        assert!(store.is_revoked(&claims(None, None, Some("expiring"))));
+
+        store.prune(200);
+        assert!(!store.is_revoked(&claims(None, None, Some("expiring"))));
+        assert!(store.is_revoked(&claims(None, None, Some("forever"))));
+    }
+
+    #[test]
+    fn subjects_are_revoked_up_to_the_revocation() {
+        let store = MemoryRevocationStore::new();
+        store.revoke_subject("1", 1000).unwrap();
+
+        assert!(store.is_revoked(&claims(Some("1"), Some(999), None)));
+        assert!(!store.is_revoked(&claims(Some("1"), Some(1001), None)));
+        assert!(!store.is_revoked(&claims(Some("2"), Some(999), None)));
+    }
+
+    #[test]
+    fn tokens_issued_in_the_second_of_the_revocation_are_revoked() {
+        let store = MemoryRevocationStore::new();
+        store.revoke_subject("1", 1000).unwrap();
+
+        assert!(store.is_revoked(&claims(Some("1"), Some(1000), None)));
+    }
+
+    #[test]
+    fn tokens_without_iat_are_revoked_with_their_subject() {
+        let store = MemoryRevocationStore::new();
+        assert!(!store.is_revoked(&claims(Some("1"), None, None)));
+
+        store.revoke_subject("1", 1000).unwrap();
+        assert!(store.is_revoked(&claims(Some("1"), None, None)));
+    }
+
+    #[test]
+    fn later_revocations_replace_earlier_ones() {
+        let store = MemoryRevocationStore::new();
+        store.revoke_subject("1", 1000).unwrap();
+        store.revoke_subject("1", 2000).unwrap();
+
+        assert!(store.is_revoked(&claims(Some("1"), Some(1500), None)));
+        assert!(!store.is_revoked(&claims(Some("1"), Some(2001), None)));
+    }
+
+    #[test]
+    fn tokens_without_a_subject_are_only_revoked_by_jti() {
+        let store = MemoryRevocationStore::new();
+        store.revoke_subject("1", 1000).unwrap();
+
+        assert!(!store.is_revoked(&claims(None, Some(999), None)));
+    }
+}
@@ -0,0 +1,53 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::de;
+use serde::Serialize;
+use serde::Serializer;
+use serde::Deserialize;
+use serde::Deserializer;
+
+/// Where tokens are looked for, written `bearer`, `cookie:<name>` or
+/// `query:<name>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JwtSource {
+    Bearer,
+    Cookie(String),
+    Query(String)
+}
+
+impl FromStr for JwtSource {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let mut parts = source.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some("bearer"), None)                           => Ok(JwtSource::Bearer),
+            (Some("cookie"), Some(name)) if !name.is_empty() => Ok(JwtSource::Cookie(name.to_string())),
+            (Some("query"), Some(name)) if !name.is_empty()  => Ok(JwtSource::Query(name.to_string())),
+            _ => Err(format!("invalid jwt source {}, should be bearer, cookie:<name> or query:<name>", source))
+        }
+    }
+}
+
+impl fmt::Display for JwtSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            JwtSource::Bearer           => f.write_str("bearer"),
+            JwtSource::Cookie(ref name) => write!(f, "cookie:{}", name),
+            JwtSource::Query(ref name)  => write!(f, "query:{}", name)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for JwtSource {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(de::Error::custom)
+    }
+}
+
+impl Serialize for JwtSource {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
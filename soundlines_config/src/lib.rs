@@ -0,0 +1,402 @@
+//! Settings of the server, the simulation and the external jobs. They are
+//! loaded in layers, each overriding the previous one:
+//!
+//! 1. the defaults below
+//! 2. `soundlines.toml`
+//! 3. `soundlines.<env>.toml`, `env` is `SOUNDLINES_ENV` or `development`
+//! 4. `soundlines.local.toml`, not checked in
+//! 5. `DATABASE_URL`, also from `.env`
+//! 6. `SOUNDLINES_<SECTION>_<KEY>` environment variables, e.g.
+//!    `SOUNDLINES_SERVER_JWT_SECRET`, and `SOUNDLINES_<SECTION>_<KEY>_FILE` to
+//!    read a secret from a file
+//!
+//! Files are looked up in `SOUNDLINES_CONFIG_DIR`, or in the closest directory
+//! up from the working directory with a `soundlines.toml`.
+
+extern crate dotenv;
+extern crate serde;
+#[macro_use] extern crate serde_derive;
+extern crate toml;
+
+mod secret;
+mod jwt_source;
+mod loader;
+
+pub use secret::Secret;
+pub use jwt_source::JwtSource;
+pub use loader::ConfigError;
+pub use loader::load;
+pub use loader::load_for;
+pub use loader::load_or_exit;
+
+/// Binaries reading the configuration, each only requires its own section
+/// besides `database`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Component {
+    Server,
+    Sim,
+    External
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub database: DatabaseConfig,
+    pub server: ServerConfig,
+    pub sim: SimConfig,
+    pub external: ExternalConfig
+}
+
+impl Config {
+    /// Problems of the sections used by the component
+    pub fn validate(&self, component: Component) -> Vec<String> {
+        let mut errors = self.database.validate();
+
+        errors.extend(match component {
+            Component::Server   => self.server.validate(),
+            Component::Sim      => self.sim.validate(),
+            Component::External => self.external.validate()
+        });
+
+        errors
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// e.g. `postgres://soundlines@localhost/soundlines`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<Secret>,
+    pub pool_size: u32
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        DatabaseConfig { url: None, pool_size: 10 }
+    }
+}
+
+impl DatabaseConfig {
+    pub fn url(&self) -> &str {
+        validated(&self.url)
+    }
+
+    fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if self.url.as_ref().map(|url| url.expose().is_empty()).unwrap_or(true) {
+            errors.push("database.url is required".to_string());
+        }
+
+        if self.pool_size == 0 {
+            errors.push("database.pool_size should be positive".to_string());
+        }
+
+        errors
+    }
+}
+
+/// RSA key of the server's tokens, the keys are PEM files
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JwtKeyConfig {
+    pub kid: String,
+    #[serde(default)]
+    pub algorithm: JwtAlgorithm,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub private_key: Option<String>,
+    pub public_key: String
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JwtAlgorithm {
+    #[serde(rename = "RS256")]
+    Rs256,
+    #[serde(rename = "RS384")]
+    Rs384,
+    #[serde(rename = "RS512")]
+    Rs512
+}
+
+impl Default for JwtAlgorithm {
+    fn default() -> Self {
+        JwtAlgorithm::Rs256
+    }
+}
+
+/// Where revoked tokens are kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JwtRevocations {
+    Database,
+    Memory,
+    /// Tokens can't be revoked, signing out and deregistering only take
+    /// effect when the tokens expire
+    None
+}
+
+/// How other users' positions are shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LocationPrivacyMode {
+    /// Centroid of their cell
+    Cell,
+    /// Center of a grid of `location_grid_meters`
+    Grid,
+    /// As reported, for development only
+    Exact
+}
+
+/// Where online users are kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PresenceStore {
+    Memory,
+    /// Also in `user_presence`, to keep online users across restarts
+    Database
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Shared with the apps, which sign their register tokens with it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwt_secret: Option<Secret>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwt_issuer: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwt_audience: Option<String>,
+    /// Seconds of access and refresh tokens, 0 for tokens which don't expire
+    pub jwt_lifetime: u64,
+    pub jwt_refresh_lifetime: u64,
+    /// Where tokens are looked for, in order
+    pub jwt_sources: Vec<JwtSource>,
+    pub jwt_revocations: JwtRevocations,
+    /// The first key signs new tokens, the rest only validate tokens signed
+    /// before a rotation
+    pub jwt_keys: Vec<JwtKeyConfig>,
+    /// Seconds since the last heartbeat after which `/ready` reports the
+    /// simulation and the external jobs as stalled
+    pub ready_simulation_max_age: i64,
+    pub ready_external_max_age: i64,
+    pub location_privacy: LocationPrivacyMode,
+    pub location_grid_meters: f64,
+    /// Seconds after their last GPS post users are considered offline
+    pub presence_ttl: i64,
+    pub presence_store: PresenceStore,
+    pub trade_distance_meters: f64,
+    /// Seconds trade offers wait for an answer
+    pub trade_offer_ttl: i64
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            jwt_secret: None,
+            jwt_issuer: None,
+            jwt_audience: None,
+            jwt_lifetime: 15 * 60,
+            jwt_refresh_lifetime: 30 * 24 * 60 * 60,
+            jwt_sources: vec![JwtSource::Bearer],
+            jwt_revocations: JwtRevocations::Database,
+            jwt_keys: Vec::new(),
+            ready_simulation_max_age: 60,
+            ready_external_max_age: 2 * 60 * 60,
+            location_privacy: LocationPrivacyMode::Cell,
+            location_grid_meters: 50.0,
+            presence_ttl: 30,
+            presence_store: PresenceStore::Memory,
+            trade_distance_meters: 50.0,
+            trade_offer_ttl: 5 * 60
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn jwt_secret(&self) -> &str {
+        validated(&self.jwt_secret)
+    }
+
+    fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if self.jwt_secret.as_ref().map(|secret| secret.expose().is_empty()).unwrap_or(true) {
+            errors.push("server.jwt_secret is required".to_string());
+        }
+
+        for (i, key) in self.jwt_keys.iter().enumerate() {
+            if key.kid.is_empty() {
+                errors.push(format!("server.jwt_keys[{}]: kid is required", i));
+            } else if self.jwt_keys[..i].iter().any(|other| other.kid == key.kid) {
+                errors.push(format!("server.jwt_keys[{}]: duplicate kid {}", i, key.kid));
+            }
+        }
+
+        if self.ready_simulation_max_age <= 0 || self.ready_external_max_age <= 0 {
+            errors.push("server.ready_simulation_max_age and ready_external_max_age should be positive".to_string());
+        }
+
+        if self.location_grid_meters <= 0.0 {
+            errors.push("server.location_grid_meters should be positive".to_string());
+        }
+
+        if self.presence_ttl <= 0 {
+            errors.push("server.presence_ttl should be positive".to_string());
+        }
+
+        if self.trade_distance_meters <= 0.0 || self.trade_offer_ttl <= 0 {
+            errors.push("server.trade_distance_meters and trade_offer_ttl should be positive".to_string());
+        }
+
+        errors
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SimConfig {
+    /// How much faster than real time the simulation runs
+    pub time_scale: f32,
+    /// How long a seed survives on the ground without blooming
+    pub seed_max_age: f32,
+    /// Size of the cells created by `gencells`, in meters
+    pub cell_size: f64,
+    /// Minutes between snapshots
+    pub snapshot_interval: u32,
+    /// Directory the snapshots are written to and served from
+    pub snapshots_dir: String
+}
+
+impl Default for SimConfig {
+    fn default() -> Self {
+        SimConfig {
+            time_scale: 1.0,
+            seed_max_age: 250.0,
+            cell_size: 50.0,
+            snapshot_interval: 60,
+            snapshots_dir: "snapshots".to_string()
+        }
+    }
+}
+
+impl SimConfig {
+    fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if self.time_scale < 1.0 {
+            errors.push("sim.time_scale should be at least 1".to_string());
+        }
+
+        if self.seed_max_age <= 0.0 || self.cell_size <= 0.0 {
+            errors.push("sim.seed_max_age and cell_size should be positive".to_string());
+        }
+
+        if self.snapshot_interval == 0 || self.snapshot_interval > 60 {
+            errors.push("sim.snapshot_interval should be 1 to 60 minutes".to_string());
+        }
+
+        errors
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExternalConfig {
+    /// Minutes between fetching the weather and the tweets
+    pub interval_minutes: u32,
+    /// Where the id of the last tweet seen is kept between runs
+    pub state_file: String,
+    /// Dark Sky API key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub forecast_token: Option<Secret>,
+    pub weather_latitude: f64,
+    pub weather_longitude: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub twitter_consumer_key: Option<Secret>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub twitter_consumer_secret: Option<Secret>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub twitter_access_token: Option<Secret>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub twitter_access_secret: Option<Secret>,
+    /// Center and radius of the area tweets are searched in
+    pub twitter_latitude: f64,
+    pub twitter_longitude: f64,
+    pub twitter_radius_km: f64
+}
+
+impl Default for ExternalConfig {
+    fn default() -> Self {
+        ExternalConfig {
+            interval_minutes: 1,
+            state_file: "external.json".to_string(),
+            forecast_token: None,
+            weather_latitude: 37.570628,
+            weather_longitude: 126.996350,
+            twitter_consumer_key: None,
+            twitter_consumer_secret: None,
+            twitter_access_token: None,
+            twitter_access_secret: None,
+            twitter_latitude: 37.570750,
+            twitter_longitude: 126.997702,
+            twitter_radius_km: 1.2
+        }
+    }
+}
+
+impl ExternalConfig {
+    fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if self.interval_minutes == 0 || self.interval_minutes > 60 {
+            errors.push("external.interval_minutes should be 1 to 60".to_string());
+        }
+
+        let secrets = [
+            ("forecast_token", &self.forecast_token),
+            ("twitter_consumer_key", &self.twitter_consumer_key),
+            ("twitter_consumer_secret", &self.twitter_consumer_secret),
+            ("twitter_access_token", &self.twitter_access_token),
+            ("twitter_access_secret", &self.twitter_access_secret)
+        ];
+
+        for &(name, secret) in secrets.iter() {
+            if secret.as_ref().map(|secret| secret.expose().is_empty()).unwrap_or(true) {
+                errors.push(format!("external.{} is required", name));
+            }
+        }
+
+        for &(name, latitude, longitude) in [("weather", self.weather_latitude, self.weather_longitude),
+                                             ("twitter", self.twitter_latitude, self.twitter_longitude)].iter() {
+            if latitude < -90.0 || latitude > 90.0 || longitude < -180.0 || longitude > 180.0 {
+                errors.push(format!("external.{}_latitude and {}_longitude should be a valid position", name, name));
+            }
+        }
+
+        if self.twitter_radius_km <= 0.0 {
+            errors.push("external.twitter_radius_km should be positive".to_string());
+        }
+
+        errors
+    }
+
+    pub fn forecast_token(&self) -> &str {
+        validated(&self.forecast_token)
+    }
+
+    /// (key, secret) of the Twitter app
+    pub fn twitter_consumer(&self) -> (&str, &str) {
+        (validated(&self.twitter_consumer_key), validated(&self.twitter_consumer_secret))
+    }
+
+    /// (token, secret) of the Twitter account
+    pub fn twitter_access(&self) -> (&str, &str) {
+        (validated(&self.twitter_access_token), validated(&self.twitter_access_secret))
+    }
+}
+
+/// Required secrets are checked by `validate`
+fn validated(secret: &Option<Secret>) -> &str {
+    secret.as_ref().map(Secret::expose).expect("Secrets should be validated before use")
+}
@@ -0,0 +1,264 @@
+use std::env;
+use std::fmt;
+use std::io;
+use std::io::Read;
+use std::fs::File;
+use std::error::Error;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+
+use dotenv::dotenv;
+use toml;
+use toml::Value;
+use toml::value::Table;
+
+use Config;
+use Component;
+
+const CONFIG_FILE: &'static str = "soundlines.toml";
+const ENV_PREFIX: &'static str = "SOUNDLINES_";
+/// Variables with the prefix which aren't settings
+const LOADER_VARS: &'static [&'static str] = &["SOUNDLINES_ENV", "SOUNDLINES_CONFIG_DIR"];
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, toml::de::Error),
+    /// The variable and what is wrong with it
+    Env(String, String),
+    Deserialize(toml::de::Error),
+    Invalid(Vec<String>)
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::Io(ref path, ref err)    => write!(f, "Failed to read {}: {}", path.display(), err),
+            ConfigError::Parse(ref path, ref err) => write!(f, "Failed to parse {}: {}", path.display(), err),
+            ConfigError::Env(ref name, ref err)   => write!(f, "Invalid {}: {}", name, err),
+            ConfigError::Deserialize(ref err)     => write!(f, "Invalid configuration: {}", err),
+            ConfigError::Invalid(ref errors)      => write!(f, "Invalid configuration: {}", errors.join(", "))
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn description(&self) -> &str {
+        "Invalid configuration"
+    }
+}
+
+/// The configuration without validating it, see the crate documentation for
+/// where it is read from
+pub fn load() -> Result<Config, ConfigError> {
+    dotenv().ok();
+
+    let mut config = Value::try_from(Config::default()).expect("Default configuration is serializable");
+    let dir = config_dir();
+    let env_name = env::var("SOUNDLINES_ENV").unwrap_or_else(|_| "development".to_string());
+
+    for name in &[CONFIG_FILE.to_string(), format!("soundlines.{}.toml", env_name), "soundlines.local.toml".to_string()] {
+        if let Some(layer) = read_file(&dir.join(name))? {
+            merge(&mut config, layer);
+        }
+    }
+
+    // Kept from before the configuration files, `.env` sets it
+    if let Ok(url) = env::var("DATABASE_URL") {
+        set(&mut config, "database", "url", Value::String(url));
+    }
+
+    let mut vars = env::vars().filter(|&(ref name, _)| name.starts_with(ENV_PREFIX)).collect::<Vec<_>>();
+    vars.sort();
+    for (name, value) in vars {
+        if !LOADER_VARS.contains(&name.as_str()) {
+            apply_env(&mut config, &name, value)?;
+        }
+    }
+
+    config.try_into().map_err(ConfigError::Deserialize)
+}
+
+/// The configuration, validated for the component
+pub fn load_for(component: Component) -> Result<Config, ConfigError> {
+    let config = load()?;
+    let errors = config.validate(component);
+
+    if errors.is_empty() {
+        Ok(config)
+    } else {
+        Err(ConfigError::Invalid(errors))
+    }
+}
+
+/// Same as `load_for`, exits with the errors when the configuration is invalid
+pub fn load_or_exit(component: Component) -> Config {
+    load_for(component).unwrap_or_else(|err| {
+        eprintln!("ERROR: {}", err);
+        process::exit(1);
+    })
+}
+
+fn config_dir() -> PathBuf {
+    if let Ok(dir) = env::var("SOUNDLINES_CONFIG_DIR") {
+        return PathBuf::from(dir);
+    }
+
+    let cwd = env::current_dir().unwrap_or_else(|_| PathBuf::from("."));
+    let mut dir = Some(cwd.as_path());
+    while let Some(current) = dir {
+        if current.join(CONFIG_FILE).is_file() {
+            return current.to_path_buf();
+        }
+
+        dir = current.parent();
+    }
+
+    cwd.clone()
+}
+
+/// `None` when there is no such file, layers are optional
+fn read_file(path: &Path) -> Result<Option<Value>, ConfigError> {
+    let mut contents = String::new();
+    match File::open(path) {
+        Ok(mut file) => file.read_to_string(&mut contents).map_err(|err| ConfigError::Io(path.to_path_buf(), err))?,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(ConfigError::Io(path.to_path_buf(), err))
+    };
+
+    contents.parse::<Value>()
+        .map(Some)
+        .map_err(|err| ConfigError::Parse(path.to_path_buf(), err))
+}
+
+/// Overrides `base` with `layer`, tables are merged key by key and every
+/// other value is replaced
+fn merge(base: &mut Value, layer: Value) {
+    match (base, layer) {
+        (&mut Value::Table(ref mut base), Value::Table(layer)) => {
+            for (key, value) in layer {
+                if base.contains_key(&key) {
+                    merge(base.get_mut(&key).expect("Checked above"), value);
+                } else {
+                    base.insert(key, value);
+                }
+            }
+        },
+        (base, layer) => *base = layer
+    }
+}
+
+fn section<'a>(config: &'a mut Value, name: &str) -> &'a mut Table {
+    let root = match *config {
+        Value::Table(ref mut root) => root,
+        _                          => unreachable!("The configuration is a table")
+    };
+
+    let section = root.entry(name.to_string()).or_insert_with(|| Value::Table(Table::new()));
+    if !section.is_table() {
+        *section = Value::Table(Table::new());
+    }
+
+    match *section {
+        Value::Table(ref mut section) => section,
+        _                             => unreachable!()
+    }
+}
+
+fn set(config: &mut Value, section_name: &str, key: &str, value: Value) {
+    section(config, section_name).insert(key.to_string(), value);
+}
+
+/// `SOUNDLINES_SERVER_JWT_LIFETIME=600` sets `jwt_lifetime` of `[server]`,
+/// parsed as the type of the value it overrides. Arrays are comma separated.
+/// `<KEY>_FILE` reads a secret from a file.
+fn apply_env(config: &mut Value, name: &str, value: String) -> Result<(), ConfigError> {
+    let setting = name[ENV_PREFIX.len()..].to_lowercase();
+    let mut parts = setting.splitn(2, '_');
+    let (section_name, key) = match (parts.next(), parts.next()) {
+        (Some(section_name), Some(key)) if !key.is_empty() => (section_name, key),
+        _ => return Err(ConfigError::Env(name.to_string(), "should be SOUNDLINES_<SECTION>_<KEY>".to_string()))
+    };
+
+    let table = section(config, section_name);
+
+    let is_secret_file = key.ends_with("_file") && !table.contains_key(key);
+    if is_secret_file {
+        let mut file = Table::new();
+        file.insert("file".to_string(), Value::String(value));
+        table.insert(key[..key.len() - "_file".len()].to_string(), Value::Table(file));
+        return Ok(());
+    }
+
+    let invalid = |expected: &str| ConfigError::Env(name.to_string(), format!("should be {}", expected));
+    let parsed = match table.get(key) {
+        Some(&Value::Integer(_)) => Value::Integer(value.trim().parse().map_err(|_| invalid("an integer"))?),
+        Some(&Value::Float(_))   => Value::Float(value.trim().parse().map_err(|_| invalid("a number"))?),
+        Some(&Value::Boolean(_)) => Value::Boolean(value.trim().parse().map_err(|_| invalid("true or false"))?),
+        Some(&Value::Array(_))   => Value::Array(value.split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(|item| Value::String(item.to_string()))
+            .collect()),
+        Some(&Value::Table(_))   => return Err(invalid("set in a file")),
+        _                        => Value::String(value)
+    };
+
+    table.insert(key.to_string(), parsed);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use JwtSource;
+
+    fn defaults() -> Value {
+        Value::try_from(Config::default()).unwrap()
+    }
+
+    #[test]
+    fn layers_override_defaults_key_by_key() {
+        let mut config = defaults();
+        merge(&mut config, "[server]\npresence_ttl = 60\n".parse().unwrap());
+
+        let config: Config = config.try_into().unwrap();
+        assert_eq!(config.server.presence_ttl, 60);
+        assert_eq!(config.server.trade_offer_ttl, 5 * 60);
+    }
+
+    #[test]
+    fn env_values_take_the_type_they_override() {
+        let mut config = defaults();
+        apply_env(&mut config, "SOUNDLINES_SERVER_JWT_LIFETIME", "600".to_string()).unwrap();
+        apply_env(&mut config, "SOUNDLINES_SERVER_JWT_SOURCES", "bearer, query:token".to_string()).unwrap();
+        apply_env(&mut config, "SOUNDLINES_EXTERNAL_STATE_FILE", "state.json".to_string()).unwrap();
+        assert!(apply_env(&mut config, "SOUNDLINES_SIM_TIME_SCALE", "fast".to_string()).is_err());
+
+        let config: Config = config.try_into().unwrap();
+        assert_eq!(config.server.jwt_lifetime, 600);
+        assert_eq!(config.server.jwt_sources, vec![JwtSource::Bearer, JwtSource::Query("token".to_string())]);
+        assert_eq!(config.external.state_file, "state.json");
+    }
+
+    #[test]
+    fn invalid_choices_fail_deserialization() {
+        let mut config = defaults();
+        apply_env(&mut config, "SOUNDLINES_SERVER_PRESENCE_STORE", "redis".to_string()).unwrap();
+        let result: Result<Config, _> = config.try_into();
+        assert!(result.is_err());
+
+        let mut config = defaults();
+        apply_env(&mut config, "SOUNDLINES_SERVER_JWT_SOURCES", "header".to_string()).unwrap();
+        let result: Result<Config, _> = config.try_into();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn missing_secrets_fail_validation() {
+        let errors = Config::default().validate(Component::Server);
+        assert!(errors.iter().any(|error| error.contains("database.url")));
+        assert!(errors.iter().any(|error| error.contains("server.jwt_secret")));
+    }
+}
@@ -0,0 +1,60 @@
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+
+use serde::de;
+use serde::Serialize;
+use serde::Serializer;
+use serde::Deserialize;
+use serde::Deserializer;
+
+/// A password, token or key. Given either in place or as `{ file = "path" }`,
+/// then the file is read and trimmed. `Debug` doesn't print it.
+#[derive(Clone, PartialEq)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new<S: Into<String>>(value: S) -> Self {
+        Secret(value.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Secret(***)")
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SecretSource {
+    Value(String),
+    File { file: String }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match SecretSource::deserialize(deserializer)? {
+            SecretSource::Value(value) => Ok(Secret(value)),
+            SecretSource::File { file } => {
+                let mut value = String::new();
+                File::open(&file)
+                    .and_then(|mut f| f.read_to_string(&mut value))
+                    .map_err(|err| de::Error::custom(format!("failed to read secret from {}: {}", file, err)))?;
+
+                Ok(Secret(value.trim().to_string()))
+            }
+        }
+    }
+}
+
+/// Only serialized as the lowest layer of the configuration
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
@@ -10,6 +10,8 @@ use std::error::Error;
 use std::result::Result as StdResult;
 use std::ops::Deref;
 
+use postgres::GenericConnection;
+
 use super::Result;
 use super::Connection;
 
@@ -88,6 +90,37 @@ pub trait SqlType {
     fn insert_fields() -> Vec<&'static str>;
 }
 
+/// Same as `QueryExtensions::insert`, usable within transactions
+pub fn insert_in<T: SqlType>(conn: &GenericConnection, value: &T) -> Result<T> {
+    let arr = value.to_sql_array();
+    let mut values_str = String::with_capacity(arr.len() * 2);
+    for i in 0..arr.len() {
+        values_str += &format!("${}{}", i + 1, if i == arr.len() -1 { "" } else { "," });
+    }
+
+    let field_names = T::insert_fields().join(",");
+
+    let query = format!("insert into {} ({}) values ({}) returning *", T::table_name(), field_names, values_str);
+    conn.query(&query, &arr)
+        .map(|rows| T::from_sql_row(rows.get(0)))
+}
+
+/// Same as `QueryExtensions::update`, usable within transactions
+pub fn update_in<T: SqlType>(conn: &GenericConnection, id: i32, value: &T) -> Result<()> {
+    let mut values = value.to_sql_array();
+    let values_len = values.len();
+    let mut values_str = "set ".to_string();
+    for (i, field) in T::insert_fields().into_iter().enumerate() {
+        values_str += &format!("{}=${}{}", field, i + 1, if i == values_len -1 { "" } else { ", " });
+    }
+
+    let query = format!("update {} {} where id=${}", T::table_name(), values_str, values_len + 1);
+    values.push(&id);
+
+    conn.execute(&query, &values)?;
+    Ok(())
+}
+
 pub trait QueryExtensions {
     fn all<T: SqlType>(&self) -> Result<Vec<T>>;
     fn first<T: SqlType>(&self) -> Result<Option<T>>;
@@ -141,17 +174,7 @@ impl QueryExtensions for Connection {
     }
 
     fn insert<T: SqlType>(&self, value: &T) -> Result<T> {
-        let arr = value.to_sql_array();
-        let mut values_str = String::with_capacity(arr.len() * 2);
-        for i in 0..arr.len() {
-            values_str += &format!("${}{}", i + 1, if i == arr.len() -1 { "" } else { "," });
-        }
-
-        let field_names = T::insert_fields().join(",");
-
-        let query = format!("insert into {} ({}) values ({}) returning *", T::table_name(), field_names, values_str);
-        self.query(&query, &arr)
-            .map(|rows| T::from_sql_row(rows.get(0)))
+        insert_in(self, value)
     }
 
     fn insert_batch<T: SqlType>(&self, values: &[T]) -> Result<()> {
@@ -198,18 +221,7 @@ impl QueryExtensions for Connection {
     }
 
     fn update<T: SqlType>(&self, id: i32, value: &T) -> Result<()> {
-        let mut values = value.to_sql_array();
-        let values_len = values.len();
-        let mut values_str = "set ".to_string();
-        for (i, field) in T::insert_fields().into_iter().enumerate() {
-            values_str += &format!("{}=${}{}", field, i + 1, if i == values_len -1 { "" } else { ", " });
-        }
-
-        let query = format!("update {} {} where id=${}", T::table_name(), values_str, values_len + 1);
-        values.push(&id);
-
-        self.execute(&query, &values)?;
-        Ok(())
+        update_in(self, id, value)
     }
 
     fn update_batch<T: SqlType>(&self, ids: &[i32], values: &[T]) -> Result<()> {
@@ -0,0 +1,114 @@
+use postgis::ewkb::Point;
+use postgres::types::ToSql;
+use chrono::prelude::*;
+
+use super::Result;
+use super::Connection;
+use super::extensions::*;
+
+/// Conditions for listing rows of a table, combined with `and`. Column names
+/// are put into the query as they are, they should never come from clients.
+#[derive(Default)]
+pub struct Filter {
+    conditions: Vec<String>,
+    values: Vec<Box<ToSql>>
+}
+
+/// A page of rows ordered by id, `has_more` tells whether there are rows
+/// after the last one
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub has_more: bool
+}
+
+impl Filter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn equals<V: ToSql + 'static>(&mut self, column: &str, value: V) {
+        self.compare(column, "=", value);
+    }
+
+    pub fn one_of<V: ToSql + 'static>(&mut self, column: &str, values: Vec<V>) {
+        let condition = format!("{} = any({})", column, self.placeholder());
+        self.conditions.push(condition);
+        self.values.push(Box::new(values));
+    }
+
+    /// Rows whose time column is at or after `time`
+    pub fn since(&mut self, column: &str, time: DateTime<Utc>) {
+        self.compare(column, ">=", time);
+    }
+
+    /// Rows with an id greater than the cursor
+    pub fn after(&mut self, cursor: i32) {
+        self.compare("id", ">", cursor);
+    }
+
+    /// Rows whose geometry intersects the box given in degrees
+    pub fn within_bbox(&mut self, column: &str, min_lng: f64, min_lat: f64, max_lng: f64, max_lat: f64) {
+        let condition = format!(
+            "st_intersects({}, st_makeenvelope({}, {}, {}, {}, 4326))",
+            column, self.placeholder(), self.placeholder_at(1), self.placeholder_at(2), self.placeholder_at(3)
+        );
+
+        self.conditions.push(condition);
+        self.values.push(Box::new(min_lng));
+        self.values.push(Box::new(min_lat));
+        self.values.push(Box::new(max_lng));
+        self.values.push(Box::new(max_lat));
+    }
+
+    /// Rows whose geometry is at most `meters` away from the center
+    pub fn within_radius(&mut self, column: &str, center: Point, meters: f64) {
+        let condition = format!(
+            "st_dwithin({}::geography, {}::geography, {})",
+            column, self.placeholder(), self.placeholder_at(1)
+        );
+
+        self.conditions.push(condition);
+        self.values.push(Box::new(center));
+        self.values.push(Box::new(meters));
+    }
+
+    /// At most `limit` rows matching the filter, ordered by id
+    pub fn page<T: SqlType>(&self, conn: &Connection, limit: i64) -> Result<Page<T>> {
+        let where_clause = if self.conditions.len() > 0 {
+            format!("where {}", self.conditions.join(" and "))
+        } else {
+            "".to_string()
+        };
+
+        // One more row than asked tells whether there is a next page
+        let query = format!("select * from {} {} order by id limit {}", T::table_name(), where_clause, self.placeholder());
+        let fetch_limit = limit + 1;
+
+        let mut values = self.values.iter().map(|value| &**value).collect::<Vec<&ToSql>>();
+        values.push(&fetch_limit);
+
+        let mut items = conn.query(&query, &values)?
+            .into_iter()
+            .map(T::from_sql_row)
+            .collect::<Vec<_>>();
+
+        let has_more = items.len() as i64 > limit;
+        items.truncate(limit as usize);
+
+        Ok(Page { items, has_more })
+    }
+
+    fn compare<V: ToSql + 'static>(&mut self, column: &str, op: &str, value: V) {
+        let condition = format!("{} {} {}", column, op, self.placeholder());
+        self.conditions.push(condition);
+        self.values.push(Box::new(value));
+    }
+
+    fn placeholder(&self) -> String {
+        self.placeholder_at(0)
+    }
+
+    fn placeholder_at(&self, offset: usize) -> String {
+        format!("${}", self.values.len() + offset + 1)
+    }
+}
@@ -1,36 +1,39 @@
-use std::env;
-
 use r2d2;
 use r2d2_postgres::PostgresConnectionManager as ConnectionManager;
 use r2d2_postgres::TlsMode;
 use postgres;
 use postgres::TlsMode as PTlsMode;
-use dotenv::dotenv;
+use soundlines_config::DatabaseConfig;
 
 pub mod models;
 pub mod extensions;
+pub mod filter;
 
 pub use self::extensions::*;
 
+/// Version of the latest migration in `migrations/`, the server is not ready
+/// until the database is migrated to it. Bump it with every new migration.
+pub const SCHEMA_VERSION: &'static str = "20171026064402";
+
 pub type Error = postgres::Error;
 pub type Result<T> = postgres::Result<T>;
 pub type Connection = postgres::Connection;
 pub type Pool = r2d2::Pool<ConnectionManager>;
 pub type PooledConnection = r2d2::PooledConnection<ConnectionManager>;
 
-pub fn init_connection() -> Connection {
-    dotenv().ok();
-
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    Connection::connect(database_url, PTlsMode::None).expect("failed to connect database")
+pub fn init_connection(config: &DatabaseConfig) -> Connection {
+    Connection::connect(config.url(), PTlsMode::None).expect("failed to connect database")
 }
 
-pub fn init_pool() -> Pool {
-    dotenv().ok();
+pub fn init_pool(config: &DatabaseConfig) -> Pool {
+    let pool_config = r2d2::Config::builder().pool_size(config.pool_size).build();
+    let manager = ConnectionManager::new(config.url(), TlsMode::None).expect("failed to connect database");
 
-    let database_url = env::var("DATABASE_URL").expect("DATABASE_URL must be set");
-    let config = r2d2::Config::default();
-    let manager = ConnectionManager::new(database_url, TlsMode::None).expect("failed to connect database");
+    r2d2::Pool::new(pool_config, manager).expect("Failed to create database pool")
+}
 
-    r2d2::Pool::new(config, manager).expect("Failed to create database pool")
+/// Version of the latest migration run on the database
+pub fn schema_version(conn: &Connection) -> Result<Option<String>> {
+    conn.query("select max(version) from __diesel_schema_migrations", &[])
+        .map(|rows| rows.get(0).get(0))
 }
@@ -0,0 +1,191 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+
+use postgres::rows::Row;
+use chrono::prelude::*;
+use serde_json;
+
+use db::Result;
+use db::Connection;
+use db::extensions::*;
+
+/// Client app settings, there is only one row of them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AppConfig {
+    /// Clients older than this are asked to upgrade
+    pub min_client_version: String,
+    pub latest_client_version: String,
+    #[serde(default)]
+    pub features: BTreeMap<String, bool>,
+    /// Message of the day shown by the clients
+    #[serde(default)]
+    pub motd: Option<String>,
+    #[serde(default = "Utc::now")]
+    pub updated_at: DateTime<Utc>
+}
+
+impl AppConfig {
+    pub fn get(conn: &Connection) -> Result<AppConfig> {
+        conn.query("select * from app_config where id = 1", &[])
+            .map(|rows| AppConfig::from_row(rows.get(0)))
+    }
+
+    pub fn save(&self, conn: &Connection) -> Result<AppConfig> {
+        let features = serde_json::to_value(&self.features).expect("Feature flags are always serializable");
+
+        conn.query(
+            "update app_config
+             set min_client_version = $1, latest_client_version = $2, features = $3, motd = $4, updated_at = now()
+             where id = 1
+             returning *",
+            &[&self.min_client_version, &self.latest_client_version, &features, &self.motd]
+        ).map(|rows| AppConfig::from_row(rows.get(0)))
+    }
+
+    /// Invalid version strings or a minimum above the latest version
+    pub fn validate(&self) -> ::std::result::Result<(), String> {
+        let min = Version::parse(&self.min_client_version)
+            .ok_or_else(|| format!("Invalid min_client_version: {}", self.min_client_version))?;
+        let latest = Version::parse(&self.latest_client_version)
+            .ok_or_else(|| format!("Invalid latest_client_version: {}", self.latest_client_version))?;
+
+        if min > latest {
+            return Err("min_client_version is newer than latest_client_version".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Whether a client of the version has to upgrade, unparsable versions
+    /// are not supported
+    pub fn is_supported(&self, client_version: &str) -> bool {
+        match (Version::parse(client_version), Version::parse(&self.min_client_version)) {
+            (Some(client), Some(min)) => client >= min,
+            (None, _)                 => false,
+            // Misconfigured minimum, better not lock every client out
+            (_, None)                 => true
+        }
+    }
+
+    fn from_row<'a>(row: Row<'a>) -> Self {
+        let features: serde_json::Value = row.get("features");
+
+        AppConfig {
+            min_client_version: row.get("min_client_version"),
+            latest_client_version: row.get("latest_client_version"),
+            features: serde_json::from_value(features).unwrap_or_default(),
+            motd: row.get("motd"),
+            updated_at: row.get("updated_at")
+        }
+    }
+}
+
+/// Dotted numeric version, e.g. `1.4.2`. Missing parts count as zero, so
+/// `1.4` equals `1.4.0`.
+#[derive(Debug, Clone, Eq)]
+pub struct Version(Vec<u32>);
+
+impl Version {
+    pub fn parse(version: &str) -> Option<Version> {
+        let parts = version.trim().split('.')
+            .map(|part| part.parse::<u32>().ok())
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Version(parts))
+    }
+
+    fn part(&self, index: usize) -> u32 {
+        self.0.get(index).cloned().unwrap_or(0)
+    }
+}
+
+impl PartialEq for Version {
+    fn eq(&self, other: &Version) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Version) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Version {
+    fn cmp(&self, other: &Version) -> Ordering {
+        let len = self.0.len().max(other.0.len());
+        (0..len)
+            .map(|i| self.part(i).cmp(&other.part(i)))
+            .find(|ordering| *ordering != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(min_client_version: &str) -> AppConfig {
+        AppConfig {
+            min_client_version: min_client_version.to_string(),
+            latest_client_version: "2.0".to_string(),
+            features: BTreeMap::new(),
+            motd: None,
+            updated_at: Utc::now()
+        }
+    }
+
+    fn version(version: &str) -> Version {
+        Version::parse(version).expect("Valid version")
+    }
+
+    #[test]
+    fn versions_compare_numerically() {
+        assert!(version("1.10") > version("1.9"));
+        assert!(version("1.9.1") < version("1.10"));
+        assert!(version("2") > version("1.99.99"));
+    }
+
+    #[test]
+    fn missing_parts_count_as_zero() {
+        assert_eq!(version("1.2"), version("1.2.0"));
+        assert_eq!(version("1"), version("1.0.0"));
+        assert!(version("1.2") < version("1.2.1"));
+    }
+
+    #[test]
+    fn invalid_versions_are_not_parsed() {
+        assert_eq!(Version::parse(""), None);
+        assert_eq!(Version::parse("   "), None);
+        assert_eq!(Version::parse("1.x"), None);
+        assert_eq!(Version::parse("v1.2"), None);
+        assert_eq!(Version::parse("1..2"), None);
+        assert_eq!(Version::parse("1.2-beta"), None);
+        assert_eq!(Version::parse("-1.2"), None);
+    }
+
+    #[test]
+    fn clients_below_the_minimum_are_not_supported() {
+        let config = config("1.9");
+
+        assert!(config.is_supported("1.10"));
+        assert!(config.is_supported("1.9.0"));
+        assert!(!config.is_supported("1.8.9"));
+        assert!(!config.is_supported(""));
+        assert!(!config.is_supported("latest"));
+    }
+
+    #[test]
+    fn misconfigured_minimum_supports_every_client() {
+        assert!(config("").is_supported("0.1"));
+        assert!(config("one").is_supported("1.0"));
+    }
+
+    #[test]
+    fn minimum_above_the_latest_is_invalid() {
+        assert!(config("1.10").validate().is_ok());
+        assert!(config("2.0.0").validate().is_ok());
+        assert!(config("2.1").validate().is_err());
+        assert!(config("1.x").validate().is_err());
+    }
+}
@@ -59,6 +59,12 @@ impl Cell {
 			.map(|rows| rows.into_iter().map(Cell::from_sql_row).collect())
 	}
 
+    /// Cells which are at most `within` meters away from the location
+    pub fn find_within(conn: &Connection, location: &Point, within: f64) -> Result<Vec<Cell>> {
+        conn.query("select * from cells where st_dwithin(geom::geography, $1::geography, $2)", &[location, &within])
+            .map(|rows| rows.into_iter().map(Cell::from_sql_row).collect())
+    }
+
     pub fn find_containing(conn: &Connection, point: &GPoint<f64>) -> Result<Option<Cell>> {
         let point = Point::new(point.x(), point.y(), Some(4326));
         conn.query("select * from cells where ST_Contains(geom, $1) LIMIT 1", &[&point])
@@ -110,6 +116,17 @@ impl Cell {
         self.to_json()
     }
 
+    /// (latitude, longitude) of the center of the cell, the mean of its
+    /// corners as cells are rectangles
+    pub fn centroid(&self) -> (f64, f64) {
+        let points = &self.geom.rings[0].points;
+        // The ring is closed, its last point is the first one again
+        let corners = if points.len() > 1 { &points[..points.len() - 1] } else { &points[..] };
+        let count = corners.len().max(1) as f64;
+
+        (corners.iter().map(|p| p.y).sum::<f64>() / count, corners.iter().map(|p| p.x).sum::<f64>() / count)
+    }
+
     pub fn find_neighbors(conn: &Connection, location: &Point, within: f64) -> Result<CellNeighbours> {
         let mut cells = HashMap::new();
         let mut entities = vec![];
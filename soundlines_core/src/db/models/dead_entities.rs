@@ -0,0 +1,127 @@
+use postgis::ewkb::Point;
+use postgres::rows::Row;
+use postgres::types::ToSql;
+use chrono::prelude::*;
+
+use serde_json::Value;
+
+use db::Result;
+use db::Connection;
+use db::extensions::*;
+use db::models::Entity;
+
+/// Last state of an owned entity before it died, kept so that players can
+/// look back at their garden
+#[derive(Debug, Clone)]
+pub struct DeadEntity {
+    pub id: i32,
+    pub owner_id: i32,
+    pub point: Point,
+    pub prefab: String,
+    pub cell_id: i32,
+    pub setting_id: i32,
+    pub nickname: String,
+    pub fitness: f32,
+    pub age: f32,
+    pub size: f32,
+    pub life_expectancy: f32,
+    pub died_at: DateTime<Utc>
+}
+
+impl DeadEntity {
+    /// Returns `None` for entities without an owner, they are not archived
+    pub fn from_entity(entity: &Entity) -> Option<Self> {
+        entity.owner_id.map(|owner_id| Self {
+            id: entity.id,
+            owner_id,
+            point: entity.point.clone(),
+            prefab: entity.prefab.clone(),
+            cell_id: entity.cell_id,
+            setting_id: entity.setting_id,
+            nickname: entity.nickname.clone(),
+            fitness: entity.fitness,
+            age: entity.age,
+            size: entity.size,
+            life_expectancy: entity.life_expectancy,
+            died_at: Utc::now()
+        })
+    }
+
+    pub fn owned_by(conn: &Connection, user_id: i32) -> Result<Vec<DeadEntity>> {
+        conn.query("select * from dead_entities where owner_id = $1 order by died_at desc", &[&user_id])
+            .map(|rows| rows.into_iter().map(DeadEntity::from_sql_row).collect())
+    }
+
+    pub fn into_json(self) -> Value {
+        json!({
+            "id": self.id,
+            "owner_id": self.owner_id,
+            "cell_id": self.cell_id,
+            "latitude": self.point.y,
+            "longitude": self.point.x,
+            "prefab": self.prefab,
+            "setting_id": self.setting_id,
+            "nickname": self.nickname,
+            "fitness": self.fitness,
+            "age": self.age,
+            "size": self.size,
+            "life_expectancy": self.life_expectancy,
+            "died_at": self.died_at
+        })
+    }
+}
+
+impl SqlType for DeadEntity {
+    fn table_name() -> &'static str { "dead_entities" }
+
+    fn from_sql_row<'a>(row: Row<'a>) -> Self {
+        Self {
+            id: row.get("id"),
+            owner_id: row.get("owner_id"),
+            point: row.get("point"),
+            prefab: row.get("prefab"),
+            cell_id: row.get("cell_id"),
+            setting_id: row.get("setting_id"),
+            nickname: row.get("nickname"),
+            fitness: row.get("fitness"),
+            age: row.get("age"),
+            size: row.get("size"),
+            life_expectancy: row.get("life_expectancy"),
+            died_at: row.get("died_at")
+        }
+    }
+
+    fn insert_fields() -> Vec<&'static str> {
+        vec![
+            "id",
+            "owner_id",
+            "point",
+            "prefab",
+            "cell_id",
+            "setting_id",
+            "nickname",
+            "fitness",
+            "age",
+            "size",
+            "life_expectancy",
+            "died_at",
+        ]
+    }
+
+    fn to_sql_array<'a>(&'a self) -> Vec<&'a ToSql> {
+        vec![
+            &self.id,
+            &self.owner_id,
+            &self.point,
+            &self.prefab,
+            &self.cell_id,
+            &self.setting_id,
+            &self.nickname,
+            &self.fitness,
+            &self.age,
+            &self.size,
+            &self.life_expectancy,
+            &self.died_at,
+        ]
+    }
+}
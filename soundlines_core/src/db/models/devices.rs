@@ -0,0 +1,185 @@
+use rand;
+use rand::Rng;
+use postgres::rows::Row;
+use postgres::types::ToSql;
+use chrono::prelude::*;
+use chrono::Duration;
+
+use db::Result;
+use db::Connection;
+use db::extensions::*;
+use db::models::User;
+use db::models::Role;
+
+/// Characters of link codes, without the ones easily mistaken for others
+const LINK_CODE_CHARS: &'static [u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+const LINK_CODE_LEN: usize = 8;
+const LINK_CODE_LIFETIME_MINS: i64 = 10;
+
+/// An installation of the app, registering again from the same device returns
+/// the same user
+#[derive(Debug, Clone, Serialize)]
+pub struct Device {
+    pub id: i32,
+    pub user_id: i32,
+    pub device_id: String,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>
+}
+
+impl Device {
+    /// Device ids are the only thing needed to get a user's tokens, so they
+    /// should be long random identifiers rather than e.g. a serial number
+    pub fn is_valid_id(device_id: &str) -> bool {
+        device_id.len() >= 16 && device_id.len() <= 128 &&
+            device_id.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    }
+
+    /// User of the device, a new player when the device is unknown or its user
+    /// deregistered. With a link code the device is moved to the user who
+    /// created the code, `None` when the code is invalid or expired.
+    pub fn register(conn: &Connection, device_id: &str, link_code: Option<&str>) -> Result<Option<User>> {
+        let tx = conn.transaction()?;
+
+        let linked_user_id = match link_code {
+            Some(code) => {
+                let user_id = tx.query(
+                    "delete from device_link_codes where code = $1 and expires_at > now() returning user_id",
+                    &[&code.to_uppercase()]
+                )?.try_get(0).map(|row| row.get::<_, i32>("user_id"));
+
+                match user_id {
+                    Some(user_id) => Some(user_id),
+                    None          => return Ok(None)
+                }
+            },
+            None => None
+        };
+
+        let current_user = tx.query(
+            "select users.* from devices inner join users on users.id = devices.user_id
+             where devices.device_id = $1 for update of devices",
+            &[&device_id]
+        )?.try_get(0).map(User::from_sql_row);
+
+        let user = match (linked_user_id, current_user) {
+            (Some(user_id), _) => {
+                let user = tx.query("select * from users where id = $1", &[&user_id])?
+                    .try_get(0)
+                    .map(User::from_sql_row);
+
+                let user = match user {
+                    Some(user) if !user.is_deregistered() => user,
+                    _ => return Ok(None)
+                };
+
+                tx.execute(
+                    "insert into devices (user_id, device_id) values ($1, $2)
+                     on conflict (device_id) do update set user_id = excluded.user_id, last_seen_at = now()",
+                    &[&user.id, &device_id]
+                )?;
+
+                user
+            },
+
+            (None, Some(user)) => {
+                let user = if user.is_deregistered() { insert_in(&tx, &User::new(Role::Player))? } else { user };
+
+                // The device row is locked above
+                tx.execute(
+                    "update devices set user_id = $1, last_seen_at = now() where device_id = $2",
+                    &[&user.id, &device_id]
+                )?;
+
+                user
+            },
+
+            (None, None) => {
+                let user = insert_in(&tx, &User::new(Role::Player))?;
+
+                // Of two first registrations of a device at once, the second
+                // waits for the first and gets its player
+                let inserted = tx.query(
+                    "insert into devices (user_id, device_id) values ($1, $2)
+                     on conflict (device_id) do nothing returning user_id",
+                    &[&user.id, &device_id]
+                )?;
+
+                if !inserted.is_empty() {
+                    user
+                } else {
+                    let winner = tx.query(
+                        "select users.* from devices inner join users on users.id = devices.user_id
+                         where devices.device_id = $1",
+                        &[&device_id]
+                    )?.try_get(0).map(User::from_sql_row);
+
+                    match winner {
+                        Some(winner) => {
+                            tx.execute("delete from users where id = $1", &[&user.id])?;
+                            winner
+                        },
+
+                        // Unlinked since
+                        None => {
+                            tx.execute("insert into devices (user_id, device_id) values ($1, $2)", &[&user.id, &device_id])?;
+                            user
+                        }
+                    }
+                }
+            }
+        };
+
+        tx.commit()?;
+        Ok(Some(user))
+    }
+
+    pub fn for_user(conn: &Connection, user_id: i32) -> Result<Vec<Device>> {
+        conn.query("select * from devices where user_id = $1 order by id", &[&user_id])
+            .map(|rows| rows.into_iter().map(Device::from_sql_row).collect())
+    }
+
+    /// Removes the device from the user, registering from it again creates a
+    /// new player. Returns whether the user had such a device.
+    pub fn unlink(conn: &Connection, user_id: i32, id: i32) -> Result<bool> {
+        conn.execute("delete from devices where id = $1 and user_id = $2", &[&id, &user_id])
+            .map(|count| count > 0)
+    }
+
+    /// One time code for registering another device as the user, valid for
+    /// 10 minutes
+    pub fn create_link_code(conn: &Connection, user_id: i32) -> Result<(String, DateTime<Utc>)> {
+        let mut rng = rand::thread_rng();
+        let code = (0..LINK_CODE_LEN)
+            .map(|_| LINK_CODE_CHARS[rng.gen_range(0, LINK_CODE_CHARS.len())] as char)
+            .collect::<String>();
+        let expires_at = Utc::now() + Duration::minutes(LINK_CODE_LIFETIME_MINS);
+
+        conn.execute("delete from device_link_codes where expires_at <= now()", &[])?;
+        conn.execute(
+            "insert into device_link_codes (code, user_id, expires_at) values ($1, $2, $3)",
+            &[&code, &user_id, &expires_at]
+        )?;
+
+        Ok((code, expires_at))
+    }
+}
+
+impl SqlType for Device {
+    fn table_name() -> &'static str { "devices" }
+
+    fn from_sql_row<'a>(row: Row<'a>) -> Self {
+        Self {
+            id: row.get("id"),
+            user_id: row.get("user_id"),
+            device_id: row.get("device_id"),
+            created_at: row.get("created_at"),
+            last_seen_at: row.get("last_seen_at")
+        }
+    }
+
+    fn insert_fields() -> Vec<&'static str> { vec!["user_id", "device_id", "created_at", "last_seen_at"] }
+    fn to_sql_array<'a>(&'a self) -> Vec<&'a ToSql> {
+        vec![&self.user_id, &self.device_id, &self.created_at, &self.last_seen_at]
+    }
+}
@@ -6,6 +6,8 @@ use postgres::types::ToSql;
 
 use serde_json::Value;
 
+use db::Result;
+use db::Connection;
 use db::extensions::*;
 use db::models::PlantSetting;
 use db::models::Dna;
@@ -24,7 +26,9 @@ pub struct Entity {
     pub age: f32,
     pub size: f32,
     pub start_mating_at: f32,
-    pub last_seed_at: f32
+    pub last_seed_at: f32,
+    /// User who planted the entity, entities grown by the simulation have none
+    pub owner_id: Option<i32>
 }
 
 impl Entity {
@@ -44,10 +48,16 @@ impl Entity {
             life_expectancy: dna.life_expectancy,
             nickname,
             start_mating_at: 0.0,
-            last_seed_at: 0.0
+            last_seed_at: 0.0,
+            owner_id: None
         }
     }
 
+    pub fn owned_by(conn: &Connection, user_id: i32) -> Result<Vec<Entity>> {
+        conn.query("select * from entities where owner_id = $1 order by id", &[&user_id])
+            .map(|rows| rows.into_iter().map(Entity::from_sql_row).collect())
+    }
+
     pub fn to_json(&self) -> Value {
         json!({
             "id": self.id as i64,
@@ -63,7 +73,8 @@ impl Entity {
             "life_expectancy": self.life_expectancy,
             "nickname": &self.nickname,
             "start_mating_at": self.start_mating_at,
-            "last_seed_at": self.last_seed_at
+            "last_seed_at": self.last_seed_at,
+            "owner_id": self.owner_id
         })
     }
 
@@ -90,6 +101,7 @@ impl SqlType for Entity {
             nickname: row.get("nickname"),
             start_mating_at: row.get("start_mating_at"),
             last_seed_at: row.get("last_seed_at"),
+            owner_id: row.get("owner_id"),
         }
     }
     
@@ -107,6 +119,7 @@ impl SqlType for Entity {
             "nickname",
             "start_mating_at",
             "last_seed_at",
+            "owner_id",
         ]
     }
 
@@ -124,6 +137,7 @@ impl SqlType for Entity {
             &self.nickname,
             &self.start_mating_at,
             &self.last_seed_at,
+            &self.owner_id,
         ]
     }
 }
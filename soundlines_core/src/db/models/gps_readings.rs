@@ -38,6 +38,11 @@ impl GpsReading {
             .map(|rows| rows.into_iter().map(GpsReading::from_sql_row).collect::<Vec<_>>())
     }
 
+    pub fn last_by_user(conn: &Connection, user_id: i32) -> Result<Option<GpsReading>> {
+        conn.query("select * from gps_readings where user_id = $1 order by created_at desc limit 1", &[&user_id])
+            .map(|rows| rows.try_get(0).map(GpsReading::from_sql_row))
+    }
+
     pub fn get_cell(&self, conn: &Connection) -> Result<Option<Cell>> {
         Cell::find_containing_core(conn, &self.point)
     }
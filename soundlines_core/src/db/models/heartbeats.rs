@@ -0,0 +1,47 @@
+use postgres::rows::Row;
+use postgres::types::ToSql;
+use chrono::prelude::*;
+
+use db::Result;
+use db::Connection;
+use db::extensions::*;
+
+/// Last time a background process reported being alive
+#[derive(Debug, Clone, Serialize)]
+pub struct Heartbeat {
+    pub name: String,
+    pub beat_at: DateTime<Utc>
+}
+
+impl Heartbeat {
+    /// `soundlines_sim simulate`, beats while ticking
+    pub const SIMULATION: &'static str = "simulation";
+    /// Weather job of `soundlines_external`
+    pub const WEATHER: &'static str = "weather";
+    /// Twitter job of `soundlines_external`
+    pub const TWITTER: &'static str = "twitter";
+
+    pub fn beat(conn: &Connection, name: &str) -> Result<()> {
+        conn.execute(
+            "insert into heartbeats (name, beat_at) values ($1, now())
+             on conflict (name) do update set beat_at = excluded.beat_at",
+            &[&name]
+        ).map(|_| ())
+    }
+
+    pub fn get(conn: &Connection, name: &str) -> Result<Option<Heartbeat>> {
+        conn.query("select * from heartbeats where name = $1", &[&name])
+            .map(|rows| rows.try_get(0).map(Heartbeat::from_sql_row))
+    }
+}
+
+impl SqlType for Heartbeat {
+    fn table_name() -> &'static str { "heartbeats" }
+
+    fn from_sql_row<'a>(row: Row<'a>) -> Self {
+        Self { name: row.get("name"), beat_at: row.get("beat_at") }
+    }
+
+    fn insert_fields() -> Vec<&'static str> { vec!["name", "beat_at"] }
+    fn to_sql_array<'a>(&'a self) -> Vec<&'a ToSql> { vec![&self.name, &self.beat_at] }
+}
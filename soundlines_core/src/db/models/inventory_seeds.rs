@@ -0,0 +1,154 @@
+use postgres::rows::Row;
+use postgres::types::ToSql;
+use postgis::ewkb::Point;
+use chrono::prelude::*;
+use serde_json::Value as JValue;
+
+use db::Result;
+use db::Connection;
+use db::extensions::*;
+use db::models::Dna;
+use db::models::Seed;
+use db::models::SeedOffer;
+use db::models::Entity;
+use db::models::PlantSetting;
+use db::models::WorldEvent;
+use db::models::UserStats;
+use db::models::StatCounter;
+
+/// A seed picked up by a user, it keeps the id it had on the ground
+#[derive(Debug, Clone)]
+pub struct InventorySeed {
+    pub seed_id: i32,
+    pub user_id: i32,
+    pub dna_id: i32,
+    pub setting_id: i32,
+    pub cell_id: i32,
+    pub prefab: String,
+    pub point: Point,
+    pub picked_at: DateTime<Utc>
+}
+
+impl InventorySeed {
+    pub fn from_seed(seed: Seed, user_id: i32) -> Self {
+        Self {
+            seed_id: seed.id.expect("Trying to pick up an unsaved seed (has no id)"),
+            user_id,
+            dna_id: seed.dna_id,
+            setting_id: seed.setting_id,
+            cell_id: seed.cell_id,
+            prefab: seed.prefab,
+            point: seed.point,
+            picked_at: Utc::now()
+        }
+    }
+
+    pub fn for_user(conn: &Connection, user_id: i32) -> Result<Vec<InventorySeed>> {
+        conn.filter::<InventorySeed>(&["user_id"], &[&user_id])
+    }
+
+    /// Moves the seed from the ground, or from the seeds offered to the user,
+    /// into the user's inventory. Returns `None` when the seed is not there
+    /// anymore, so a seed is only ever picked up by one user.
+    pub fn pickup(conn: &Connection, seed_id: i32, user_id: i32) -> Result<Option<InventorySeed>> {
+        let tx = conn.transaction()?;
+
+        let seed = tx.query("delete from seeds where id = $1 returning *", &[&seed_id])?
+            .try_get(0)
+            .map(Seed::from_sql_row);
+
+        let seed = match seed {
+            Some(seed) => Some(seed),
+            None       => SeedOffer::take(&tx, seed_id, user_id)?.map(SeedOffer::into_seed)
+        };
+
+        let seed = match seed {
+            Some(seed) => seed,
+            None       => return Ok(None)
+        };
+
+        let item = insert_in(&tx, &InventorySeed::from_seed(seed, user_id))?;
+        insert_in(&tx, &WorldEvent::pickup(&item))?;
+        UserStats::record(&tx, user_id, StatCounter::SeedsPicked, 1)?;
+        tx.commit()?;
+
+        Ok(Some(item))
+    }
+
+    /// Takes the seed with the dna out of the user's inventory and plants it
+    /// as a new entity. Returns `None` when the user doesn't hold such a seed.
+    pub fn spread(conn: &Connection, user_id: i32, dna_id: i32, location: Point, cell_id: i32, nickname: String) -> Result<Option<Entity>> {
+        let tx = conn.transaction()?;
+
+        let item = tx.query("delete from inventory_seeds where user_id = $1 and dna_id = $2 returning *", &[&user_id, &dna_id])?
+            .try_get(0)
+            .map(InventorySeed::from_sql_row);
+
+        let item = match item {
+            Some(item) => item,
+            None       => return Ok(None)
+        };
+
+        let dna = tx.query("select * from dnas where id = $1", &[&item.dna_id])?
+            .try_get(0)
+            .map(Dna::from_sql_row);
+
+        let setting = tx.query("select * from settings where id = $1", &[&item.setting_id])?
+            .try_get(0)
+            .map(PlantSetting::from_sql_row);
+
+        let (dna, setting) = match (dna, setting) {
+            (Some(dna), Some(setting)) => (dna, setting),
+            _                          => return Ok(None)
+        };
+
+        let mut entity = Entity::new(location, cell_id, &setting, &dna);
+        entity.nickname = nickname;
+        entity.owner_id = Some(user_id);
+
+        let entity = insert_in(&tx, &entity)?;
+        insert_in(&tx, &WorldEvent::spread(&item, &entity))?;
+        UserStats::record(&tx, user_id, StatCounter::SeedsSpread, 1)?;
+        tx.commit()?;
+
+        Ok(Some(entity))
+    }
+
+    pub fn into_json(self) -> JValue {
+        json!({
+            "id": self.seed_id,
+            "cell_id": self.cell_id,
+            "dna_id": self.dna_id,
+            "setting_id": self.setting_id,
+            "latitude": self.point.y,
+            "longitude": self.point.x,
+            "prefab": self.prefab,
+            "picked_at": self.picked_at
+        })
+    }
+}
+
+impl SqlType for InventorySeed {
+    fn table_name() -> &'static str { "inventory_seeds" }
+
+    fn from_sql_row<'a>(row: Row<'a>) -> Self {
+        Self {
+            seed_id: row.get("seed_id"),
+            user_id: row.get("user_id"),
+            dna_id: row.get("dna_id"),
+            setting_id: row.get("setting_id"),
+            cell_id: row.get("cell_id"),
+            prefab: row.get("prefab"),
+            point: row.get("point"),
+            picked_at: row.get("picked_at")
+        }
+    }
+
+    fn insert_fields() -> Vec<&'static str> {
+        vec![ "seed_id", "user_id", "dna_id", "setting_id", "cell_id", "prefab", "point", "picked_at" ]
+    }
+
+    fn to_sql_array<'a>(&'a self) -> Vec<&'a ToSql> {
+        vec![ &self.seed_id, &self.user_id, &self.dna_id, &self.setting_id, &self.cell_id, &self.prefab, &self.point, &self.picked_at ]
+    }
+}
@@ -19,16 +19,52 @@ pub use self::entities::*;
 mod plant_settings;
 pub use self::plant_settings::*;
 
+mod dead_entities;
+pub use self::dead_entities::*;
+
 mod dnas;
 pub use self::dnas::*;
 
 mod seeds;
 pub use self::seeds::*;
 
+mod seed_offers;
+pub use self::seed_offers::*;
+
 mod users;
 pub use self::users::*;
 
 mod weather;
 pub use self::weather::*;
 
+mod revoked_tokens;
+pub use self::revoked_tokens::*;
+
+mod inventory_seeds;
+pub use self::inventory_seeds::*;
+
+mod heartbeats;
+pub use self::heartbeats::*;
+
+mod app_config;
+pub use self::app_config::*;
+
+mod setting_versions;
+pub use self::setting_versions::*;
+
+mod world_events;
+pub use self::world_events::*;
+
+mod user_stats;
+pub use self::user_stats::*;
+
+mod devices;
+pub use self::devices::*;
+
+mod online_users;
+pub use self::online_users::*;
+
+mod trades;
+pub use self::trades::*;
+
 pub fn default_user_id() -> i32 { 1 }
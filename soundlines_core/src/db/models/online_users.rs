@@ -0,0 +1,89 @@
+use postgis::ewkb::Point;
+use chrono::prelude::*;
+
+use db::Result;
+use db::Connection;
+use db::models::UserLocation;
+
+/// Last position of a user seen online, kept in memory by the server and in
+/// `user_presence` when it persists presence
+#[derive(Debug, Clone)]
+pub struct OnlineUser {
+    pub user_id: i32,
+    pub latitude: f64,
+    pub longitude: f64,
+    /// -1 outside the grid
+    pub cell_id: i32,
+    /// (latitude, longitude) of the centroid of the cell
+    pub cell_centroid: Option<(f64, f64)>,
+    pub hidden: bool,
+    pub seen_at: DateTime<Utc>
+}
+
+impl OnlineUser {
+    pub fn location(&self) -> UserLocation {
+        UserLocation {
+            id: self.user_id,
+            latitude: self.latitude,
+            longitude: self.longitude,
+            cell_id: self.cell_id,
+            cell_centroid: self.cell_centroid
+        }
+    }
+
+    /// Great circle distance in meters
+    pub fn distance_to(&self, other: &OnlineUser) -> f64 {
+        const EARTH_RADIUS: f64 = 6_371_000.0;
+
+        let (lat1, lat2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lng = (other.longitude - self.longitude).to_radians();
+
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lng / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS * a.sqrt().asin()
+    }
+
+    pub fn save(&self, conn: &Connection) -> Result<()> {
+        let point = Point::new(self.longitude, self.latitude, Some(4326));
+        conn.execute(
+            "insert into user_presence (user_id, point, cell_id, seen_at) values ($1, $2, $3, $4)
+             on conflict (user_id) do update set point = excluded.point, cell_id = excluded.cell_id, seen_at = excluded.seen_at",
+            &[&self.user_id, &point, &self.cell_id, &self.seen_at]
+        ).map(|_| ())
+    }
+
+    pub fn remove(conn: &Connection, user_id: i32) -> Result<()> {
+        conn.execute("delete from user_presence where user_id = $1", &[&user_id])
+            .map(|_| ())
+    }
+
+    /// Users seen since `since`, e.g. to restore presence after a restart.
+    /// Older entries are deleted.
+    pub fn since(conn: &Connection, since: &DateTime<Utc>) -> Result<Vec<OnlineUser>> {
+        conn.execute("delete from user_presence where seen_at < $1", &[since])?;
+
+        let query = r#"
+            select user_presence.*, users.hidden, st_centroid(cells.geom) as centroid
+            from user_presence
+            inner join users on users.id = user_presence.user_id and users.deregistered_at is null
+            left join cells on cells.id = user_presence.cell_id
+            where user_presence.seen_at >= $1"#;
+
+        conn.query(query, &[since])
+            .map(|rows| {
+                rows.into_iter().map(|row| {
+                    let point: Point = row.get("point");
+                    let centroid: Option<Point> = row.get("centroid");
+                    OnlineUser {
+                        user_id: row.get("user_id"),
+                        latitude: point.y,
+                        longitude: point.x,
+                        cell_id: row.get("cell_id"),
+                        cell_centroid: centroid.map(|centroid| (centroid.y, centroid.x)),
+                        hidden: row.get("hidden"),
+                        seen_at: row.get("seen_at")
+                    }
+                }).collect()
+            })
+    }
+}
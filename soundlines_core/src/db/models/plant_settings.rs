@@ -1,5 +1,7 @@
 use postgres::rows::Row;
 use postgres::types::ToSql;
+use serde_json;
+use serde_json::Value as JValue;
 
 use db::extensions::*;
 
@@ -23,11 +25,92 @@ pub struct PlantSetting {
     pub crowd_distance: f32
 }
 
+/// A field which differs between two versions of a setting
+#[derive(Debug, Clone, Serialize)]
+pub struct SettingChange {
+    pub field: String,
+    pub from: JValue,
+    pub to: JValue
+}
+
 impl PlantSetting {
     pub fn find_by_prefab(prefab: &str, conn: &::db::Connection) -> ::db::Result<Option<PlantSetting>> {
         conn.query("select * from settings where prefab=$1", &[&prefab])
             .map(|rows| rows.try_get(0).map(PlantSetting::from_sql_row))
     }
+
+    /// Problems with the values, the simulation misbehaves with settings that
+    /// are out of these ranges
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if self.name.trim().is_empty() {
+            errors.push("name is empty".to_string());
+        }
+
+        if self.prefab.trim().is_empty() {
+            errors.push("prefab is empty".to_string());
+        }
+
+        let probabilities = [("birth_proba", self.birth_proba), ("bloom_proba", self.bloom_proba)];
+        for &(field, value) in probabilities.iter() {
+            if !(value >= 0.0 && value <= 1.0) {
+                errors.push(format!("{} should be between 0 and 1", field));
+            }
+        }
+
+        let positives = [
+            ("growth_limit", self.growth_limit),
+            ("life_expectancy", self.life_expectancy),
+            ("mating_freq", self.mating_freq),
+            ("mating_duration", self.mating_duration),
+            ("fruit_duration", self.fruit_duration),
+            ("mating_distance", self.mating_distance),
+            ("crowd_distance", self.crowd_distance)
+        ];
+        for &(field, value) in positives.iter() {
+            if !(value > 0.0 && value.is_finite()) {
+                errors.push(format!("{} should be positive", field));
+            }
+        }
+
+        let finites = [
+            ("wifi_sensitivity", self.wifi_sensitivity),
+            ("light_sensitivity", self.light_sensitivity),
+            ("sound_sensitivity", self.sound_sensitivity),
+            ("neighbor_tolerance", self.neighbor_tolerance)
+        ];
+        for &(field, value) in finites.iter() {
+            if !value.is_finite() {
+                errors.push(format!("{} should be a number", field));
+            }
+        }
+
+        errors
+    }
+
+    /// Fields changed from `self` to `other`, the id is not compared
+    pub fn diff(&self, other: &PlantSetting) -> Vec<SettingChange> {
+        let from = serde_json::to_value(self).expect("Settings are always serializable");
+        let to = serde_json::to_value(other).expect("Settings are always serializable");
+
+        let (from, to) = match (from, to) {
+            (JValue::Object(from), JValue::Object(to)) => (from, to),
+            _ => return Vec::new()
+        };
+
+        from.into_iter()
+            .filter(|&(ref field, _)| field != "id")
+            .filter_map(|(field, from)| {
+                let to = to.get(&field).cloned().unwrap_or(JValue::Null);
+                if from == to {
+                    None
+                } else {
+                    Some(SettingChange { field, from, to })
+                }
+            })
+            .collect()
+    }
 }
 
 impl SqlType for PlantSetting {
@@ -95,3 +178,104 @@ impl SqlType for PlantSetting {
         ]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::f32;
+
+    use super::*;
+
+    fn setting() -> PlantSetting {
+        PlantSetting {
+            id: Some(1),
+            name: "Fern".to_string(),
+            prefab: "fern".to_string(),
+            growth_limit: 2.0,
+            life_expectancy: 100.0,
+            wifi_sensitivity: 0.5,
+            light_sensitivity: -0.5,
+            sound_sensitivity: 0.0,
+            neighbor_tolerance: 3.0,
+            birth_proba: 0.5,
+            bloom_proba: 0.5,
+            mating_freq: 10.0,
+            mating_duration: 5.0,
+            fruit_duration: 5.0,
+            mating_distance: 2.0,
+            crowd_distance: 1.0
+        }
+    }
+
+    fn errors<F: FnOnce(&mut PlantSetting)>(change: F) -> Vec<String> {
+        let mut setting = setting();
+        change(&mut setting);
+        setting.validate()
+    }
+
+    #[test]
+    fn valid_settings_have_no_errors() {
+        assert!(setting().validate().is_empty());
+    }
+
+    #[test]
+    fn probabilities_include_0_and_1() {
+        assert!(errors(|s| s.birth_proba = 0.0).is_empty());
+        assert!(errors(|s| s.bloom_proba = 1.0).is_empty());
+
+        assert_eq!(errors(|s| s.birth_proba = -0.01), vec!["birth_proba should be between 0 and 1"]);
+        assert_eq!(errors(|s| s.bloom_proba = 1.01), vec!["bloom_proba should be between 0 and 1"]);
+        assert_eq!(errors(|s| s.bloom_proba = f32::NAN), vec!["bloom_proba should be between 0 and 1"]);
+        assert_eq!(errors(|s| s.birth_proba = f32::INFINITY), vec!["birth_proba should be between 0 and 1"]);
+    }
+
+    #[test]
+    fn durations_and_distances_are_positive() {
+        assert_eq!(errors(|s| s.growth_limit = 0.0), vec!["growth_limit should be positive"]);
+        assert_eq!(errors(|s| s.life_expectancy = -1.0), vec!["life_expectancy should be positive"]);
+        assert_eq!(errors(|s| s.mating_freq = f32::NAN), vec!["mating_freq should be positive"]);
+        assert_eq!(errors(|s| s.crowd_distance = f32::INFINITY), vec!["crowd_distance should be positive"]);
+        assert!(errors(|s| s.fruit_duration = f32::MIN_POSITIVE).is_empty());
+    }
+
+    #[test]
+    fn sensitivities_can_be_negative_but_not_nan() {
+        assert!(errors(|s| s.wifi_sensitivity = -10.0).is_empty());
+        assert_eq!(errors(|s| s.sound_sensitivity = f32::NAN), vec!["sound_sensitivity should be a number"]);
+        assert_eq!(errors(|s| s.neighbor_tolerance = f32::NEG_INFINITY), vec!["neighbor_tolerance should be a number"]);
+    }
+
+    #[test]
+    fn names_are_required() {
+        assert_eq!(errors(|s| { s.name = " ".to_string(); s.prefab = String::new(); }), vec!["name is empty", "prefab is empty"]);
+    }
+
+    #[test]
+    fn every_error_is_reported() {
+        assert_eq!(errors(|s| { s.birth_proba = 2.0; s.growth_limit = 0.0; }).len(), 2);
+    }
+
+    #[test]
+    fn diff_lists_changed_fields() {
+        let mut other = setting();
+        other.birth_proba = 0.25;
+        other.prefab = "fern_2".to_string();
+
+        let mut changes = setting().diff(&other);
+        changes.sort_by(|a, b| a.field.cmp(&b.field));
+        let fields = changes.iter().map(|change| change.field.as_str()).collect::<Vec<_>>();
+
+        assert_eq!(fields, vec!["birth_proba", "prefab"]);
+        assert_eq!(changes[0].from, json!(0.5));
+        assert_eq!(changes[0].to, json!(0.25));
+    }
+
+    #[test]
+    fn diff_ignores_the_id() {
+        let mut other = setting();
+        other.id = Some(2);
+        assert!(setting().diff(&other).is_empty());
+
+        other.id = None;
+        assert!(setting().diff(&other).is_empty());
+    }
+}
@@ -0,0 +1,40 @@
+use postgres::rows::Row;
+use postgres::types::ToSql;
+use chrono::prelude::*;
+
+use db::Result;
+use db::Connection;
+use db::extensions::*;
+
+pub struct RevokedToken {
+    pub jti: String,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>
+}
+
+impl RevokedToken {
+    pub fn new(jti: String, expires_at: Option<DateTime<Utc>>) -> Self {
+        RevokedToken { jti, expires_at, created_at: Utc::now() }
+    }
+
+    pub fn exists(conn: &Connection, jti: &str) -> Result<bool> {
+        conn.query("select 1 from revoked_tokens where jti = $1", &[&jti])
+            .map(|rows| rows.len() > 0)
+    }
+
+    /// Revoked tokens are kept only until they expire anyway
+    pub fn delete_expired(conn: &Connection) -> Result<u64> {
+        conn.execute("delete from revoked_tokens where expires_at < now()", &[])
+    }
+}
+
+impl SqlType for RevokedToken {
+    fn table_name() -> &'static str { "revoked_tokens" }
+
+    fn from_sql_row<'a>(row: Row<'a>) -> Self {
+        Self { jti: row.get("jti"), expires_at: row.get("expires_at"), created_at: row.get("created_at") }
+    }
+
+    fn insert_fields() -> Vec<&'static str> { vec!["jti", "expires_at", "created_at"] }
+    fn to_sql_array<'a>(&'a self) -> Vec<&'a ToSql> { vec![&self.jti, &self.expires_at, &self.created_at] }
+}
@@ -0,0 +1,102 @@
+use postgres::GenericConnection;
+use postgres::rows::Row;
+use postgres::types::ToSql;
+use postgis::ewkb::Point;
+use chrono::prelude::*;
+use serde_json::Value as JValue;
+
+use db::Result;
+use db::Connection;
+use db::extensions::*;
+use db::models::Seed;
+
+/// Seed generated for a player by `/seeds/get`. It isn't on the ground, so the
+/// simulation and the other players don't see it, and only that player can
+/// pick it up until it expires. Ids are taken from the seeds, so both are
+/// picked up by id the same way.
+#[derive(Debug, Clone)]
+pub struct SeedOffer {
+    pub id: Option<i32>,
+    pub user_id: i32,
+    pub cell_id: i32,
+    pub dna_id: i32,
+    pub setting_id: i32,
+    pub point: Point,
+    pub prefab: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>
+}
+
+impl SeedOffer {
+    pub fn new(user_id: i32, seed: Seed, expires_at: DateTime<Utc>) -> Self {
+        Self {
+            id: None,
+            user_id,
+            cell_id: seed.cell_id,
+            dna_id: seed.dna_id,
+            setting_id: seed.setting_id,
+            point: seed.point,
+            prefab: seed.prefab,
+            created_at: seed.created_at,
+            expires_at
+        }
+    }
+
+    /// Removes the offer for pickup, `None` when it isn't the user's or expired
+    pub fn take(conn: &GenericConnection, id: i32, user_id: i32) -> Result<Option<SeedOffer>> {
+        conn.query(
+            "delete from seed_offers where id = $1 and user_id = $2 and expires_at > now() returning *",
+            &[&id, &user_id]
+        ).map(|rows| rows.try_get(0).map(SeedOffer::from_sql_row))
+    }
+
+    pub fn delete_expired(conn: &Connection) -> Result<()> {
+        conn.execute("delete from seed_offers where expires_at <= now()", &[]).map(|_| ())
+    }
+
+    pub fn into_seed(self) -> Seed {
+        Seed {
+            id: self.id,
+            cell_id: self.cell_id,
+            dna_id: self.dna_id,
+            setting_id: self.setting_id,
+            point: self.point,
+            created_at: self.created_at,
+            age: 1.0,
+            prefab: self.prefab
+        }
+    }
+
+    pub fn into_json(self) -> JValue {
+        let expires_at = self.expires_at;
+        let mut json = self.into_seed().into_json();
+        json["expires_at"] = json!(expires_at);
+        json
+    }
+}
+
+impl SqlType for SeedOffer {
+    fn table_name() -> &'static str { "seed_offers" }
+
+    fn from_sql_row<'a>(row: Row<'a>) -> Self {
+        Self {
+            id: Some(row.get("id")),
+            user_id: row.get("user_id"),
+            cell_id: row.get("cell_id"),
+            dna_id: row.get("dna_id"),
+            setting_id: row.get("setting_id"),
+            point: row.get("point"),
+            prefab: row.get("prefab"),
+            created_at: row.get("created_at"),
+            expires_at: row.get("expires_at")
+        }
+    }
+
+    fn insert_fields() -> Vec<&'static str> {
+        vec![ "user_id", "cell_id", "dna_id", "setting_id", "point", "prefab", "created_at", "expires_at" ]
+    }
+
+    fn to_sql_array<'a>(&'a self) -> Vec<&'a ToSql> {
+        vec![ &self.user_id, &self.cell_id, &self.dna_id, &self.setting_id, &self.point, &self.prefab, &self.created_at, &self.expires_at ]
+    }
+}
@@ -0,0 +1,106 @@
+use postgres::rows::Row;
+use chrono::prelude::*;
+use serde_json;
+use serde_json::Value as JValue;
+
+use db::Result;
+use db::Connection;
+use db::extensions::*;
+use db::models::PlantSetting;
+
+/// A plant setting as it was after a change, every update of a setting is
+/// stored as a new version
+#[derive(Debug, Clone, Serialize)]
+pub struct SettingVersion {
+    pub id: i32,
+    pub setting_id: i32,
+    pub version: i32,
+    pub setting: PlantSetting,
+    pub author_id: Option<i32>,
+    /// Version this one restored, when created by a rollback
+    pub restored_version: Option<i32>,
+    pub created_at: DateTime<Utc>
+}
+
+/// Outcome of `SettingVersion::rollback`
+#[derive(Debug)]
+pub enum Rollback {
+    Restored(SettingVersion),
+    /// The version's setting doesn't pass `PlantSetting::validate`
+    Invalid(Vec<String>),
+    /// No such setting or version
+    NotFound
+}
+
+impl SettingVersion {
+    /// Versions of the setting, newest first
+    pub fn for_setting(conn: &Connection, setting_id: i32) -> Result<Vec<SettingVersion>> {
+        conn.query("select * from setting_versions where setting_id = $1 order by version desc", &[&setting_id])
+            .map(|rows| rows.into_iter().map(SettingVersion::from_row).collect())
+    }
+
+    pub fn get(conn: &Connection, setting_id: i32, version: i32) -> Result<Option<SettingVersion>> {
+        conn.query("select * from setting_versions where setting_id = $1 and version = $2", &[&setting_id, &version])
+            .map(|rows| rows.try_get(0).map(SettingVersion::from_row))
+    }
+
+    /// Overwrites the setting and stores it as its next version. Returns `None`
+    /// when there is no such setting.
+    pub fn record(conn: &Connection, setting_id: i32, mut setting: PlantSetting, author_id: Option<i32>, restored_version: Option<i32>) -> Result<Option<SettingVersion>> {
+        let tx = conn.transaction()?;
+
+        // Locks the setting, so concurrent updates get consecutive versions
+        let exists = tx.query("select id from settings where id = $1 for update", &[&setting_id])?
+            .try_get(0)
+            .is_some();
+
+        if !exists {
+            return Ok(None);
+        }
+
+        setting.id = Some(setting_id);
+        update_in(&tx, setting_id, &setting)?;
+
+        let data = serde_json::to_value(&setting).expect("Settings are always serializable");
+        let version = tx.query(
+            "insert into setting_versions (setting_id, version, data, author_id, restored_version)
+             select $1, coalesce(max(version), 0) + 1, $2, $3, $4 from setting_versions where setting_id = $1
+             returning *",
+            &[&setting_id, &data, &author_id, &restored_version]
+        ).map(|rows| SettingVersion::from_row(rows.get(0)))?;
+
+        tx.commit()?;
+        Ok(Some(version))
+    }
+
+    /// Restores the setting to the version, as a new version. Versions stored
+    /// before settings were validated are checked like an update.
+    pub fn rollback(conn: &Connection, setting_id: i32, version: i32, author_id: Option<i32>) -> Result<Rollback> {
+        let restored = match SettingVersion::get(conn, setting_id, version)? {
+            Some(restored) => restored,
+            None           => return Ok(Rollback::NotFound)
+        };
+
+        let errors = restored.setting.validate();
+        if !errors.is_empty() {
+            return Ok(Rollback::Invalid(errors));
+        }
+
+        SettingVersion::record(conn, setting_id, restored.setting, author_id, Some(version))
+            .map(|recorded| recorded.map(Rollback::Restored).unwrap_or(Rollback::NotFound))
+    }
+
+    fn from_row<'a>(row: Row<'a>) -> Self {
+        let data: JValue = row.get("data");
+
+        SettingVersion {
+            id: row.get("id"),
+            setting_id: row.get("setting_id"),
+            version: row.get("version"),
+            setting: serde_json::from_value(data).expect("Setting versions always hold a setting"),
+            author_id: row.get("author_id"),
+            restored_version: row.get("restored_version"),
+            created_at: row.get("created_at")
+        }
+    }
+}
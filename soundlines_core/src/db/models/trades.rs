@@ -0,0 +1,265 @@
+use std::error::Error;
+
+use postgres::rows::Row;
+use postgres::GenericConnection;
+use postgres::types::ToSql;
+use postgres::types::FromSql;
+use postgres::types::IsNull;
+use postgres::types::Type;
+use chrono::prelude::*;
+use chrono::Duration;
+
+use db::Result;
+use db::Connection;
+use db::extensions::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TradeStatus {
+    /// Waiting for the other user
+    Pending,
+    /// Seeds were exchanged
+    Accepted,
+    /// Turned down by the other user
+    Declined,
+    /// Withdrawn by the user who offered it
+    Cancelled,
+    /// Not answered in time
+    Expired,
+    /// Accepted, but one of the users didn't hold the seeds anymore
+    Failed
+}
+
+impl TradeStatus {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            TradeStatus::Pending   => "pending",
+            TradeStatus::Accepted  => "accepted",
+            TradeStatus::Declined  => "declined",
+            TradeStatus::Cancelled => "cancelled",
+            TradeStatus::Expired   => "expired",
+            TradeStatus::Failed    => "failed"
+        }
+    }
+
+    pub fn from_str(status: &str) -> Option<TradeStatus> {
+        match status {
+            "pending"   => Some(TradeStatus::Pending),
+            "accepted"  => Some(TradeStatus::Accepted),
+            "declined"  => Some(TradeStatus::Declined),
+            "cancelled" => Some(TradeStatus::Cancelled),
+            "expired"   => Some(TradeStatus::Expired),
+            "failed"    => Some(TradeStatus::Failed),
+            _           => None
+        }
+    }
+}
+
+impl FromSql for TradeStatus {
+    fn from_sql(ty: &Type, raw: &[u8]) -> ::std::result::Result<TradeStatus, Box<Error + Sync + Send>> {
+        let status = String::from_sql(ty, raw)?;
+        TradeStatus::from_str(&status).ok_or_else(|| format!("Unknown trade status: {}", status).into())
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        <String as FromSql>::accepts(ty)
+    }
+}
+
+impl ToSql for TradeStatus {
+    fn to_sql(&self, ty: &Type, out: &mut Vec<u8>) -> ::std::result::Result<IsNull, Box<Error + Sync + Send>> {
+        self.as_str().to_sql(ty, out)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        <&str as ToSql>::accepts(ty)
+    }
+
+    fn to_sql_checked(&self, ty: &Type, out: &mut Vec<u8>) -> ::std::result::Result<IsNull, Box<Error + Sync + Send>> {
+        self.as_str().to_sql_checked(ty, out)
+    }
+}
+
+/// An offer of seeds from the inventory of one user for seeds of another,
+/// either side can be empty to give or ask for seeds
+#[derive(Debug, Clone, Serialize)]
+pub struct Trade {
+    pub id: i32,
+    pub from_user_id: i32,
+    pub to_user_id: i32,
+    pub offered_seed_ids: Vec<i32>,
+    pub requested_seed_ids: Vec<i32>,
+    pub status: TradeStatus,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>
+}
+
+impl Trade {
+    pub fn new(from_user_id: i32, to_user_id: i32, offered_seed_ids: Vec<i32>, requested_seed_ids: Vec<i32>, ttl: Duration) -> Self {
+        let now = Utc::now();
+        Trade {
+            id: -1,
+            from_user_id,
+            to_user_id,
+            offered_seed_ids,
+            requested_seed_ids,
+            status: TradeStatus::Pending,
+            created_at: now,
+            expires_at: now + ttl,
+            resolved_at: None
+        }
+    }
+
+    pub fn get(conn: &Connection, id: i32) -> Result<Option<Trade>> {
+        conn.query("select * from trades where id = $1", &[&id])
+            .map(|rows| rows.try_get(0).map(Trade::from_sql_row))
+    }
+
+    /// Stores the offer, `None` when the users don't hold the seeds. They are
+    /// checked again on acceptance.
+    pub fn offer(conn: &Connection, trade: &Trade) -> Result<Option<Trade>> {
+        if !holds(conn, trade.from_user_id, &trade.offered_seed_ids)? || !holds(conn, trade.to_user_id, &trade.requested_seed_ids)? {
+            return Ok(None);
+        }
+
+        conn.insert(trade).map(Some)
+    }
+
+    /// Pending offers made by or to the user, newest first
+    pub fn pending_for(conn: &Connection, user_id: i32) -> Result<Vec<Trade>> {
+        Trade::expire(conn)?;
+        conn.query(
+            "select * from trades where (from_user_id = $1 or to_user_id = $1) and status = 'pending'
+             order by created_at desc",
+            &[&user_id]
+        ).map(|rows| rows.into_iter().map(Trade::from_sql_row).collect())
+    }
+
+    /// Resolved trades of the user, newest first
+    pub fn history(conn: &Connection, user_id: i32, limit: i64) -> Result<Vec<Trade>> {
+        Trade::expire(conn)?;
+        conn.query(
+            "select * from trades where (from_user_id = $1 or to_user_id = $1) and status != 'pending'
+             order by resolved_at desc, id desc limit $2",
+            &[&user_id, &limit]
+        ).map(|rows| rows.into_iter().map(Trade::from_sql_row).collect())
+    }
+
+    /// Marks the pending offers past their expiry as expired
+    pub fn expire(conn: &Connection) -> Result<u64> {
+        conn.execute(
+            "update trades set status = 'expired', resolved_at = expires_at where status = 'pending' and expires_at <= now()",
+            &[]
+        )
+    }
+
+    /// Exchanges the seeds of a pending offer made to the user, all of them
+    /// or none. The trade is `Failed` when a user doesn't hold its seeds
+    /// anymore, `None` when there is no such pending offer.
+    pub fn accept(conn: &Connection, id: i32, user_id: i32) -> Result<Option<Trade>> {
+        Trade::expire(conn)?;
+        let tx = conn.transaction()?;
+
+        let trade = tx.query(
+            "select * from trades where id = $1 and to_user_id = $2 and status = 'pending' for update",
+            &[&id, &user_id]
+        )?.try_get(0).map(Trade::from_sql_row);
+
+        let trade = match trade {
+            Some(trade) => trade,
+            None        => return Ok(None)
+        };
+
+        // Locks the seeds, so they can't be spread or traded away meanwhile
+        let mut seed_ids = trade.offered_seed_ids.clone();
+        seed_ids.extend(trade.requested_seed_ids.iter().cloned());
+        tx.execute("select 1 from inventory_seeds where seed_id = any($1) for update", &[&seed_ids])?;
+
+        let status = if holds(&tx, trade.from_user_id, &trade.offered_seed_ids)? && holds(&tx, trade.to_user_id, &trade.requested_seed_ids)? {
+            tx.execute(
+                "update inventory_seeds set user_id = $1 where seed_id = any($2)",
+                &[&trade.to_user_id, &trade.offered_seed_ids]
+            )?;
+            tx.execute(
+                "update inventory_seeds set user_id = $1 where seed_id = any($2)",
+                &[&trade.from_user_id, &trade.requested_seed_ids]
+            )?;
+
+            TradeStatus::Accepted
+        } else {
+            TradeStatus::Failed
+        };
+
+        let trade = resolve(&tx, id, status)?;
+        tx.commit()?;
+
+        Ok(trade)
+    }
+
+    /// Turns down a pending offer made to the user
+    pub fn decline(conn: &Connection, id: i32, user_id: i32) -> Result<Option<Trade>> {
+        Trade::expire(conn)?;
+        conn.query(
+            "update trades set status = 'declined', resolved_at = now()
+             where id = $1 and to_user_id = $2 and status = 'pending' returning *",
+            &[&id, &user_id]
+        ).map(|rows| rows.try_get(0).map(Trade::from_sql_row))
+    }
+
+    /// Withdraws a pending offer made by the user
+    pub fn cancel(conn: &Connection, id: i32, user_id: i32) -> Result<Option<Trade>> {
+        Trade::expire(conn)?;
+        conn.query(
+            "update trades set status = 'cancelled', resolved_at = now()
+             where id = $1 and from_user_id = $2 and status = 'pending' returning *",
+            &[&id, &user_id]
+        ).map(|rows| rows.try_get(0).map(Trade::from_sql_row))
+    }
+}
+
+/// Whether the user holds every one of the seeds
+fn holds(conn: &GenericConnection, user_id: i32, seed_ids: &[i32]) -> Result<bool> {
+    if seed_ids.is_empty() {
+        return Ok(true);
+    }
+
+    conn.query(
+        "select count(*) as count from inventory_seeds where user_id = $1 and seed_id = any($2)",
+        &[&user_id, &seed_ids]
+    ).map(|rows| rows.get(0).get::<_, i64>("count") == seed_ids.len() as i64)
+}
+
+fn resolve(conn: &GenericConnection, id: i32, status: TradeStatus) -> Result<Option<Trade>> {
+    conn.query(
+        "update trades set status = $1, resolved_at = now() where id = $2 returning *",
+        &[&status, &id]
+    ).map(|rows| rows.try_get(0).map(Trade::from_sql_row))
+}
+
+impl SqlType for Trade {
+    fn table_name() -> &'static str { "trades" }
+
+    fn from_sql_row<'a>(row: Row<'a>) -> Self {
+        Self {
+            id: row.get("id"),
+            from_user_id: row.get("from_user_id"),
+            to_user_id: row.get("to_user_id"),
+            offered_seed_ids: row.get("offered_seed_ids"),
+            requested_seed_ids: row.get("requested_seed_ids"),
+            status: row.get("status"),
+            created_at: row.get("created_at"),
+            expires_at: row.get("expires_at"),
+            resolved_at: row.get("resolved_at")
+        }
+    }
+
+    fn insert_fields() -> Vec<&'static str> {
+        vec!["from_user_id", "to_user_id", "offered_seed_ids", "requested_seed_ids", "status", "created_at", "expires_at", "resolved_at"]
+    }
+
+    fn to_sql_array<'a>(&'a self) -> Vec<&'a ToSql> {
+        vec![&self.from_user_id, &self.to_user_id, &self.offered_seed_ids, &self.requested_seed_ids,
+             &self.status, &self.created_at, &self.expires_at, &self.resolved_at]
+    }
+}
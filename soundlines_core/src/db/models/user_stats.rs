@@ -0,0 +1,263 @@
+use chrono::prelude::*;
+use chrono::Duration;
+use postgres::GenericConnection;
+use postgres::types::ToSql;
+
+use db::Result;
+use db::Connection;
+
+/// Counters kept per user and day, incremented as the user plays
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatCounter {
+    WifiReadings,
+    SoundReadings,
+    LightReadings,
+    GpsReadings,
+    SeedsPicked,
+    SeedsSpread
+}
+
+impl StatCounter {
+    fn column(&self) -> &'static str {
+        match *self {
+            StatCounter::WifiReadings  => "wifi_readings",
+            StatCounter::SoundReadings => "sound_readings",
+            StatCounter::LightReadings => "light_readings",
+            StatCounter::GpsReadings   => "gps_readings",
+            StatCounter::SeedsPicked   => "seeds_picked",
+            StatCounter::SeedsSpread   => "seeds_spread"
+        }
+    }
+}
+
+/// Time window of statistics and leaderboards, days are in UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatsWindow {
+    /// Today
+    Daily,
+    /// Today and the 6 days before
+    Weekly,
+    AllTime
+}
+
+impl StatsWindow {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            StatsWindow::Daily   => "daily",
+            StatsWindow::Weekly  => "weekly",
+            StatsWindow::AllTime => "all-time"
+        }
+    }
+
+    pub fn from_str(window: &str) -> Option<StatsWindow> {
+        match window {
+            "daily"    => Some(StatsWindow::Daily),
+            "weekly"   => Some(StatsWindow::Weekly),
+            "all-time" => Some(StatsWindow::AllTime),
+            _          => None
+        }
+    }
+
+    /// First day in the window
+    fn since(&self) -> NaiveDate {
+        let today = Utc::today().naive_utc();
+        match *self {
+            StatsWindow::Daily   => today,
+            StatsWindow::Weekly  => today - Duration::days(6),
+            StatsWindow::AllTime => NaiveDate::from_ymd(1970, 1, 1)
+        }
+    }
+}
+
+/// What leaderboards rank users by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeaderboardMetric {
+    /// Readings of every sensor
+    Readings,
+    CellsVisited,
+    SeedsPicked,
+    SeedsSpread,
+    /// Plants of the user alive now, regardless of the window
+    SurvivingPlants
+}
+
+impl LeaderboardMetric {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            LeaderboardMetric::Readings        => "readings",
+            LeaderboardMetric::CellsVisited    => "cells_visited",
+            LeaderboardMetric::SeedsPicked     => "seeds_picked",
+            LeaderboardMetric::SeedsSpread     => "seeds_spread",
+            LeaderboardMetric::SurvivingPlants => "surviving_plants"
+        }
+    }
+
+    pub fn from_str(metric: &str) -> Option<LeaderboardMetric> {
+        match metric {
+            "readings"         => Some(LeaderboardMetric::Readings),
+            "cells_visited"    => Some(LeaderboardMetric::CellsVisited),
+            "seeds_picked"     => Some(LeaderboardMetric::SeedsPicked),
+            "seeds_spread"     => Some(LeaderboardMetric::SeedsSpread),
+            "surviving_plants" => Some(LeaderboardMetric::SurvivingPlants),
+            _                  => None
+        }
+    }
+
+    fn is_windowed(&self) -> bool {
+        *self != LeaderboardMetric::SurvivingPlants
+    }
+
+    /// Totals by user, `$1` is the first day of the window for windowed metrics
+    fn query(&self) -> &'static str {
+        match *self {
+            LeaderboardMetric::Readings => "
+                select user_id, sum(wifi_readings + sound_readings + light_readings + gps_readings)::bigint as value
+                from user_daily_stats where day >= $1 group by user_id",
+            LeaderboardMetric::CellsVisited => "
+                select user_id, count(distinct cell_id) as value
+                from user_cell_visits where day >= $1 group by user_id",
+            LeaderboardMetric::SeedsPicked => "
+                select user_id, sum(seeds_picked)::bigint as value
+                from user_daily_stats where day >= $1 group by user_id",
+            LeaderboardMetric::SeedsSpread => "
+                select user_id, sum(seeds_spread)::bigint as value
+                from user_daily_stats where day >= $1 group by user_id",
+            LeaderboardMetric::SurvivingPlants => "
+                select owner_id as user_id, count(*) as value
+                from entities where owner_id is not null group by owner_id"
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LeaderboardEntry {
+    pub rank: i64,
+    pub user_id: i32,
+    pub value: i64
+}
+
+/// Oldest plant a user planted, dead or alive
+#[derive(Debug, Clone, Serialize)]
+pub struct LongestLivingPlant {
+    pub entity_id: i32,
+    pub nickname: String,
+    pub prefab: String,
+    pub age: f32,
+    pub alive: bool
+}
+
+/// Statistics of a user over a window
+#[derive(Debug, Clone, Serialize)]
+pub struct UserStats {
+    pub user_id: i32,
+    pub wifi_readings: i64,
+    pub sound_readings: i64,
+    pub light_readings: i64,
+    pub gps_readings: i64,
+    pub cells_visited: i64,
+    pub seeds_picked: i64,
+    pub seeds_spread: i64,
+    /// Plants of the user alive now, regardless of the window
+    pub surviving_plants: i64,
+    /// Regardless of the window
+    pub longest_living_plant: Option<LongestLivingPlant>
+}
+
+impl UserStats {
+    /// Adds `count` to today's counter of the user
+    pub fn record(conn: &GenericConnection, user_id: i32, counter: StatCounter, count: i32) -> Result<()> {
+        let query = format!(
+            "insert into user_daily_stats (user_id, day, {column}) values ($1, (now() at time zone 'utc')::date, $2)
+             on conflict (user_id, day) do update set {column} = user_daily_stats.{column} + excluded.{column}",
+            column = counter.column()
+        );
+
+        conn.execute(&query, &[&user_id, &count]).map(|_| ())
+    }
+
+    /// Notes that the user was in the cell today
+    pub fn visit(conn: &GenericConnection, user_id: i32, cell_id: i32) -> Result<()> {
+        conn.execute(
+            "insert into user_cell_visits (user_id, cell_id, day) values ($1, $2, (now() at time zone 'utc')::date)
+             on conflict do nothing",
+            &[&user_id, &cell_id]
+        ).map(|_| ())
+    }
+
+    pub fn for_user(conn: &Connection, user_id: i32, window: StatsWindow) -> Result<UserStats> {
+        let since = window.since();
+
+        let counters = conn.query(
+            "select coalesce(sum(wifi_readings), 0)::bigint as wifi_readings,
+                    coalesce(sum(sound_readings), 0)::bigint as sound_readings,
+                    coalesce(sum(light_readings), 0)::bigint as light_readings,
+                    coalesce(sum(gps_readings), 0)::bigint as gps_readings,
+                    coalesce(sum(seeds_picked), 0)::bigint as seeds_picked,
+                    coalesce(sum(seeds_spread), 0)::bigint as seeds_spread
+             from user_daily_stats where user_id = $1 and day >= $2",
+            &[&user_id, &since]
+        )?;
+        let counters = counters.get(0);
+
+        let cells_visited: i64 = conn.query(
+            "select count(distinct cell_id) from user_cell_visits where user_id = $1 and day >= $2",
+            &[&user_id, &since]
+        )?.get(0).get(0);
+
+        let surviving_plants: i64 = conn.query("select count(*) from entities where owner_id = $1", &[&user_id])?
+            .get(0)
+            .get(0);
+
+        let longest_living_plant = conn.query(
+            "select id, nickname, prefab, age, true as alive from entities where owner_id = $1
+             union all
+             select id, nickname, prefab, age, false as alive from dead_entities where owner_id = $1
+             order by age desc limit 1",
+            &[&user_id]
+        )?.into_iter().next().map(|row| LongestLivingPlant {
+            entity_id: row.get("id"),
+            nickname: row.get("nickname"),
+            prefab: row.get("prefab"),
+            age: row.get("age"),
+            alive: row.get("alive")
+        });
+
+        Ok(UserStats {
+            user_id,
+            wifi_readings: counters.get("wifi_readings"),
+            sound_readings: counters.get("sound_readings"),
+            light_readings: counters.get("light_readings"),
+            gps_readings: counters.get("gps_readings"),
+            cells_visited,
+            seeds_picked: counters.get("seeds_picked"),
+            seeds_spread: counters.get("seeds_spread"),
+            surviving_plants,
+            longest_living_plant
+        })
+    }
+
+    /// Top `limit` users by the metric over the window, users with equal
+    /// values share a rank
+    pub fn leaderboard(conn: &Connection, metric: LeaderboardMetric, window: StatsWindow, limit: i64) -> Result<Vec<LeaderboardEntry>> {
+        let since = window.since();
+        let (params, limit_placeholder): (Vec<&ToSql>, _) = if metric.is_windowed() {
+            (vec![&since as &ToSql, &limit], "$2")
+        } else {
+            (vec![&limit as &ToSql], "$1")
+        };
+
+        let query = format!(
+            "select rank() over (order by value desc) as rank, user_id, value
+             from ({}) totals where value > 0
+             order by value desc, user_id limit {}",
+            metric.query(), limit_placeholder
+        );
+
+        conn.query(&query, &params)
+            .map(|rows| rows.into_iter().map(|row| LeaderboardEntry {
+                rank: row.get("rank"),
+                user_id: row.get("user_id"),
+                value: row.get("value")
+            }).collect())
+    }
+}
@@ -1,26 +1,159 @@
+use std::error::Error;
+
 use postgres::rows::Row;
 use postgres::types::ToSql;
+use postgres::types::FromSql;
+use postgres::types::IsNull;
+use postgres::types::Type;
 use chrono::prelude::*;
 
 use db::Result;
 use db::Connection;
 use db::extensions::*;
 
-#[derive(Serialize, Deserialize)]
+/// Roles are ordered by privilege, a role is allowed to do everything the
+/// roles before it can do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Player,
+    Curator,
+    Admin
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Role::Player  => "player",
+            Role::Curator => "curator",
+            Role::Admin   => "admin"
+        }
+    }
+
+    pub fn from_str(role: &str) -> Option<Role> {
+        match role {
+            "player"  => Some(Role::Player),
+            "curator" => Some(Role::Curator),
+            "admin"   => Some(Role::Admin),
+            _         => None
+        }
+    }
+}
+
+impl Default for Role {
+    fn default() -> Self {
+        Role::Player
+    }
+}
+
+impl FromSql for Role {
+    fn from_sql(ty: &Type, raw: &[u8]) -> ::std::result::Result<Role, Box<Error + Sync + Send>> {
+        let role = String::from_sql(ty, raw)?;
+        Role::from_str(&role).ok_or_else(|| format!("Unknown role: {}", role).into())
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        <String as FromSql>::accepts(ty)
+    }
+}
+
+impl ToSql for Role {
+    fn to_sql(&self, ty: &Type, out: &mut Vec<u8>) -> ::std::result::Result<IsNull, Box<Error + Sync + Send>> {
+        self.as_str().to_sql(ty, out)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        <&str as ToSql>::accepts(ty)
+    }
+
+    fn to_sql_checked(&self, ty: &Type, out: &mut Vec<u8>) -> ::std::result::Result<IsNull, Box<Error + Sync + Send>> {
+        self.as_str().to_sql_checked(ty, out)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
     pub id: i32,
-    pub created_at: DateTime<Utc>
+    pub created_at: DateTime<Utc>,
+    // Tokens issued before roles were introduced don't carry it
+    #[serde(default)]
+    pub role: Role,
+    #[serde(skip)]
+    pub tokens_revoked_at: Option<DateTime<Utc>>,
+    #[serde(skip)]
+    pub deregistered_at: Option<DateTime<Utc>>
 }
 
 impl SqlType for User {
     fn table_name() -> &'static str { "users" }
 
     fn from_sql_row<'a>(row: Row<'a>) -> Self {
-        Self { id: row.get("id"), created_at: row.get("created_at") }
+        Self {
+            id: row.get("id"),
+            created_at: row.get("created_at"),
+            role: row.get("role"),
+            tokens_revoked_at: row.get("tokens_revoked_at"),
+            deregistered_at: row.get("deregistered_at")
+        }
+    }
+
+    fn insert_fields() -> Vec<&'static str> { vec!["created_at", "role", "tokens_revoked_at", "deregistered_at"] }
+    fn to_sql_array<'a>(&'a self) -> Vec<&'a ToSql> {
+        vec![&self.created_at, &self.role, &self.tokens_revoked_at, &self.deregistered_at]
+    }
+}
+
+/// How a user is shown to others, every field is optional
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UserProfile {
+    #[serde(default)]
+    pub display_name: Option<String>,
+    /// `#rrggbb`
+    #[serde(default)]
+    pub avatar_color: Option<String>,
+    /// Language tag, e.g. `ja` or `en-US`
+    #[serde(default)]
+    pub language: Option<String>
+}
+
+impl UserProfile {
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+
+        if let Some(ref name) = self.display_name {
+            let length = name.trim().chars().count();
+            if length == 0 || length > 32 {
+                errors.push("display_name should be 1 to 32 characters".to_string());
+            }
+        }
+
+        if let Some(ref color) = self.avatar_color {
+            let valid = color.len() == 7 && color.starts_with('#') && color[1..].chars().all(|c| c.is_digit(16));
+            if !valid {
+                errors.push("avatar_color should be like #a0b1c2".to_string());
+            }
+        }
+
+        if let Some(ref language) = self.language {
+            let mut parts = language.split('-');
+            let primary_valid = parts.next()
+                .map(|primary| primary.len() >= 2 && primary.len() <= 3 && primary.chars().all(|c| c.is_alphabetic()))
+                .unwrap_or(false);
+            let rest_valid = parts.all(|part| part.len() >= 2 && part.len() <= 8 && part.chars().all(|c| c.is_alphanumeric()));
+
+            if language.len() > 16 || !primary_valid || !rest_valid || !language.bytes().all(|b| b < 128) {
+                errors.push("language should be a language tag like en or ja-JP".to_string());
+            }
+        }
+
+        errors
     }
+}
 
-    fn insert_fields() -> Vec<&'static str> { vec!["created_at"] }
-    fn to_sql_array<'a>(&'a self) -> Vec<&'a ToSql> { vec![&self.created_at] }
+/// Whether the user is shown to other players
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UserPrivacy {
+    pub hidden: bool
 }
 
 #[derive(Serialize, Deserialize)]
@@ -28,10 +161,110 @@ pub struct UserLocation {
     pub id: i32,
     pub latitude: f64,
     pub longitude: f64,
-    pub cell_id: i32
+    pub cell_id: i32,
+    /// (latitude, longitude) of the centroid of the cell, `None` outside the grid
+    #[serde(skip)]
+    pub cell_centroid: Option<(f64, f64)>
 }
 
 impl User {
+    pub fn new(role: Role) -> Self {
+        User { id: -1, created_at: Utc::now(), role, tokens_revoked_at: None, deregistered_at: None }
+    }
+
+    pub fn is_deregistered(&self) -> bool {
+        self.deregistered_at.is_some()
+    }
+
+    /// Whether the user exists and isn't deregistered, without loading it
+    pub fn is_active(conn: &Connection, id: i32) -> Result<bool> {
+        conn.query("select 1 from users where id = $1 and deregistered_at is null", &[&id])
+            .map(|rows| !rows.is_empty())
+    }
+
+    pub fn tokens_revoked_at(conn: &Connection, id: i32) -> Result<Option<DateTime<Utc>>> {
+        conn.query("select tokens_revoked_at from users where id = $1", &[&id])
+            .map(|rows| rows.try_get(0).and_then(|row| row.get("tokens_revoked_at")))
+    }
+
+    /// Tokens of the user issued before `before` are no longer accepted
+    pub fn revoke_tokens(conn: &Connection, id: i32, before: &DateTime<Utc>) -> Result<()> {
+        conn.execute("update users set tokens_revoked_at = $1 where id = $2", &[before, &id])
+            .map(|_| ())
+    }
+
+    pub fn deregister(conn: &Connection, id: i32) -> Result<()> {
+        conn.execute("update users set deregistered_at = now(), tokens_revoked_at = now() where id = $1", &[&id])
+            .map(|_| ())
+    }
+
+    pub fn profile(conn: &Connection, id: i32) -> Result<Option<UserProfile>> {
+        conn.query("select display_name, avatar_color, language from users where id = $1", &[&id])
+            .map(|rows| rows.try_get(0).map(|row| UserProfile {
+                display_name: row.get("display_name"),
+                avatar_color: row.get("avatar_color"),
+                language: row.get("language")
+            }))
+    }
+
+    /// Replaces the profile, names are trimmed and colors lowercased
+    pub fn update_profile(conn: &Connection, id: i32, profile: &UserProfile) -> Result<()> {
+        let display_name = profile.display_name.as_ref().map(|name| name.trim().to_string());
+        let avatar_color = profile.avatar_color.as_ref().map(|color| color.to_lowercase());
+
+        conn.execute(
+            "update users set display_name = $1, avatar_color = $2, language = $3 where id = $4",
+            &[&display_name, &avatar_color, &profile.language, &id]
+        ).map(|_| ())
+    }
+
+    pub fn privacy(conn: &Connection, id: i32) -> Result<Option<UserPrivacy>> {
+        conn.query("select hidden from users where id = $1", &[&id])
+            .map(|rows| rows.try_get(0).map(|row| UserPrivacy { hidden: row.get("hidden") }))
+    }
+
+    pub fn update_privacy(conn: &Connection, id: i32, privacy: &UserPrivacy) -> Result<()> {
+        conn.execute("update users set hidden = $1 where id = $2", &[&privacy.hidden, &id])
+            .map(|_| ())
+    }
+
+    /// Removes the readings, last position, inventory, statistics, devices,
+    /// trades and profile of the user, and the archive of its dead plants.
+    /// Living plants and world events are kept for the other players but no
+    /// longer point to the user.
+    pub fn delete_data(conn: &Connection, id: i32) -> Result<()> {
+        let tx = conn.transaction()?;
+
+        for table in &["wifi_readings", "sound_readings", "light_readings", "gps_readings",
+                       "inventory_seeds", "seed_offers", "user_daily_stats", "user_cell_visits",
+                       "devices", "device_link_codes", "user_presence"] {
+            tx.execute(&format!("delete from {} where user_id = $1", table), &[&id])?;
+        }
+
+        tx.execute("delete from dead_entities where owner_id = $1", &[&id])?;
+        tx.execute("delete from trades where from_user_id = $1 or to_user_id = $1", &[&id])?;
+        tx.execute("update entities set owner_id = null where owner_id = $1", &[&id])?;
+        tx.execute(
+            "update world_events set payload = payload #- '{entity,owner_id}'
+             where payload->'entity'->'owner_id' = to_jsonb($1::integer)",
+            &[&id]
+        )?;
+        tx.execute("update world_events set user_id = null where user_id = $1", &[&id])?;
+        tx.execute(
+            "update users set display_name = null, avatar_color = null, language = null where id = $1",
+            &[&id]
+        )?;
+
+        tx.commit()
+    }
+
+    pub fn set_role(conn: &Connection, id: i32, role: Role) -> Result<Option<User>> {
+        conn.query("update users set role = $1 where id = $2 returning *", &[&role, &id])
+            .map(|rows| rows.try_get(0).map(User::from_sql_row))
+    }
+
+    /// Users with a GPS reading in the last 30 seconds, for processes without
+    /// the server's presence tracking
     pub fn get_all_locations(conn: &Connection, except: Option<i32>) -> Result<Vec<UserLocation>> {
         use chrono::Duration;
         use postgis::ewkb::Point;
@@ -41,20 +274,55 @@ impl User {
         let three_seconds_before = Utc::now() - Duration::seconds(30) - Duration::milliseconds(10);
 
 		let query = r#"
-		    select DISTINCT ON (gps_readings.user_id) user_id, gps_readings.created_at, gps_readings.point, cells.id as cell_id from gps_readings
+		    select DISTINCT ON (gps_readings.user_id) user_id, gps_readings.created_at, gps_readings.point, cells.id as cell_id,
+		        st_centroid(cells.geom) as centroid
+		    from gps_readings
 		    inner join cells on st_contains(cells.geom, gps_readings.point)
-		    where created_at >= $1 and user_id != $2
-		    order by gps_readings.user_id asc, created_at desc"#;
+		    inner join users on users.id = gps_readings.user_id
+		    where gps_readings.created_at >= $1 and user_id != $2
+		        and not users.hidden and users.deregistered_at is null
+		    order by gps_readings.user_id asc, gps_readings.created_at desc"#;
 
         conn.query(query, &[&three_seconds_before, &except_id])
             .map(|rows| {
                 rows.into_iter().map(|row| {
                     let point: Point = row.get("point");
+                    let centroid: Point = row.get("centroid");
+                    UserLocation {
+                        id: row.get("user_id"),
+                        latitude: point.y,
+                        longitude: point.x,
+                        cell_id: row.get("cell_id"),
+                        cell_centroid: Some((centroid.y, centroid.x))
+                    }
+                }).collect()
+            })
+    }
+
+    /// Positions of the users shown to others between `since` and `until`,
+    /// readings outside the grid have a `cell_id` of -1
+    pub fn locations_between(conn: &Connection, since: &DateTime<Utc>, until: &DateTime<Utc>) -> Result<Vec<UserLocation>> {
+        use postgis::ewkb::Point;
+
+        let query = r#"
+            select gps_readings.user_id, gps_readings.point, cells.id as cell_id, st_centroid(cells.geom) as centroid
+            from gps_readings
+            inner join users on users.id = gps_readings.user_id
+            left join cells on st_contains(cells.geom, gps_readings.point)
+            where gps_readings.created_at >= $1 and gps_readings.created_at < $2 and not users.hidden
+            order by gps_readings.created_at asc"#;
+
+        conn.query(query, &[since, until])
+            .map(|rows| {
+                rows.into_iter().map(|row| {
+                    let point: Point = row.get("point");
+                    let centroid: Option<Point> = row.get("centroid");
                     UserLocation {
                         id: row.get("user_id"),
                         latitude: point.y,
                         longitude: point.x,
-                        cell_id: row.get("cell_id")
+                        cell_id: row.get::<_, Option<i32>>("cell_id").unwrap_or(-1),
+                        cell_centroid: centroid.map(|centroid| (centroid.y, centroid.x))
                     }
                 }).collect()
             })
@@ -0,0 +1,297 @@
+use std::error::Error;
+
+use postgis::ewkb::Point;
+use postgres::rows::Row;
+use postgres::types::ToSql;
+use postgres::types::FromSql;
+use postgres::types::IsNull;
+use postgres::types::Type;
+use chrono::prelude::*;
+use serde_json::Value as JValue;
+
+use db::Result;
+use db::Connection;
+use db::extensions::*;
+use db::filter::Filter;
+use db::filter::Page;
+use db::models::Entity;
+use db::models::Seed;
+use db::models::InventorySeed;
+
+/// What happened in the world
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    /// Two entities mated, the payload has the `mate_id`
+    Mating,
+    /// A seed from a mating landed in a cell, the payload has the `seed`
+    SeedThrown,
+    /// A seed on the ground got too old
+    SeedDied,
+    /// A seed bloomed into a new entity, the payload has the `entity`
+    Birth,
+    /// An entity died, the payload has its last `age` and `fitness`
+    Death,
+    /// A player picked up a seed
+    Pickup,
+    /// A player planted a seed from their inventory, the payload has the `entity`
+    Spread,
+    /// A player came online, only with the cell
+    Join,
+    /// A player went offline or deregistered
+    Leave
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            EventKind::Mating     => "mating",
+            EventKind::SeedThrown => "seed_thrown",
+            EventKind::SeedDied   => "seed_died",
+            EventKind::Birth      => "birth",
+            EventKind::Death      => "death",
+            EventKind::Pickup     => "pickup",
+            EventKind::Spread     => "spread",
+            EventKind::Join       => "join",
+            EventKind::Leave      => "leave"
+        }
+    }
+
+    pub fn from_str(kind: &str) -> Option<EventKind> {
+        match kind {
+            "mating"      => Some(EventKind::Mating),
+            "seed_thrown" => Some(EventKind::SeedThrown),
+            "seed_died"   => Some(EventKind::SeedDied),
+            "birth"       => Some(EventKind::Birth),
+            "death"       => Some(EventKind::Death),
+            "pickup"      => Some(EventKind::Pickup),
+            "spread"      => Some(EventKind::Spread),
+            "join"        => Some(EventKind::Join),
+            "leave"       => Some(EventKind::Leave),
+            _             => None
+        }
+    }
+}
+
+impl FromSql for EventKind {
+    fn from_sql(ty: &Type, raw: &[u8]) -> ::std::result::Result<EventKind, Box<Error + Sync + Send>> {
+        let kind = String::from_sql(ty, raw)?;
+        EventKind::from_str(&kind).ok_or_else(|| format!("Unknown event kind: {}", kind).into())
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        <String as FromSql>::accepts(ty)
+    }
+}
+
+impl ToSql for EventKind {
+    fn to_sql(&self, ty: &Type, out: &mut Vec<u8>) -> ::std::result::Result<IsNull, Box<Error + Sync + Send>> {
+        self.as_str().to_sql(ty, out)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        <&str as ToSql>::accepts(ty)
+    }
+
+    fn to_sql_checked(&self, ty: &Type, out: &mut Vec<u8>) -> ::std::result::Result<IsNull, Box<Error + Sync + Send>> {
+        self.as_str().to_sql_checked(ty, out)
+    }
+}
+
+/// An entry of the world's history, written by the simulation and by the
+/// player endpoints
+#[derive(Debug, Clone)]
+pub struct WorldEvent {
+    pub id: Option<i32>,
+    pub kind: EventKind,
+    pub entity_id: Option<i32>,
+    pub seed_id: Option<i32>,
+    pub dna_id: Option<i32>,
+    pub user_id: Option<i32>,
+    pub point: Option<Point>,
+    pub cell_id: Option<i32>,
+    pub payload: JValue,
+    pub created_at: DateTime<Utc>
+}
+
+/// Conditions of `WorldEvent::find`, events are returned oldest first
+#[derive(Debug, Clone, Default)]
+pub struct EventQuery {
+    pub since: Option<DateTime<Utc>>,
+    pub cell_id: Option<i32>,
+    /// Any of the kinds, all kinds when empty
+    pub kinds: Vec<EventKind>,
+    /// Id of the last event of the previous page
+    pub after: Option<i32>
+}
+
+impl WorldEvent {
+    pub fn find(conn: &Connection, query: &EventQuery, limit: i64) -> Result<Page<WorldEvent>> {
+        let mut filter = Filter::new();
+
+        if let Some(since) = query.since {
+            filter.since("created_at", since);
+        }
+
+        if let Some(cell_id) = query.cell_id {
+            filter.equals("cell_id", cell_id);
+        }
+
+        if !query.kinds.is_empty() {
+            filter.one_of("kind", query.kinds.clone());
+        }
+
+        if let Some(after) = query.after {
+            filter.after(after);
+        }
+
+        filter.page::<WorldEvent>(conn, limit)
+    }
+
+    /// Events after `since` up to and including `until`, in the order they happened
+    pub fn between(conn: &Connection, since: DateTime<Utc>, until: DateTime<Utc>) -> Result<Vec<WorldEvent>> {
+        conn.query(
+            "select * from world_events where created_at > $1 and created_at <= $2 order by created_at, id",
+            &[&since, &until]
+        ).map(|rows| rows.into_iter().map(WorldEvent::from_sql_row).collect())
+    }
+
+    pub fn new(kind: EventKind) -> Self {
+        Self {
+            id: None,
+            kind,
+            entity_id: None,
+            seed_id: None,
+            dna_id: None,
+            user_id: None,
+            point: None,
+            cell_id: None,
+            payload: json!({}),
+            created_at: Utc::now()
+        }
+    }
+
+    pub fn mating(entity: &Entity, mate_id: i32) -> Self {
+        let mut event = WorldEvent::of_entity(EventKind::Mating, entity);
+        event.payload = json!({ "mate_id": mate_id });
+        event
+    }
+
+    pub fn seed_thrown(seed: &Seed) -> Self {
+        let mut event = WorldEvent::of_seed(EventKind::SeedThrown, seed);
+        event.payload = json!({ "seed": seed.clone().into_json() });
+        event
+    }
+
+    pub fn seed_died(seed: &Seed) -> Self {
+        WorldEvent::of_seed(EventKind::SeedDied, seed)
+    }
+
+    pub fn birth(seed: &Seed, entity: &Entity) -> Self {
+        let mut event = WorldEvent::of_entity(EventKind::Birth, entity);
+        event.seed_id = seed.id;
+        event.payload = json!({ "entity": entity.to_json() });
+        event
+    }
+
+    pub fn death(entity: &Entity) -> Self {
+        let mut event = WorldEvent::of_entity(EventKind::Death, entity);
+        event.payload = json!({ "age": entity.age, "fitness": entity.fitness });
+        event
+    }
+
+    pub fn pickup(item: &InventorySeed) -> Self {
+        let mut event = WorldEvent::new(EventKind::Pickup);
+        event.seed_id = Some(item.seed_id);
+        event.dna_id = Some(item.dna_id);
+        event.user_id = Some(item.user_id);
+        event.point = Some(item.point.clone());
+        event.cell_id = Some(item.cell_id);
+        event
+    }
+
+    pub fn spread(item: &InventorySeed, entity: &Entity) -> Self {
+        let mut event = WorldEvent::of_entity(EventKind::Spread, entity);
+        event.seed_id = Some(item.seed_id);
+        event.user_id = Some(item.user_id);
+        event.payload = json!({ "entity": entity.to_json() });
+        event
+    }
+
+    /// Presence changes don't carry the position, `cell_id` is -1 outside the grid
+    pub fn join(user_id: i32, cell_id: i32) -> Self {
+        WorldEvent::of_user(EventKind::Join, user_id, cell_id)
+    }
+
+    pub fn leave(user_id: i32, cell_id: i32) -> Self {
+        WorldEvent::of_user(EventKind::Leave, user_id, cell_id)
+    }
+
+    fn of_user(kind: EventKind, user_id: i32, cell_id: i32) -> Self {
+        let mut event = WorldEvent::new(kind);
+        event.user_id = Some(user_id);
+        event.cell_id = if cell_id == -1 { None } else { Some(cell_id) };
+        event
+    }
+
+    fn of_entity(kind: EventKind, entity: &Entity) -> Self {
+        let mut event = WorldEvent::new(kind);
+        event.entity_id = Some(entity.id);
+        event.dna_id = Some(entity.dna_id);
+        event.point = Some(entity.point.clone());
+        event.cell_id = Some(entity.cell_id);
+        event
+    }
+
+    fn of_seed(kind: EventKind, seed: &Seed) -> Self {
+        let mut event = WorldEvent::new(kind);
+        event.seed_id = seed.id;
+        event.dna_id = Some(seed.dna_id);
+        event.point = Some(seed.point.clone());
+        event.cell_id = Some(seed.cell_id);
+        event
+    }
+
+    pub fn into_json(self) -> JValue {
+        json!({
+            "id": self.id,
+            "kind": self.kind,
+            "entity_id": self.entity_id,
+            "seed_id": self.seed_id,
+            "dna_id": self.dna_id,
+            "user_id": self.user_id,
+            "latitude": self.point.as_ref().map(|point| point.y),
+            "longitude": self.point.as_ref().map(|point| point.x),
+            "cell_id": self.cell_id,
+            "payload": self.payload,
+            "created_at": self.created_at
+        })
+    }
+}
+
+impl SqlType for WorldEvent {
+    fn table_name() -> &'static str { "world_events" }
+
+    fn from_sql_row<'a>(row: Row<'a>) -> Self {
+        Self {
+            id: row.get("id"),
+            kind: row.get("kind"),
+            entity_id: row.get("entity_id"),
+            seed_id: row.get("seed_id"),
+            dna_id: row.get("dna_id"),
+            user_id: row.get("user_id"),
+            point: row.get("point"),
+            cell_id: row.get("cell_id"),
+            payload: row.get("payload"),
+            created_at: row.get("created_at")
+        }
+    }
+
+    fn insert_fields() -> Vec<&'static str> {
+        vec![ "kind", "entity_id", "seed_id", "dna_id", "user_id", "point", "cell_id", "payload", "created_at" ]
+    }
+
+    fn to_sql_array<'a>(&'a self) -> Vec<&'a ToSql> {
+        vec![ &self.kind, &self.entity_id, &self.seed_id, &self.dna_id, &self.user_id, &self.point, &self.cell_id, &self.payload, &self.created_at ]
+    }
+}
@@ -1,7 +1,6 @@
 #![feature(custom_attribute)]
 
 extern crate chrono;
-extern crate dotenv;
 extern crate geo;
 pub extern crate r2d2;
 pub extern crate r2d2_postgres;
@@ -13,5 +12,6 @@ extern crate rand;
 #[macro_use] extern crate serde_json;
 
 pub extern crate postgis;
+extern crate soundlines_config;
 
 pub mod db;
@@ -9,6 +9,7 @@ extern crate chrono;
 extern crate chrono_tz;
 extern crate serde_json;
 extern crate soundlines_core;
+extern crate soundlines_config;
 extern crate forecast_io;
 
 use std::thread;
@@ -23,9 +24,12 @@ use cron::Schedule;
 use job_scheduler::Job;
 use job_scheduler::JobScheduler;
 
+use soundlines_core::db::Pool;
 use soundlines_core::db::init_pool;
+use soundlines_core::db::models::Heartbeat;
+use soundlines_config::Component;
 
-mod config;
+mod state;
 mod twitter;
 mod weather;
 mod errors;
@@ -35,51 +39,59 @@ use self::errors::*;
 fn run() -> errors::Result<()> {
 	env_logger::init();
 
-    let pool = init_pool();
+    let config = soundlines_config::load_or_exit(Component::External);
+    let pool = init_pool(&config.database);
     let mut job_scheduler = JobScheduler::new();
 
-    let config = match config::from_json_file("external.json") {
-        Ok(config) => config,
+    let state = match state::from_json_file(&config.external.state_file) {
+        Ok(state) => state,
         Err(errors::Error(errors::ErrorKind::Io(_), _)) => {
-            println!("There is no configuration file. Creating external.json...");
-            config::create("external.json")?
+            println!("There is no state file. Creating {}...", config.external.state_file);
+            state::create(&config.external.state_file)?
         },
         Err(err) => return Err(err)
     };
 
-    let tweet_minutes: Schedule = format!("0 0/{} * * * * *", config.minutes).parse()
+    let tweet_minutes: Schedule = format!("0 0/{} * * * * *", config.external.interval_minutes).parse()
         .chain_err(|| "Failed to parse cron string!")?;
 
-	let weather_minutes: Schedule = format!("0 0/{} * * * * *", config.minutes).parse()
+	let weather_minutes: Schedule = format!("0 0/{} * * * * *", config.external.interval_minutes).parse()
 		.chain_err(|| "Failed to parse cron string!")?;
 
     println!("Now: {}", Utc::now().with_timezone(&Japan));
-    println!("Last tweet id: {}", config.last_tweet_id.unwrap_or(0));
+    println!("Last tweet id: {}", state.last_tweet_id.unwrap_or(0));
     println!("Upcoming times to fetch external data...");
     for time in tweet_minutes.upcoming(Japan).take(3) {
         println!("* {}", time);
     }
 
-    let config = Arc::new(Mutex::new(config));
+    let state = Arc::new(Mutex::new(state));
 
     let conn = pool.get()?;
-    let mut twitter_runner = twitter::TweetRunner::new(conn, config.clone());
+    let mut twitter_runner = twitter::TweetRunner::new(conn, &config.external, state.clone());
 
+    let heartbeat_pool = pool.clone();
     job_scheduler.add(Job::new(tweet_minutes, move || {
         if let Err(err) = twitter_runner.run() {
             handle_error(&err);
         }
+
+        beat(&heartbeat_pool, Heartbeat::TWITTER);
     }));
 
 	let conn = pool.get()?;
-	let mut weather_runner = weather::WeatherRunner::new(conn);
+	let mut weather_runner = weather::WeatherRunner::new(conn, &config.external);
 
 	weather_runner.run()?;
+	beat(&pool, Heartbeat::WEATHER);
 
+	let heartbeat_pool = pool.clone();
 	job_scheduler.add(Job::new(weather_minutes, move || {
 		if let Err(err) = weather_runner.run() {
 			handle_error(&err);
 		}
+
+		beat(&heartbeat_pool, Heartbeat::WEATHER);
 	}));
 
     let half_minute = Duration::from_secs(30);
@@ -91,6 +103,18 @@ fn run() -> errors::Result<()> {
 
 quick_main!(run);
 
+/// A missed heartbeat only makes the server report the job as stale, so it
+/// doesn't stop the jobs
+fn beat(pool: &Pool, name: &str) {
+    let res = pool.get()
+        .map_err(|err| err.to_string())
+        .and_then(|conn| Heartbeat::beat(&conn, name).map_err(|err| err.to_string()));
+
+    if let Err(err) = res {
+        println!("Failed to write {} heartbeat: {}", name, err);
+    }
+}
+
 fn handle_error(e: &self::errors::ErrorKind) {
     use std::io::Write;
     let stderr = &mut ::std::io::stderr();
@@ -0,0 +1,45 @@
+use std::fs;
+use std::path::Path;
+use std::io::Write;
+use serde_json;
+
+use errors::*;
+
+/// What is kept between runs, in `state_file` of the external configuration
+#[derive(Serialize, Deserialize)]
+pub struct State {
+    pub last_tweet_id: Option<u64>,
+    #[serde(default, skip)]
+    path: String,
+}
+
+fn default<P: AsRef<Path>>(path: P) -> State {
+    State {
+        last_tweet_id: None,
+        path: path.as_ref().to_str().unwrap().to_string()
+    }
+}
+
+pub fn from_json_file<P: AsRef<Path>>(path: P) -> Result<State> {
+    let path_string = path.as_ref().to_str().ok_or(Error::from("Failed to convert path to str"))?.to_string();
+    let file = fs::File::open(&path)?;
+    let mut state: State = serde_json::from_reader(file)?;
+    state.path = path_string;
+
+    Ok(state)
+}
+
+pub fn create<P: AsRef<Path>>(path: P) -> Result<State> {
+    let state = default(path);
+    save(&state)?;
+
+    Ok(state)
+}
+
+pub fn save(state: &State) -> Result<()> {
+    let json = serde_json::to_string_pretty(state)?;
+    let mut file = fs::File::create(&state.path)?;
+    file.write(json.as_bytes())?;
+
+    Ok(())
+}
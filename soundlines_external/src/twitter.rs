@@ -10,18 +10,21 @@ use soundlines_core::db::PooledConnection;
 use soundlines_core::db::Result as DbResult;
 use soundlines_core::postgis::ewkb::Point;
 
-use config;
-use config::Config;
+use soundlines_config::ExternalConfig;
+
+use state;
+use state::State;
 use errors::*;
 
 pub struct TweetRunner {
     pub conn: PooledConnection,
-    pub config: Arc<Mutex<Config>>
+    pub config: ExternalConfig,
+    pub state: Arc<Mutex<State>>
 }
 
 impl TweetRunner {
-    pub fn new(conn: PooledConnection, config: Arc<Mutex<Config>>) -> Self {
-        TweetRunner { conn, config }
+    pub fn new(conn: PooledConnection, config: &ExternalConfig, state: Arc<Mutex<State>>) -> Self {
+        TweetRunner { conn, config: config.clone(), state }
     }
 
     fn update_cell_for_coord(&self, (latitude, longitude): (f64, f64)) -> DbResult<Option<Cell>> {
@@ -33,12 +36,12 @@ impl TweetRunner {
     }
 
     pub fn run(&mut self) -> Result<()> {
-        let last_tweet_id = match self.config.lock() {
-            Ok(config) => (*config).last_tweet_id.clone(),
-            _          => return Err(Error::from("Lock failed"))
+        let last_tweet_id = match self.state.lock() {
+            Ok(state) => (*state).last_tweet_id.clone(),
+            _         => return Err(Error::from("Lock failed"))
         };
 
-        let egg_mode::search::SearchResult { statuses, max_id, .. } = get_tweets(last_tweet_id)?;
+        let egg_mode::search::SearchResult { statuses, max_id, .. } = get_tweets(&self.config, last_tweet_id)?;
         let mut cells = vec![];
 
         if statuses.len() == 0 {
@@ -67,33 +70,33 @@ impl TweetRunner {
 
         println!("Increased sns number for {} cells", cells.len());
 
-        match self.config.lock() {
-            Ok(mut config) => {
-                (*config).last_tweet_id = Some(max_id);
-                config::save(&*config)?;
+        match self.state.lock() {
+            Ok(mut state) => {
+                (*state).last_tweet_id = Some(max_id);
+                state::save(&*state)?;
             },
 
-            _ => return Err(Error::from("Failed to acquire config mutex lock"))
+            _ => return Err(Error::from("Failed to acquire state mutex lock"))
         }
 
         Ok(())
     }
 }
 
-fn get_tweets(since: Option<u64>) -> Result<egg_mode::search::SearchResult<'static>> {
-    let consumer_token = egg_mode::KeyPair::new("ZBFo3rW87dXNnLkbBg75vL7PS",
-                                                "OkQwhYTVVJOjGsPw1arK51B7ziqDo8E93fB6tmyb3gzJy9RVQy");
+fn get_tweets(config: &ExternalConfig, since: Option<u64>) -> Result<egg_mode::search::SearchResult<'static>> {
+    let (consumer_key, consumer_secret) = config.twitter_consumer();
+    let consumer_token = egg_mode::KeyPair::new(consumer_key.to_string(), consumer_secret.to_string());
 
-    let access_token = egg_mode::KeyPair::new("365459783-mZEFoiD9rt8PvGMUJtS77LB1JyItBdN6t0ST2HC8",
-                                              "Ujv3QlFyTr2dsqLhlAdozhoBlWV9zleeHJ675GWTl7c4E");
+    let (access_key, access_secret) = config.twitter_access();
+    let access_token = egg_mode::KeyPair::new(access_key.to_string(), access_secret.to_string());
     let token = egg_mode::Token::Access {
         consumer: consumer_token,
         access: access_token,
     };
 
-    let distance = egg_mode::search::Distance::Kilometers(1.2);
+    let distance = egg_mode::search::Distance::Kilometers(config.twitter_radius_km);
     let mut search = egg_mode::search::search("-")
-        .geocode(37.570750, 126.997702, distance);
+        .geocode(config.twitter_latitude, config.twitter_longitude, distance);
 
     if since.is_some() {
         search = search.since_tweet(since.unwrap());
@@ -4,26 +4,32 @@ use forecast_io::PrecipitationType;
 use soundlines_core::db::extensions::*;
 use soundlines_core::db::models::Weather;
 use soundlines_core::db::PooledConnection;
+use soundlines_config::ExternalConfig;
 
 use errors;
 use errors::*;
 
 pub struct WeatherRunner {
 	conn:  PooledConnection,
+	token: String,
+	latitude: f64,
+	longitude: f64
 }
 
 impl WeatherRunner {
-	const LATITUDE: f64 = 37.570628;
-	const LONGITUDE: f64 = 126.996350;
-
-	pub fn new(conn: PooledConnection) -> WeatherRunner {
-		WeatherRunner { conn }
+	pub fn new(conn: PooledConnection, config: &ExternalConfig) -> WeatherRunner {
+		WeatherRunner {
+			conn,
+			token: config.forecast_token().to_string(),
+			latitude: config.weather_latitude,
+			longitude: config.weather_longitude
+		}
 	}
 
 	pub fn run(&mut self) -> errors::Result<()> {
-		let forecast = forecast_io::get_forecast("fedc77e38dd033363070e349d61acd90", Self::LATITUDE, Self::LONGITUDE)?;
-		let current = forecast.currently.ok_or(ErrorKind::WeatherDataUnavailable(Self::LATITUDE, Self::LONGITUDE))?;
-		let temperature = current.temperature.ok_or(ErrorKind::WeatherDataUnavailable(Self::LATITUDE, Self::LONGITUDE))?;
+		let forecast = forecast_io::get_forecast(self.token.as_str(), self.latitude, self.longitude)?;
+		let current = forecast.currently.ok_or(ErrorKind::WeatherDataUnavailable(self.latitude, self.longitude))?;
+		let temperature = current.temperature.ok_or(ErrorKind::WeatherDataUnavailable(self.latitude, self.longitude))?;
 
 		let precip = current.precip_type.map(|precip| match precip {
 			PrecipitationType::Rain => "rain".to_string(),
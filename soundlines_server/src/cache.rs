@@ -0,0 +1,169 @@
+use std::io::Cursor;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+
+use chrono::prelude::*;
+
+use rocket::Request;
+use rocket::Response;
+use rocket::response;
+use rocket::response::Responder;
+use rocket::http::Status;
+use rocket::http::ContentType;
+
+/// Entries are recomputed after this long even without writes, as other
+/// processes (e.g. `gencells`) write to the database too
+const MAX_AGE_SECS: u64 = 5 * 60;
+
+const HTTP_DATE: &'static str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// Serialized JSON of a resource with its validators
+#[derive(Clone)]
+pub struct CacheEntry {
+    pub body: Arc<String>,
+    pub etag: String,
+    pub last_modified: DateTime<Utc>,
+    created: Instant
+}
+
+impl CacheEntry {
+    pub fn new(body: String) -> Self {
+        CacheEntry {
+            etag: etag_of(&body),
+            body: Arc::new(body),
+            last_modified: Utc::now(),
+            created: Instant::now()
+        }
+    }
+
+    fn is_fresh(&self) -> bool {
+        self.created.elapsed() < Duration::from_secs(MAX_AGE_SECS)
+    }
+
+    /// Response of the entry, answered with `304 Not Modified` when the
+    /// client has it already
+    pub fn respond(&self) -> CachedJson {
+        CachedJson { body: self.body.clone(), etag: self.etag.clone(), last_modified: Some(self.last_modified) }
+    }
+}
+
+/// In-process cache of rarely changing resources, keyed by their path. Writes
+/// to a resource invalidate every key starting with its prefix.
+#[derive(Default)]
+pub struct ResponseCache {
+    entries: Mutex<HashMap<String, CacheEntry>>,
+    // Bumped on every invalidation, entries computed while a write happened
+    // are not stored as they might be stale already
+    generation: Mutex<u64>
+}
+
+impl ResponseCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get_or_insert_with<F, E>(&self, key: &str, compute: F) -> Result<CacheEntry, E>
+        where F: FnOnce() -> Result<String, E>
+    {
+        if let Some(entry) = self.get(key) {
+            return Ok(entry);
+        }
+
+        let generation = self.generation();
+        let entry = CacheEntry::new(compute()?);
+
+        if let (Ok(mut entries), Ok(current)) = (self.entries.lock(), self.generation.lock()) {
+            if *current == generation {
+                // Listing pages are cached under their parameters, so expired
+                // entries are dropped instead of waiting for the next write
+                entries.retain(|_, entry| entry.is_fresh());
+                entries.insert(key.to_string(), entry.clone());
+            }
+        }
+
+        Ok(entry)
+    }
+
+    pub fn invalidate(&self, prefix: &str) {
+        if let (Ok(mut entries), Ok(mut generation)) = (self.entries.lock(), self.generation.lock()) {
+            entries.retain(|key, _| !key.starts_with(prefix));
+            *generation += 1;
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<CacheEntry> {
+        let entries = self.entries.lock().ok()?;
+        match entries.get(key) {
+            Some(entry) if entry.is_fresh() => Some(entry.clone()),
+            _ => None
+        }
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation.lock().map(|generation| *generation).unwrap_or(0)
+    }
+}
+
+pub fn etag_of(body: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// JSON response with `ETag` and `Last-Modified`, honouring `If-None-Match`
+/// and `If-Modified-Since`. Responses built for a single user have no
+/// `last_modified`, as it would only tell when the shared part changed.
+pub struct CachedJson {
+    pub body: Arc<String>,
+    pub etag: String,
+    pub last_modified: Option<DateTime<Utc>>
+}
+
+impl CachedJson {
+    fn is_fresh(&self, request: &Request, etag: &str) -> bool {
+        // If-Modified-Since is ignored when If-None-Match is given
+        if let Some(tags) = request.headers().get_one("If-None-Match") {
+            return tags.split(',')
+                .map(|tag| tag.trim().trim_left_matches("W/"))
+                .any(|tag| tag == etag || tag == "*");
+        }
+
+        match (self.last_modified, request.headers().get_one("If-Modified-Since")) {
+            (Some(last_modified), Some(since)) => DateTime::parse_from_rfc2822(since)
+                .map(|since| last_modified.timestamp() <= since.timestamp())
+                .unwrap_or(false),
+            _ => false
+        }
+    }
+}
+
+impl<'r> Responder<'r> for CachedJson {
+    fn respond_to(self, request: &Request) -> response::Result<'r> {
+        let etag = format!("\"{}\"", self.etag);
+        let mut response = Response::build();
+
+        response
+            .raw_header("ETag", etag.clone())
+            .raw_header("Cache-Control", "no-cache");
+
+        if let Some(last_modified) = self.last_modified {
+            response.raw_header("Last-Modified", last_modified.format(HTTP_DATE).to_string());
+        }
+
+        if self.is_fresh(request, &etag) {
+            response.status(Status::NotModified);
+        } else {
+            response
+                .header(ContentType::JSON)
+                .sized_body(Cursor::new((*self.body).clone()));
+        }
+
+        response.ok()
+    }
+}
@@ -0,0 +1,58 @@
+use std::io;
+use std::error::Error;
+
+use chrono::prelude::*;
+use chrono::Duration;
+
+use rocket_jwt::Jwt;
+
+use soundlines_core::db;
+use soundlines_core::db::models::User;
+use soundlines_core::db::models::Role;
+use soundlines_core::db::extensions::*;
+use soundlines_config::Config;
+
+use server;
+use replay;
+use replay::World;
+use replay::ReplayStream;
+use snapshots::SnapshotsDir;
+
+/// Creates a new user with the given role, or changes the role of an existing
+/// one, and returns a token for it signed with the server's secret.
+pub fn mint_token(config: &Config, role: Role, user_id: Option<i32>) -> Result<String, Box<Error>> {
+    let conn = db::init_connection(&config.database);
+
+    let user = match user_id {
+        Some(id) => User::set_role(&conn, id, role)?.ok_or(format!("User with id: {} not found", id))?,
+        None     => conn.insert(&User::new(role))?
+    };
+
+    let jwt_config = server::jwt_config(&config.server);
+    let user_id = user.id;
+    let token = Jwt::new(user).subject(user_id.to_string()).encode(&jwt_config).map_err(|_| "Failed to encode token")?;
+
+    Ok(token)
+}
+
+/// Prints the world at a past time as json
+pub fn replay_at(config: &Config, at: DateTime<Utc>) -> Result<(), Box<Error>> {
+    let conn = db::init_connection(&config.database);
+    let world = World::at(&conn, &SnapshotsDir::from_config(&config.sim), at)?;
+
+    println!("{}", world.to_json());
+    Ok(())
+}
+
+/// Prints the world every `step` seconds from `from` to `until`, one json
+/// per line, `speed` times faster than real time
+pub fn replay_stream(config: &Config, from: DateTime<Utc>, until: DateTime<Utc>, step: i64, speed: f64) -> Result<(), Box<Error>> {
+    let step = Duration::seconds(step);
+    replay::check_stream(from, until, step, speed)?;
+
+    let pool = db::init_pool(&config.database);
+    let mut stream = ReplayStream::new(pool, None, &SnapshotsDir::from_config(&config.sim), from, until, step, speed)?;
+
+    io::copy(&mut stream, &mut io::stdout())?;
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use rocket::request;
+use rocket::Request;
+use rocket::State;
+use rocket::http::Status;
+use rocket::outcome::Outcome;
+use rocket::request::FromRequest;
+
+use serde_json;
+
+use soundlines_core::db::Result as DbResult;
+use soundlines_core::db::Connection;
+use soundlines_core::db::models::AppConfig;
+
+use cache::CacheEntry;
+use cache::ResponseCache;
+use db_guard::DbConn;
+
+/// Header the client apps send their version in, e.g. `1.4.2`
+pub const CLIENT_VERSION_HEADER: &'static str = "X-Client-Version";
+
+/// Cached JSON of the app config, shared by `/config` and the version checks
+pub fn app_config_entry(cache: &ResponseCache, conn: &Connection) -> DbResult<CacheEntry> {
+    cache.get_or_insert_with("app_config", || {
+        AppConfig::get(conn).map(|config| json!(config).to_string())
+    })
+}
+
+pub fn app_config(cache: &ResponseCache, conn: &Connection) -> DbResult<AppConfig> {
+    app_config_entry(cache, conn)
+        .map(|entry| serde_json::from_str(&entry.body).expect("Cached app config is always valid"))
+}
+
+/// Request from a client that is not older than the minimum client version.
+/// Fails with `Upgrade Required` otherwise. Requests without a version header
+/// (scripts, the web dashboard) are let through.
+pub struct SupportedClient;
+
+impl<'a, 'r> FromRequest<'a, 'r> for SupportedClient {
+    type Error = ();
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        let version = match request.headers().get_one(CLIENT_VERSION_HEADER) {
+            Some(version) => version,
+            None          => return Outcome::Success(SupportedClient)
+        };
+
+        let cache = request.guard::<State<ResponseCache>>()?;
+        let conn = request.guard::<DbConn>()?;
+        let config = match app_config(&cache, &conn) {
+            Ok(config) => config,
+            Err(_)     => return Outcome::Failure((Status::InternalServerError, ()))
+        };
+
+        if !config.is_supported(version) {
+            return Outcome::Failure((Status::UpgradeRequired, ()));
+        }
+
+        Outcome::Success(SupportedClient)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket;
+    use rocket::local::Client;
+
+    use super::*;
+
+    #[get("/")]
+    fn checked(_client: SupportedClient) -> &'static str {
+        "ok"
+    }
+
+    /// Requests without the header never reach the app config, so this needs
+    /// no database
+    #[test]
+    fn requests_without_a_version_are_let_through() {
+        let client = Client::new(rocket::ignite().mount("/", routes![checked])).unwrap();
+        let mut response = client.get("/").dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.body_string(), Some("ok".to_string()));
+    }
+}
@@ -0,0 +1,310 @@
+use std::io::Write;
+use std::io::Cursor;
+
+use rocket::Request;
+use rocket::Response;
+use rocket::http::ContentType;
+use rocket::fairing::Fairing;
+use rocket::fairing::Info;
+use rocket::fairing::Kind;
+
+use brotli;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use rmp_serde;
+use serde_cbor;
+use serde_json;
+use serde_json::Value;
+
+/// Bodies smaller than this are not worth compressing
+const MIN_COMPRESS_SIZE: usize = 1024;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Json,
+    MsgPack,
+    Cbor
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Encoding {
+    Identity,
+    Gzip,
+    Brotli
+}
+
+/// Re-encodes JSON responses as MessagePack or CBOR when the client prefers
+/// them in `Accept`, and compresses them with brotli or gzip when allowed by
+/// `Accept-Encoding`. The schema stays the same, only the encoding changes.
+pub struct ResponseEncoding;
+
+impl Fairing for ResponseEncoding {
+    fn info(&self) -> Info {
+        Info { name: "Response encoding", kind: Kind::Response }
+    }
+
+    fn on_response(&self, request: &Request, response: &mut Response) {
+        if response.content_type() != Some(ContentType::JSON) {
+            return;
+        }
+
+        let format = negotiate(request.headers().get_one("Accept"), &[
+            ("application/json", Format::Json),
+            ("application/msgpack", Format::MsgPack),
+            ("application/x-msgpack", Format::MsgPack),
+            ("application/cbor", Format::Cbor)
+        ]).unwrap_or(Format::Json);
+
+        let encoding = negotiate(request.headers().get_one("Accept-Encoding"), &[
+            ("identity", Encoding::Identity),
+            ("br", Encoding::Brotli),
+            ("gzip", Encoding::Gzip)
+        ]).unwrap_or(Encoding::Identity);
+
+        response.set_raw_header("Vary", "Accept, Accept-Encoding");
+
+        if format == Format::Json && encoding == Encoding::Identity {
+            return;
+        }
+
+        let json = match response.body_bytes() {
+            Some(body) => body,
+            None       => return
+        };
+
+        let mut changed = format != Format::Json;
+        let (mut body, content_type) = match encode(&json, format) {
+            Some(encoded) => encoded,
+            // Not valid JSON after all, send it as it is
+            None          => {
+                changed = false;
+                (json, ContentType::JSON)
+            }
+        };
+
+        if body.len() >= MIN_COMPRESS_SIZE {
+            if let Some(compressed) = compress(&body, encoding) {
+                body = compressed;
+                changed = true;
+                response.set_raw_header("Content-Encoding", match encoding {
+                    Encoding::Brotli => "br",
+                    _                => "gzip"
+                });
+            }
+        }
+
+        // The body isn't byte for byte the same anymore
+        let weak_etag = response.headers().get_one("ETag")
+            .filter(|etag| changed && !etag.starts_with("W/"))
+            .map(|etag| format!("W/{}", etag));
+
+        if let Some(etag) = weak_etag {
+            response.set_raw_header("ETag", etag);
+        }
+
+        response.set_header(content_type);
+        response.set_sized_body(Cursor::new(body));
+    }
+}
+
+fn encode(json: &[u8], format: Format) -> Option<(Vec<u8>, ContentType)> {
+    if format == Format::Json {
+        return Some((json.to_vec(), ContentType::JSON));
+    }
+
+    let value: Value = serde_json::from_slice(json).ok()?;
+
+    match format {
+        Format::MsgPack => rmp_serde::to_vec_named(&value).ok()
+            .map(|body| (body, ContentType::new("application", "msgpack"))),
+        Format::Cbor    => serde_cbor::to_vec(&value).ok()
+            .map(|body| (body, ContentType::new("application", "cbor"))),
+        Format::Json    => unreachable!()
+    }
+}
+
+fn compress(body: &[u8], encoding: Encoding) -> Option<Vec<u8>> {
+    match encoding {
+        Encoding::Identity => None,
+        Encoding::Gzip     => {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(body).ok()?;
+            encoder.finish().ok()
+        },
+        Encoding::Brotli   => {
+            let mut compressed = Vec::new();
+            {
+                // Quality 5 keeps it fast enough to run on every request
+                let mut writer = brotli::CompressorWriter::new(&mut compressed, 4096, 5, 22);
+                writer.write_all(body).ok()?;
+            }
+
+            Some(compressed)
+        }
+    }
+}
+
+/// Picks the supported value the client weighs most in an `Accept` style
+/// header, earlier ones win ties. `q=0` rules a value out, and so does a `q`
+/// which isn't a number from 0 to 1. Wildcards match nothing, the caller's
+/// default is what any value means.
+fn negotiate<T: Copy>(header: Option<&str>, supported: &[(&str, T)]) -> Option<T> {
+    let header = header?;
+    let mut best: Option<(f32, T)> = None;
+
+    for item in header.split(',') {
+        let mut parts = item.split(';').map(str::trim);
+        let name = match parts.next() {
+            Some(name) => name.to_lowercase(),
+            None       => continue
+        };
+
+        let quality = parts
+            .filter_map(|param| {
+                let mut pair = param.splitn(2, '=').map(str::trim);
+                match (pair.next(), pair.next()) {
+                    (Some("q"), Some(q)) => Some(q.parse::<f32>().ok().filter(|q| *q >= 0.0 && *q <= 1.0)),
+                    _                    => None
+                }
+            })
+            .next()
+            .unwrap_or(Some(1.0));
+
+        let quality = match quality {
+            Some(quality) if quality > 0.0 => quality,
+            _                              => continue
+        };
+
+        let value = match supported.iter().find(|&&(supported, _)| supported == name) {
+            Some(&(_, value)) => value,
+            None              => continue
+        };
+
+        if best.map(|(best_quality, _)| quality > best_quality).unwrap_or(true) {
+            best = Some((quality, value));
+        }
+    }
+
+    best.map(|(_, value)| value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    use super::*;
+
+    const ENCODINGS: &'static [(&'static str, Encoding)] = &[
+        ("identity", Encoding::Identity),
+        ("br", Encoding::Brotli),
+        ("gzip", Encoding::Gzip)
+    ];
+
+    fn encoding(header: &str) -> Option<Encoding> {
+        negotiate(Some(header), ENCODINGS)
+    }
+
+    fn sample() -> Value {
+        json!({
+            "cells": (0..100).map(|id| json!({ "id": id, "sound": 0.5, "points": [[126.97, 37.56]] })).collect::<Vec<_>>(),
+            "current_cell_id": null,
+            "next_cursor": 99
+        })
+    }
+
+    #[test]
+    fn highest_quality_wins() {
+        assert!(encoding("gzip;q=0.5, br;q=0.8") == Some(Encoding::Brotli));
+        assert!(encoding("gzip, br;q=0.9") == Some(Encoding::Gzip));
+        assert!(encoding("GZIP ; q=0.2") == Some(Encoding::Gzip));
+    }
+
+    #[test]
+    fn earlier_values_win_ties() {
+        assert!(encoding("gzip, br") == Some(Encoding::Gzip));
+        assert!(encoding("br;q=0.5, gzip;q=0.5") == Some(Encoding::Brotli));
+    }
+
+    #[test]
+    fn zero_quality_rules_a_value_out() {
+        assert!(encoding("br;q=0, gzip;q=0.1") == Some(Encoding::Gzip));
+        assert!(encoding("br;q=0.0") == None);
+        assert!(encoding("gzip;q=0, identity;q=0") == None);
+    }
+
+    #[test]
+    fn malformed_quality_rules_a_value_out() {
+        assert!(encoding("br;q=high, gzip;q=0.1") == Some(Encoding::Gzip));
+        assert!(encoding("br;q=NaN, gzip;q=0.1") == Some(Encoding::Gzip));
+        assert!(encoding("br;q=2, gzip;q=0.1") == Some(Encoding::Gzip));
+        assert!(encoding("br;q=-1") == None);
+        assert!(encoding("br;q=") == None);
+    }
+
+    #[test]
+    fn wildcards_and_unknown_values_match_nothing() {
+        assert!(encoding("*") == None);
+        assert!(encoding("deflate, compress") == None);
+        assert!(encoding("*, gzip;q=0.1") == Some(Encoding::Gzip));
+        assert!(encoding("") == None);
+        assert!(negotiate(None, ENCODINGS) == None);
+
+        let format = negotiate(Some("*/*, application/*"), &[("application/json", Format::Json), ("application/cbor", Format::Cbor)]);
+        assert!(format == None);
+    }
+
+    #[test]
+    fn msgpack_round_trips() {
+        let json = sample().to_string().into_bytes();
+        let (body, content_type) = encode(&json, Format::MsgPack).unwrap();
+
+        assert_eq!(content_type, ContentType::new("application", "msgpack"));
+        assert_eq!(rmp_serde::from_slice::<Value>(&body).unwrap(), sample());
+    }
+
+    #[test]
+    fn cbor_round_trips() {
+        let json = sample().to_string().into_bytes();
+        let (body, content_type) = encode(&json, Format::Cbor).unwrap();
+
+        assert_eq!(content_type, ContentType::new("application", "cbor"));
+        assert_eq!(serde_cbor::from_slice::<Value>(&body).unwrap(), sample());
+    }
+
+    #[test]
+    fn invalid_json_is_not_encoded() {
+        assert!(encode(b"{\"cells\":", Format::MsgPack).is_none());
+        assert_eq!(encode(b"{}", Format::Json).unwrap().0, b"{}".to_vec());
+    }
+
+    #[test]
+    fn gzip_round_trips() {
+        let json = sample().to_string().into_bytes();
+        let compressed = compress(&json, Encoding::Gzip).unwrap();
+
+        let mut decompressed = Vec::new();
+        GzDecoder::new(&compressed[..]).read_to_end(&mut decompressed).unwrap();
+
+        assert!(compressed.len() < json.len());
+        assert_eq!(decompressed, json);
+    }
+
+    #[test]
+    fn brotli_round_trips() {
+        let json = sample().to_string().into_bytes();
+        let compressed = compress(&json, Encoding::Brotli).unwrap();
+
+        let mut decompressed = Vec::new();
+        brotli::Decompressor::new(&compressed[..], 4096).read_to_end(&mut decompressed).unwrap();
+
+        assert!(compressed.len() < json.len());
+        assert_eq!(decompressed, json);
+    }
+
+    #[test]
+    fn identity_is_not_compressed() {
+        assert!(compress(b"{}", Encoding::Identity).is_none());
+    }
+}
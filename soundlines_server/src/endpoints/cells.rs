@@ -1,27 +1,83 @@
+use std::sync::Arc;
+use std::result::Result as StdResult;
+
+use rocket::State;
+use rocket::http::Status;
+use rocket::response::Failure;
 use rocket_contrib::Json;
 
 use soundlines_core::db::Result;
+use soundlines_core::db::Connection;
 use soundlines_core::db::models::Cell;
+use soundlines_core::db::models::GpsReading;
 use soundlines_core::db::extensions::*;
 
 use serde_json::Value;
 use db_guard::DbConn;
+use user::OptionalAuth;
+use listing::ListQuery;
+use cache::etag_of;
+use cache::CachedJson;
+use cache::ResponseCache;
 
+/// Every cell, or a page of them with any of the `ListParams`. Signed in
+/// clients also get the id of the cell they were last seen in.
 #[get("/")]
-pub fn index(conn: DbConn) -> Result<Json<Value>> {
-    let cells = conn.all::<Cell>()?;
-    let cells = cells.into_iter().map(Cell::into_json).collect::<Vec<_>>();
+pub fn index(query: ListQuery, auth: OptionalAuth, conn: DbConn, cache: State<ResponseCache>) -> StdResult<CachedJson, Failure> {
+    let cells = match query.0 {
+        Some(params) => {
+            if params.has_plant_filters() {
+                return Err(Failure(Status::BadRequest));
+            }
 
-    Ok(Json(json!({
-        "cells": cells
-    })))
+            let filter = params.filter("geom")?;
+            cache.get_or_insert_with(&format!("cells?{:?}", params), || {
+                filter.page::<Cell>(&conn, params.limit())
+                    .map(|page| params.page_json("cells", page, |cell| cell.id, Cell::into_json).to_string())
+            })
+        },
+        None => cache.get_or_insert_with("cells", || {
+            conn.all::<Cell>().map(|cells| {
+                let cells = cells.into_iter().map(Cell::into_json).collect::<Vec<_>>();
+                json!({ "cells": cells, "next_cursor": null }).to_string()
+            })
+        })
+    }.map_err(|_| Failure(Status::InternalServerError))?;
+
+    let user = match auth.0 {
+        Some(user) => user,
+        None       => return Ok(cells.respond())
+    };
+
+    let current_cell_id = current_cell_id(&conn, user.id).map_err(|_| Failure(Status::InternalServerError))?;
+
+    // Cells are cached serialized, so the user's cell is spliced in instead
+    // of parsing them again for every request
+    // The user's cell can change while the cells don't, so personalised
+    // responses are only validated by their ETag
+    let body = format!(r#"{},"current_cell_id":{}}}"#, &cells.body[..cells.body.len() - 1], json!(current_cell_id));
+    Ok(CachedJson { etag: etag_of(&body), body: Arc::new(body), last_modified: None })
+}
+
+fn current_cell_id(conn: &Connection, user_id: i32) -> Result<Option<i32>> {
+    match GpsReading::last_by_user(conn, user_id)? {
+        Some(reading) => Ok(reading.get_cell(conn)?.map(|cell| cell.id)),
+        None          => Ok(None)
+    }
 }
 
 #[get("/<id>")]
-pub fn show(conn: DbConn, id: i32) -> Result<Option<Json<Value>>> {
-    let cell = conn.get::<Cell>(id)?;
+pub fn show(conn: DbConn, id: i32, cache: State<ResponseCache>) -> Result<Option<CachedJson>> {
+    // Missing cells are cached as null
+    let entry = cache.get_or_insert_with(&format!("cells/{}", id), || {
+        conn.get::<Cell>(id).map(|cell| json!(cell.map(Cell::into_json)).to_string())
+    })?;
+
+    if entry.body.as_str() == "null" {
+        return Ok(None);
+    }
 
-    Ok(cell.map(|c| Json(c.to_json())))
+    Ok(Some(entry.respond()))
 }
 
 #[get("/<latitude>/<longitude>")]
@@ -1,15 +1,31 @@
+use rocket::State;
 use rocket::response::status;
 use rocket_contrib::Json;
 use serde_json::Value;
+use serde_json::Map;
 use chrono::prelude::*;
 
 use soundlines_core::db::Result;
+use soundlines_core::db::Connection;
 use soundlines_core::db::extensions::*;
 use soundlines_core::db::models::*;
 use soundlines_core::postgis::ewkb::Point;
 
 use db_guard::*;
 use user::Auth;
+use openapi::*;
+use cache::ResponseCache;
+use metrics::Metrics;
+use privacy::LocationPrivacy;
+use presence::Presence;
+
+/// The statistics are secondary to the readings, so a failure to count a
+/// stored reading is logged instead of failing the request
+fn record_stats(conn: &Connection, user_id: i32, counter: StatCounter, count: i32) {
+    if let Err(err) = UserStats::record(conn, user_id, counter, count) {
+        eprintln!("ERROR: Failed to record {:?} of user {}: {}", counter, user_id, err);
+    }
+}
 
 #[derive(Deserialize, Serialize)]
 pub struct WifiReadingsPayload {
@@ -18,8 +34,18 @@ pub struct WifiReadingsPayload {
     wifi_items: Vec<WifiReadingJson>
 }
 
+impl Schema for WifiReadingsPayload {
+    fn schema() -> Value {
+        object(&[
+            ("latitude", number()),
+            ("longitude", number()),
+            ("wifi_items", array(WifiReadingJson::schema()))
+        ], &["latitude", "longitude", "wifi_items"])
+    }
+}
+
 #[post("/wifi", data = "<payload>")]
-pub fn wifi(auth: Auth, conn: DbConn, payload: Json<WifiReadingsPayload>) -> Result<status::NoContent> {
+pub fn wifi(auth: Auth, conn: DbConn, cache: State<ResponseCache>, metrics: State<Metrics>, payload: Json<WifiReadingsPayload>) -> Result<status::NoContent> {
     let user = auth.into_user();
     let user_id = user.id;
 
@@ -40,6 +66,11 @@ pub fn wifi(auth: Auth, conn: DbConn, payload: Json<WifiReadingsPayload>) -> Res
 
             cell.wifi = cell.wifi_total / cell.wifi_count;
             conn.update(cell.id, &cell)?;
+            cache.invalidate("cells");
+            // Stored like the other readings so the aggregates can be replayed
+            conn.insert_batch(&readings)?;
+            metrics.readings("wifi", readings.len() as u64);
+            record_stats(&conn, user_id, StatCounter::WifiReadings, readings.len() as i32);
         },
 
         None => return Ok(status::NoContent)
@@ -55,8 +86,14 @@ pub struct SoundReadingPayload {
     level: f32
 }
 
+impl Schema for SoundReadingPayload {
+    fn schema() -> Value {
+        object(&[("latitude", number()), ("longitude", number()), ("level", number())], &["latitude", "longitude", "level"])
+    }
+}
+
 #[post("/sound", data = "<payload>")]
-pub fn sound(auth: Auth, conn: DbConn, payload: Json<SoundReadingPayload>) -> Result<status::NoContent> {
+pub fn sound(auth: Auth, conn: DbConn, cache: State<ResponseCache>, metrics: State<Metrics>, payload: Json<SoundReadingPayload>) -> Result<status::NoContent> {
     let user = auth.into_user();
     let payload = payload.into_inner();
 
@@ -75,11 +112,15 @@ pub fn sound(auth: Auth, conn: DbConn, payload: Json<SoundReadingPayload>) -> Re
             cell.sound = cell.sound_total / cell.sound_count;
 
             conn.update(cell.id, &cell)?;
+            cache.invalidate("cells");
         },
         None => return Ok(status::NoContent)
     };
 
     conn.insert(&reading)?;
+    metrics.readings("sound", 1);
+    record_stats(&conn, user.id, StatCounter::SoundReadings, 1);
+
     Ok(status::NoContent)
 }
 
@@ -90,8 +131,14 @@ pub struct LightReadingPayload {
     level: f32
 }
 
+impl Schema for LightReadingPayload {
+    fn schema() -> Value {
+        object(&[("latitude", number()), ("longitude", number()), ("level", number())], &["latitude", "longitude", "level"])
+    }
+}
+
 #[post("/light", data = "<payload>")]
-pub fn light(auth: Auth, conn: DbConn, payload: Json<LightReadingPayload>) -> Result<status::NoContent> {
+pub fn light(auth: Auth, conn: DbConn, cache: State<ResponseCache>, metrics: State<Metrics>, payload: Json<LightReadingPayload>) -> Result<status::NoContent> {
     let user = auth.into_user();
     let payload = payload.into_inner();
 
@@ -110,26 +157,54 @@ pub fn light(auth: Auth, conn: DbConn, payload: Json<LightReadingPayload>) -> Re
             cell.light = cell.light_total / cell.light_count;
 
             conn.update(cell.id, &cell)?;
+            cache.invalidate("cells");
         },
         None => return Ok(status::NoContent)
     };
 
     conn.insert(&reading)?;
+    metrics.readings("light", 1);
+    record_stats(&conn, user.id, StatCounter::LightReadings, 1);
+
     Ok(status::NoContent)
 }
 
 #[post("/gps", data = "<reading>")]
-pub fn gps(auth: Auth, conn: DbConn, reading: Json<GpsReadingJson>) -> Result<Option<Json>> {
+pub fn gps(auth: Auth, conn: DbConn, cache: State<ResponseCache>, metrics: State<Metrics>, privacy: State<LocationPrivacy>, presence: State<Presence>, reading: Json<GpsReadingJson>) -> Result<Option<Json>> {
     let user = auth.into_user();
     let mut reading = reading.into_inner();
     reading.user_id = user.id;
 
-    let other_users = User::get_all_locations(&*conn, Some(user.id))?;
+    let other_users = presence.others(Some(user.id))
+        .iter()
+        .map(|location| privacy.to_json(location))
+        .collect::<Vec<_>>();
 
     let gps_reading: GpsReading = reading.into_gps_reading(0);
     let CellNeighbours { entities, cells, seeds, current_cell_id } =
         Cell::find_neighbors(&*conn, &gps_reading.point, 120.0)?;
 
+    let hidden = User::privacy(&*conn, user.id)?.map(|privacy| privacy.hidden).unwrap_or(false);
+    presence.seen(&*conn, OnlineUser {
+        user_id: user.id,
+        latitude: gps_reading.point.y,
+        longitude: gps_reading.point.x,
+        cell_id: current_cell_id,
+        cell_centroid: cells.get(&current_cell_id).map(Cell::centroid),
+        hidden,
+        seen_at: Utc::now()
+    })?;
+
+    // Players online in the cells around, without the user
+    let occupancy = presence.occupancy();
+    let occupancy = cells.keys()
+        .map(|id| {
+            let count = occupancy.get(id).cloned().unwrap_or(0);
+            let others = if *id == current_cell_id && !hidden { count.saturating_sub(1) } else { count };
+            (id.to_string(), json!(others))
+        })
+        .collect::<Map<_, _>>();
+
     if cells.len() == 0 {
         return Ok(Some(Json(json!({
             "user_id": gps_reading.user_id as i64,
@@ -138,6 +213,7 @@ pub fn gps(auth: Auth, conn: DbConn, reading: Json<GpsReadingJson>) -> Result<Op
 	            "longitude": gps_reading.point.x
 	        },
 	        "others": other_users,
+	        "occupancy": occupancy,
 	        "cell_id": current_cell_id as i64,
 	        "neighbor_cells": cells.iter().map(|(id, _)| *id).collect::<Vec<i32>>(),
 	        "entities": [],
@@ -151,8 +227,23 @@ pub fn gps(auth: Auth, conn: DbConn, reading: Json<GpsReadingJson>) -> Result<Op
         .unwrap_or(false);
 
     let gps_reading = conn.insert(&gps_reading)?;
+    metrics.readings("gps", 1);
+    record_stats(&conn, user.id, StatCounter::GpsReadings, 1);
 
-    let entities = entities.into_iter().map(Entity::into_json).collect::<Vec<_>>();
+    // Readings next to the grid are not in any cell
+    if current_cell_id != -1 {
+        if let Err(err) = UserStats::visit(&*conn, user.id, current_cell_id) {
+            eprintln!("ERROR: Failed to record the visit of user {} to cell {}: {}", user.id, current_cell_id, err);
+        }
+    }
+
+    // Lets the client highlight its own plants
+    let entities = entities.into_iter().map(|entity| {
+        let owned = entity.owner_id == Some(gps_reading.user_id);
+        let mut json = entity.into_json();
+        json["owned"] = json!(owned);
+        json
+    }).collect::<Vec<_>>();
     let seeds = seeds
         .into_iter()
         .map(Seed::into_json)
@@ -168,10 +259,11 @@ pub fn gps(auth: Auth, conn: DbConn, reading: Json<GpsReadingJson>) -> Result<Op
         cell_id as i64
     }).collect::<Vec<_>>();
 
-    cell_to_update_visits.map(|mut cell| {
+    if let Some(mut cell) = cell_to_update_visits {
         cell.visit += 1;
-        conn.update(cell.id, &cell)
-    }).unwrap_or(Ok(()))?;
+        conn.update(cell.id, &cell)?;
+        cache.invalidate("cells");
+    }
 
     Ok(Some(Json(json!({
         "user_id": gps_reading.user_id as i64,
@@ -180,6 +272,7 @@ pub fn gps(auth: Auth, conn: DbConn, reading: Json<GpsReadingJson>) -> Result<Op
             "longitude": gps_reading.point.x
         },
         "others": other_users,
+        "occupancy": occupancy,
         "cell_id": current_cell_id as i64,
         "neighbor_cells": cells,
         "entities": entities,
@@ -0,0 +1,39 @@
+use std::result::Result as StdResult;
+
+use rocket::State;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket_contrib::Json;
+use serde_json::Value;
+
+use db_guard::*;
+use user::Auth;
+use user::Admin;
+use cache::CachedJson;
+use cache::ResponseCache;
+use client_version::app_config_entry;
+
+use soundlines_core::db::Result as DbResult;
+use soundlines_core::db::models::AppConfig;
+
+/// Client versions, feature flags and the message of the day, fetched by the
+/// apps on startup
+#[get("/config")]
+pub fn get(conn: DbConn, cache: State<ResponseCache>) -> DbResult<CachedJson> {
+    app_config_entry(&cache, &conn).map(|entry| entry.respond())
+}
+
+#[put("/config", data = "<config>")]
+pub fn update(_auth: Auth<Admin>, conn: DbConn, cache: State<ResponseCache>, config: Json<AppConfig>) -> StdResult<Json<AppConfig>, status::Custom<Json<Value>>> {
+    let config = config.into_inner();
+
+    if let Err(error) = config.validate() {
+        return Err(status::Custom(Status::BadRequest, Json(json!({ "error": error }))));
+    }
+
+    let config = config.save(&conn)
+        .map_err(|_| status::Custom(Status::InternalServerError, Json(json!({}))))?;
+    cache.invalidate("app_config");
+
+    Ok(Json(config))
+}
@@ -1,54 +1,158 @@
 use std::io;
-use std::io::Read;
-use std::io::Write;
-use std::fs::File;
-use std::path::Path;
+use std::result::Result as StdResult;
 
+use rocket::State;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket::response::Failure;
 use rocket::response::NamedFile;
 use rocket_contrib::Json;
+use serde_json;
+use serde_json::Value;
 
 use db_guard::*;
+use user::Auth;
+use user::Admin;
+use user::Curator;
+use cache::CachedJson;
+use cache::ResponseCache;
+use client_version::app_config;
+use snapshots;
+use snapshots::SnapshotId;
+use snapshots::SnapshotsDir;
 
 use soundlines_core::db::Result as DbResult;
 use soundlines_core::db::models::*;
 use soundlines_core::db::extensions::*;
 
+/// Latest client version, kept for clients predating `/config`
 #[get("/version")]
-pub fn get_version() -> Result<String, io::Error> {
-    let mut content = String::new();
-    let mut file = match File::open("resources/version") {
-        Ok(file) => file,
-        _        => return Ok(content)
-    };
-
-    file.read_to_string(&mut content)?;
-    Ok(content)
+pub fn get_version(conn: DbConn, cache: State<ResponseCache>) -> DbResult<String> {
+    app_config(&cache, &conn).map(|config| config.latest_client_version)
 }
 
 #[put("/version", data = "<content>")]
-pub fn update_version(content: String) -> Result<String, io::Error> {
-    let mut file = File::create("resources/version")?;
-    file.write_all(content.as_bytes())?;
+pub fn update_version(_auth: Auth<Admin>, conn: DbConn, cache: State<ResponseCache>, content: String) -> StdResult<String, Status> {
+    let mut config = app_config(&cache, &conn).map_err(|_| Status::InternalServerError)?;
+    config.latest_client_version = content.trim().to_string();
+
+    if config.validate().is_err() {
+        return Err(Status::BadRequest);
+    }
+
+    let config = config.save(&conn).map_err(|_| Status::InternalServerError)?;
+    cache.invalidate("app_config");
 
-    Ok(content)
+    Ok(config.latest_client_version)
 }
 
 #[get("/settings")]
-pub fn get_settings(conn: DbConn) -> DbResult<Json<Vec<PlantSetting>>> {
-    let settings = conn.all::<PlantSetting>()?;
-    Ok(Json(settings))
+pub fn get_settings(_auth: Auth<Curator>, conn: DbConn, cache: State<ResponseCache>) -> DbResult<CachedJson> {
+    cache.get_or_insert_with("settings", || {
+        conn.all::<PlantSetting>().map(|settings| json!(settings).to_string())
+    }).map(|entry| entry.respond())
 }
 
+/// Validates the setting and stores it as the next version of the setting,
+/// the simulation picks it up on its next tick
 #[put("/settings/<setting_id>", data="<setting>")]
-pub fn update_setting(conn: DbConn, setting_id: i32, setting: Json<PlantSetting>) -> DbResult<()> {
+pub fn update_setting(auth: Auth<Curator>, conn: DbConn, cache: State<ResponseCache>, setting_id: i32, setting: Json<PlantSetting>) -> StdResult<Json<SettingVersion>, status::Custom<Json<Value>>> {
     let setting = setting.into_inner();
-    let _ = conn.update(setting_id, &setting)?;
 
-    Ok(())
+    let errors = setting.validate();
+    if !errors.is_empty() {
+        return Err(status::Custom(Status::BadRequest, Json(json!({ "errors": errors }))));
+    }
+
+    let version = SettingVersion::record(&conn, setting_id, setting, Some(auth.into_user().id), None)
+        .map_err(|_| status::Custom(Status::InternalServerError, Json(json!({}))))?
+        .ok_or_else(|| status::Custom(Status::NotFound, Json(json!({}))))?;
+    cache.invalidate("settings");
+
+    Ok(Json(version))
+}
+
+/// Versions of a setting, newest first
+#[get("/settings/<setting_id>/versions")]
+pub fn setting_versions(_auth: Auth<Curator>, conn: DbConn, setting_id: i32) -> DbResult<Json<Vec<SettingVersion>>> {
+    SettingVersion::for_setting(&conn, setting_id).map(Json)
+}
+
+/// Fields changed between two versions of a setting
+#[get("/settings/<setting_id>/diff/<from>/<to>")]
+pub fn setting_diff(_auth: Auth<Curator>, conn: DbConn, setting_id: i32, from: i32, to: i32) -> StdResult<Json<Value>, Failure> {
+    let version = |version| SettingVersion::get(&conn, setting_id, version)
+        .map_err(|_| Failure(Status::InternalServerError))
+        .and_then(|version| version.ok_or(Failure(Status::NotFound)));
+
+    let (from, to) = (version(from)?, version(to)?);
+
+    Ok(Json(json!({
+        "setting_id": setting_id,
+        "from": from.version,
+        "to": to.version,
+        "changes": from.setting.diff(&to.setting)
+    })))
+}
+
+/// Restores a previous version of a setting, stored as a new version. The
+/// version is validated like an update.
+#[post("/settings/<setting_id>/rollback/<version>")]
+pub fn rollback_setting(auth: Auth<Curator>, conn: DbConn, cache: State<ResponseCache>, setting_id: i32, version: i32) -> StdResult<Json<SettingVersion>, status::Custom<Json<Value>>> {
+    let rollback = SettingVersion::rollback(&conn, setting_id, version, Some(auth.into_user().id))
+        .map_err(|_| status::Custom(Status::InternalServerError, Json(json!({}))))?;
+
+    let version = match rollback {
+        Rollback::Restored(version) => version,
+        Rollback::Invalid(errors)   => return Err(status::Custom(Status::BadRequest, Json(json!({ "errors": errors })))),
+        Rollback::NotFound          => return Err(status::Custom(Status::NotFound, Json(json!({}))))
+    };
+    cache.invalidate("settings");
+
+    Ok(Json(version))
+}
+
+/// Snapshots with the time they were taken at and their item counts, newest first
+#[get("/snapshots")]
+pub fn list_snapshots(_auth: Auth<Curator>, cache: State<ResponseCache>, dir: State<SnapshotsDir>) -> StdResult<Json<Value>, Failure> {
+    let ids = snapshots::list(&dir).map_err(|_| Failure(Status::InternalServerError))?;
+
+    // Snapshot files are never rewritten, their summaries only expire
+    let summaries = ids.iter()
+        .filter_map(|id| {
+            cache.get_or_insert_with(&format!("snapshots/{}", id.as_str()), || id.summary(&dir).map(|summary| summary.to_string()))
+                .ok()
+                .and_then(|entry| serde_json::from_str::<Value>(&entry.body).ok())
+        })
+        .collect::<Vec<_>>();
+
+    Ok(Json(json!(summaries)))
 }
 
-#[get("/snapshot/<time>")]
-pub fn get_snapshot(time: String) -> io::Result<NamedFile> {
-    let path = Path::new("snapshots").join(time).with_extension("json");
-    NamedFile::open(&path)
-}
\ No newline at end of file
+#[get("/snapshots/<id>")]
+pub fn get_snapshot(_auth: Auth<Curator>, dir: State<SnapshotsDir>, id: SnapshotId) -> io::Result<NamedFile> {
+    NamedFile::open(id.path(&dir))
+}
+
+/// Entities born, died and changed and seeds added and removed from one
+/// snapshot to the other
+#[get("/snapshots/<from>/diff/<to>")]
+pub fn diff_snapshots(_auth: Auth<Curator>, dir: State<SnapshotsDir>, from: SnapshotId, to: SnapshotId) -> StdResult<Json<Value>, Failure> {
+    let read = |id: &SnapshotId| id.read(&dir).map_err(|err| match err.kind() {
+        io::ErrorKind::NotFound => Failure(Status::NotFound),
+        _                       => Failure(Status::InternalServerError)
+    });
+
+    let (before, after) = (read(&from)?, read(&to)?);
+    let mut diff = snapshots::diff(&before, &after);
+    diff["from"] = json!(from.as_str());
+    diff["to"] = json!(to.as_str());
+
+    Ok(Json(diff))
+}
+
+/// Same as `/snapshots/<id>`, kept for older dashboards
+#[get("/snapshot/<id>")]
+pub fn get_snapshot_legacy(auth: Auth<Curator>, dir: State<SnapshotsDir>, id: SnapshotId) -> io::Result<NamedFile> {
+    get_snapshot(auth, dir, id)
+}
@@ -0,0 +1,11 @@
+use rocket_contrib::Json;
+use serde_json::Value;
+
+use openapi;
+use server;
+
+/// OpenAPI document of every mounted route
+#[get("/openapi.json")]
+pub fn openapi() -> Json<Value> {
+    Json(openapi::spec(&server::mounts()))
+}
@@ -11,9 +11,13 @@ use soundlines_core::db::models::Dna;
 use soundlines_core::db::models::Entity;
 
 use db_guard::*;
+use user::Auth;
+use user::Admin;
+use user::Curator;
+use listing::ListQuery;
 
 #[post("/generate")]
-pub fn generate(conn: DbConn) -> Result<&'static str> {
+pub fn generate(_auth: Auth<Admin>, conn: DbConn) -> Result<&'static str> {
     conn.execute("delete from entities", &[])?;
 
     conn.execute(r#"
@@ -41,18 +45,31 @@ pub fn generate(conn: DbConn) -> Result<&'static str> {
     Ok("")
 }
 
+/// Every entity, or a page of them with any of the `ListParams`
 #[get("/")]
-pub fn index(conn: DbConn) -> Result<Json> {
-    let entities: Vec<Entity> = conn.all()?;
-    let entities = entities.into_iter().map(Entity::into_json).collect::<Vec<_>>();
+pub fn index(query: ListQuery, conn: DbConn) -> StdResult<Json, Failure> {
+    let params = match query.0 {
+        Some(params) => params,
+        None => {
+            let entities: Vec<Entity> = conn.all().map_err(|_| Failure(Status::InternalServerError))?;
+            let entities = entities.into_iter().map(Entity::into_json).collect::<Vec<_>>();
 
-    Ok(Json(json!({
-        "entities": entities
-    })))
+            return Ok(Json(json!({
+                "entities": entities,
+                "next_cursor": null
+            })));
+        }
+    };
+
+    let page = params.plant_filter("point")?
+        .page::<Entity>(&conn, params.limit())
+        .map_err(|_| Failure(Status::InternalServerError))?;
+
+    Ok(Json(params.page_json("entities", page, |entity| entity.id, Entity::into_json)))
 }
 
 #[delete("/<id>")]
-pub fn delete(conn: DbConn, id: i32) -> StdResult<status::NoContent, Failure> {
+pub fn delete(_auth: Auth<Curator>, conn: DbConn, id: i32) -> StdResult<status::NoContent, Failure> {
     let entity = conn.get::<Entity>(id)
 	    .map_err(|_| Failure(Status::InternalServerError))?
         .ok_or(Failure(Status::BadRequest))?;
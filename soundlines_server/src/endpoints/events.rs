@@ -0,0 +1,105 @@
+use std::result::Result as StdResult;
+
+use chrono::prelude::*;
+use rocket::http::Status;
+use rocket::response::Failure;
+use rocket_contrib::Json;
+use serde_json::Value;
+
+use soundlines_core::db::models::EventKind;
+use soundlines_core::db::models::EventQuery;
+use soundlines_core::db::models::WorldEvent;
+use soundlines_core::db::models::Role;
+
+use db_guard::*;
+use user::Auth;
+use openapi::*;
+
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 1000;
+
+/// Query parameters of `/events`:
+///
+/// - `since`: RFC 3339 time, e.g. `2017-10-16T00:00:00Z`
+/// - `cell`: cell id
+/// - `kind`: comma separated event kinds
+/// - `cursor`: `next_cursor` of the previous page
+/// - `limit`: page size, at most 1000
+#[derive(FromForm, Default)]
+pub struct EventParams {
+    pub since: Option<String>,
+    pub cell: Option<i32>,
+    pub kind: Option<String>,
+    pub cursor: Option<i32>,
+    pub limit: Option<i64>
+}
+
+impl Schema for EventParams {
+    fn schema() -> Value {
+        object(&[
+            ("since", date_time()),
+            ("cell", integer()),
+            ("kind", string()),
+            ("cursor", integer()),
+            ("limit", integer())
+        ], &[])
+    }
+}
+
+impl EventParams {
+    fn query(&self) -> Option<EventQuery> {
+        let since = match self.since {
+            Some(ref since) => Some(since.parse::<DateTime<Utc>>().ok()?),
+            None            => None
+        };
+
+        let kinds = match self.kind {
+            Some(ref kinds) => kinds.split(',')
+                .map(str::trim)
+                .filter(|kind| kind.len() > 0)
+                .map(EventKind::from_str)
+                .collect::<Option<Vec<_>>>()?,
+            None => Vec::new()
+        };
+
+        Some(EventQuery { since, cell_id: self.cell, kinds, after: self.cursor })
+    }
+}
+
+/// Page of world events, oldest first. Players only see their own user id and
+/// entity owners on events, the positions of pickups and spreads would give
+/// the others away.
+#[get("/?<params>")]
+pub fn list(auth: Auth, params: EventParams, conn: DbConn) -> StdResult<Json, Failure> {
+    let user = auth.into_user();
+    let query = params.query().ok_or(Failure(Status::BadRequest))?;
+    let limit = params.limit.unwrap_or(DEFAULT_LIMIT).max(1).min(MAX_LIMIT);
+
+    let page = WorldEvent::find(&conn, &query, limit)
+        .map_err(|_| Failure(Status::InternalServerError))?;
+
+    let next_cursor = if page.has_more { page.items.last().and_then(|event| event.id) } else { None };
+    let events = page.items.into_iter().map(|mut event| {
+        if user.role < Role::Curator && event.user_id != Some(user.id) {
+            event.user_id = None;
+
+            // Spreads carry the planted entity, owner included
+            let entity = event.payload.as_object_mut()
+                .and_then(|payload| payload.get_mut("entity"))
+                .and_then(Value::as_object_mut);
+
+            if let Some(entity) = entity {
+                entity.remove("owner_id");
+            }
+        }
+
+        event.into_json()
+    }).collect::<Vec<_>>();
+
+    Ok(Json(json!({ "events": events, "next_cursor": next_cursor })))
+}
+
+#[get("/", rank = 2)]
+pub fn index(auth: Auth, conn: DbConn) -> StdResult<Json, Failure> {
+    list(auth, EventParams::default(), conn)
+}
@@ -0,0 +1,82 @@
+use rocket::State;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket_contrib::Json;
+use serde_json::Value;
+use chrono::prelude::*;
+use chrono::Duration;
+
+use soundlines_core::db;
+use soundlines_core::db::Pool;
+use soundlines_core::db::Connection;
+use soundlines_core::db::models::Heartbeat;
+
+/// How old heartbeats can be before `/ready` fails, from `ready_simulation_max_age`
+/// and `ready_external_max_age` of the server configuration (seconds)
+pub struct ReadyConfig {
+    pub simulation_max_age: i64,
+    pub external_max_age: i64
+}
+
+/// Liveness, answers as long as the server is running
+#[get("/health")]
+pub fn health() -> Json<Value> {
+    Json(json!({ "status": "ok" }))
+}
+
+/// Readiness, whether the database is reachable and migrated and the
+/// background processes are alive. Answers `503` with the failed checks.
+#[get("/ready")]
+pub fn ready(pool: State<Pool>, config: State<ReadyConfig>) -> status::Custom<Json<Value>> {
+    let conn = match pool.get() {
+        Ok(conn) => conn,
+        Err(err) => return status::Custom(Status::ServiceUnavailable, Json(json!({
+            "ready": false,
+            "checks": { "database": { "ok": false, "error": err.to_string() } }
+        })))
+    };
+
+    let checks = json!({
+        "database": database_check(&conn),
+        "migrations": migrations_check(&conn),
+        "simulation": heartbeat_check(&conn, Heartbeat::SIMULATION, config.simulation_max_age),
+        "weather": heartbeat_check(&conn, Heartbeat::WEATHER, config.external_max_age),
+        "twitter": heartbeat_check(&conn, Heartbeat::TWITTER, config.external_max_age)
+    });
+
+    let ready = checks.as_object()
+        .map(|checks| checks.values().all(|check| check["ok"] == json!(true)))
+        .unwrap_or(false);
+
+    let status = if ready { Status::Ok } else { Status::ServiceUnavailable };
+    status::Custom(status, Json(json!({ "ready": ready, "checks": checks })))
+}
+
+fn database_check(conn: &Connection) -> Value {
+    match conn.execute("select 1", &[]) {
+        Ok(_)    => json!({ "ok": true }),
+        Err(err) => json!({ "ok": false, "error": err.to_string() })
+    }
+}
+
+fn migrations_check(conn: &Connection) -> Value {
+    match db::schema_version(conn) {
+        Ok(version) => json!({
+            "ok": version.as_ref().map(String::as_str) == Some(db::SCHEMA_VERSION),
+            "version": version,
+            "expected": db::SCHEMA_VERSION
+        }),
+        Err(err) => json!({ "ok": false, "error": err.to_string() })
+    }
+}
+
+fn heartbeat_check(conn: &Connection, name: &str, max_age: i64) -> Value {
+    match Heartbeat::get(conn, name) {
+        Ok(Some(heartbeat)) => json!({
+            "ok": Utc::now().signed_duration_since(heartbeat.beat_at) <= Duration::seconds(max_age),
+            "beat_at": heartbeat.beat_at
+        }),
+        Ok(None)  => json!({ "ok": false, "beat_at": null }),
+        Err(err)  => json!({ "ok": false, "error": err.to_string() })
+    }
+}
@@ -0,0 +1,36 @@
+use std::result::Result as StdResult;
+
+use rocket::State;
+use rocket::http::Status;
+use rocket::response::Failure;
+
+use soundlines_core::db::models::UserStats;
+use soundlines_core::db::models::StatsWindow;
+use soundlines_core::db::models::LeaderboardMetric;
+
+use db_guard::*;
+use user::Auth;
+use cache::CachedJson;
+use cache::ResponseCache;
+
+const LEADERBOARD_SIZE: i64 = 50;
+
+/// Top players by the metric over the window, `window` is one of `daily`,
+/// `weekly` or `all-time`. Leaderboards are cached, so they lag behind by up
+/// to the cache's max age.
+#[get("/<window>/<metric>")]
+pub fn get(_auth: Auth, conn: DbConn, cache: State<ResponseCache>, window: String, metric: String) -> StdResult<CachedJson, Failure> {
+    let window = StatsWindow::from_str(&window).ok_or(Failure(Status::NotFound))?;
+    let metric = LeaderboardMetric::from_str(&metric).ok_or(Failure(Status::NotFound))?;
+
+    let key = format!("leaderboards/{}/{}", window.as_str(), metric.as_str());
+    cache.get_or_insert_with(&key, || {
+        UserStats::leaderboard(&conn, metric, window, LEADERBOARD_SIZE).map(|entries| json!({
+            "window": window.as_str(),
+            "metric": metric.as_str(),
+            "entries": entries
+        }).to_string())
+    })
+    .map(|entry| entry.respond())
+    .map_err(|_| Failure(Status::InternalServerError))
+}
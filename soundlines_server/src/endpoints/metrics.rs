@@ -0,0 +1,13 @@
+use rocket::State;
+use rocket::http::ContentType;
+use rocket::response::content::Content;
+
+use soundlines_core::db::Pool;
+
+use metrics::Metrics;
+
+/// Metrics in the Prometheus text format
+#[get("/metrics")]
+pub fn metrics(metrics: State<Metrics>, pool: State<Pool>) -> Content<String> {
+    Content(ContentType::Plain, metrics.render(&pool))
+}
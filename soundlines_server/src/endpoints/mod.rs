@@ -4,4 +4,13 @@ pub mod users;
 pub mod entities;
 pub mod dev;
 pub mod seeds;
-pub mod weather;
\ No newline at end of file
+pub mod weather;
+pub mod well_known;
+pub mod docs;
+pub mod metrics;
+pub mod health;
+pub mod config;
+pub mod events;
+pub mod replay;
+pub mod leaderboards;
+pub mod trades;
\ No newline at end of file
@@ -0,0 +1,77 @@
+use std::result::Result as StdResult;
+
+use chrono::prelude::*;
+use chrono::Duration;
+use rocket::State;
+use rocket::http::Status;
+use rocket::http::ContentType;
+use rocket::response::Failure;
+use rocket::response::Stream;
+use rocket::response::content::Content;
+use rocket_contrib::Json;
+use serde_json::Value;
+
+use soundlines_core::db::Pool;
+
+use db_guard::*;
+use user::Auth;
+use user::Curator;
+use openapi::*;
+use replay;
+use replay::World;
+use replay::ReplayStream;
+use replay::ReplayStreams;
+use snapshots::SnapshotsDir;
+use rocket_extensions::DateTimeUtc;
+
+/// Query parameters of `/replay/stream`:
+///
+/// - `from` and `until`: RFC 3339 times
+/// - `step`: world time between frames in seconds, 60 by default
+/// - `speed`: how much faster than real time the frames are sent, 1 by default
+#[derive(FromForm)]
+pub struct ReplayParams {
+    pub from: String,
+    pub until: String,
+    pub step: Option<i64>,
+    pub speed: Option<f64>
+}
+
+impl Schema for ReplayParams {
+    fn schema() -> Value {
+        object(&[
+            ("from", date_time()),
+            ("until", date_time()),
+            ("step", integer()),
+            ("speed", number())
+        ], &["from", "until"])
+    }
+}
+
+/// Entities, seeds and cells at a past time. Cells are the ones of the last
+/// snapshot before it, and empty before the first snapshot
+#[get("/at/<time>")]
+pub fn world_at(_auth: Auth<Curator>, conn: DbConn, dir: State<SnapshotsDir>, time: DateTimeUtc) -> StdResult<Json, Failure> {
+    World::at(&conn, &dir, *time)
+        .map(|world| Json(world.to_json()))
+        .map_err(|_| Failure(Status::InternalServerError))
+}
+
+/// The world every `step` from `from` to `until` as newline delimited JSON,
+/// sent at `speed` times real time, for at most an hour. Fails with `Service
+/// Unavailable` when `replay::MAX_STREAMS` streams are being sent already.
+#[get("/stream?<params>")]
+pub fn stream(_auth: Auth<Curator>, pool: State<Pool>, streams: State<ReplayStreams>, dir: State<SnapshotsDir>, params: ReplayParams) -> StdResult<Content<Stream<ReplayStream>>, Failure> {
+    let from = params.from.parse::<DateTime<Utc>>().map_err(|_| Failure(Status::BadRequest))?;
+    let until = params.until.parse::<DateTime<Utc>>().map_err(|_| Failure(Status::BadRequest))?;
+    let step = Duration::seconds(params.step.unwrap_or(60));
+    let speed = params.speed.unwrap_or(1.0);
+
+    replay::check_stream(from, until, step, speed).map_err(|_| Failure(Status::BadRequest))?;
+
+    let slot = streams.slot().ok_or(Failure(Status::ServiceUnavailable))?;
+    let stream = ReplayStream::new(pool.inner().clone(), Some(slot), &dir, from, until, step, speed)
+        .map_err(|_| Failure(Status::InternalServerError))?;
+
+    Ok(Content(ContentType::new("application", "x-ndjson"), Stream::from(stream)))
+}
@@ -1,32 +1,53 @@
 use std::error::Error;
 use std::collections::HashMap;
 
+use rocket::State;
 use rocket::http::Status;
 use rocket::response::Failure;
 use rocket_contrib::Json;
 use serde_json::Value;
+use chrono::prelude::*;
+use chrono::Duration;
 
 use soundlines_core::db::models::Dna;
 use soundlines_core::db::models::Cell;
 use soundlines_core::db::models::Seed;
-use soundlines_core::db::models::Entity;
+use soundlines_core::db::models::SeedOffer;
+use soundlines_core::db::models::InventorySeed;
 use soundlines_core::db::models::PlantSetting;
 use soundlines_core::db::extensions::*;
 use soundlines_core::db::Result as DbResult;
+use soundlines_core::db::Connection;
 use soundlines_core::postgis::ewkb::Point;
 
 use soundlines_simlib::sim_seed::SeedDraft;
 use soundlines_simlib::sim_seed::generate as generate_seed;
+use soundlines_simlib::sim_seed::choose_cell;
 
 use user::Auth;
+use user::Curator;
 use db_guard::DbConn;
+use listing::ListQuery;
+use openapi::*;
+use metrics::Metrics;
 
+/// Every seed, or a page of them with any of the `ListParams`
 #[get("/")]
-pub fn index(conn: DbConn) -> DbResult<Json> {
-	conn.all::<Seed>()
-		.map(|seeds| seeds.into_iter().map(Seed::into_json).collect::<Vec<_>>())
-		.map(|seeds| json!({ "seeds": seeds }))
-		.map(Json)
+pub fn index(query: ListQuery, conn: DbConn) -> Result<Json, Failure> {
+	let params = match query.0 {
+		Some(params) => params,
+		None => {
+			let seeds = conn.all::<Seed>().map_err(|_| Failure(Status::InternalServerError))?;
+			let seeds = seeds.into_iter().map(Seed::into_json).collect::<Vec<_>>();
+			return Ok(Json(json!({ "seeds": seeds, "next_cursor": null })));
+		}
+	};
+
+	let page = params.plant_filter("point")?
+		.page::<Seed>(&conn, params.limit())
+		.map_err(|_| Failure(Status::InternalServerError))?;
+
+	Ok(Json(params.page_json("seeds", page, |seed| seed.id.unwrap_or(-1), Seed::into_json)))
 }
 
 #[derive(Deserialize)]
@@ -34,44 +55,99 @@ pub struct PickupPayload {
 	id: i32
 }
 
+impl Schema for PickupPayload {
+	fn schema() -> Value {
+		object(&[("id", integer())], &["id"])
+	}
+}
+
 #[post("/pickup", data = "<payload>")]
-pub fn pickup(_auth: Auth, payload: Json<PickupPayload>, conn: DbConn) -> DbResult<Option<Json>> {
+pub fn pickup(auth: Auth, payload: Json<PickupPayload>, conn: DbConn, metrics: State<Metrics>) -> DbResult<Option<Json>> {
+	let user = auth.into_user();
 	let seed_id = payload.into_inner().id;
 
-	let seed = match conn.get::<Seed>(seed_id)? {
+	let seed = match InventorySeed::pickup(&*conn, seed_id, user.id)? {
 		Some(seed) => seed,
 		None => return Ok(None)
 	};
 
-	conn.delete::<Seed>(seed_id)?;
-
+	metrics.seed_picked();
 	Ok(Some(Json(seed.into_json())))
 }
 
-#[get("/get/<count>")]
-pub fn get(_auth: Auth, conn: DbConn, count: u32) -> DbResult<Json<Value>> {
+const DEFAULT_SEED_RADIUS: f64 = 100.0;
+const MAX_SEED_RADIUS: f64 = 500.0;
+/// Most seeds generated for a player at once
+const MAX_SEED_COUNT: u32 = 20;
+/// Minutes generated seeds can be picked up for
+const SEED_OFFER_TTL_MINS: i64 = 10;
+
+/// Where the player is, seeds are generated in the cells within `radius`
+/// meters of it
+#[derive(FromForm)]
+pub struct SeedArea {
+	latitude: f64,
+	longitude: f64,
+	radius: Option<f64>
+}
+
+impl Schema for SeedArea {
+	fn schema() -> Value {
+		object(&[("latitude", number()), ("longitude", number()), ("radius", number())], &["latitude", "longitude"])
+	}
+}
+
+/// Seeds around the player which only they can pick up, for 10 minutes
+#[get("/get/<count>?<area>")]
+pub fn get(auth: Auth, conn: DbConn, count: u32, area: SeedArea) -> Result<Json<Value>, Failure> {
+	if count > MAX_SEED_COUNT {
+		return Err(Failure(Status::BadRequest));
+	}
+
+	offer_seeds(&conn, auth.into_user().id, count, area).map_err(|_| Failure(Status::InternalServerError))
+}
+
+fn offer_seeds(conn: &Connection, user_id: i32, count: u32, area: SeedArea) -> DbResult<Json<Value>> {
 	let plant_settings = conn.all::<PlantSetting>()?;
 	let prefabs = plant_settings.iter().map(|setting| {
 		(setting.id.unwrap(), &setting.prefab)
 	}).collect::<HashMap<_, _>>();
 
-	let cell = conn.first::<Cell>()?.expect("No cell found in database");
+	let location = Point::new(area.longitude, area.latitude, Some(4326));
+	let radius = area.radius.unwrap_or(DEFAULT_SEED_RADIUS).max(0.0).min(MAX_SEED_RADIUS);
+	let cells = Cell::find_within(conn, &location, radius)?;
+
+	// Player is away from the grid
+	if cells.len() == 0 || plant_settings.len() == 0 {
+		return Ok(Json(json!({ "seeds": [] })));
+	}
 
 	let mut dnas_to_insert = Vec::<Dna>::with_capacity(count as usize);
 	let mut locations = Vec::<Point>::with_capacity(count as usize);
+	let mut cell_ids = Vec::<i32>::with_capacity(count as usize);
 
 	for _ in 0..count {
-		let SeedDraft { dna, location, .. } = generate_seed(&plant_settings, &cell);
+		let cell = choose_cell(&cells).expect("Cells to choose from");
+		let SeedDraft { dna, location, cell_id } = generate_seed(&plant_settings, cell);
 		dnas_to_insert.push(dna);
 		locations.push(location);
+		cell_ids.push(cell_id);
 	}
 
 	let dnas: Vec<Dna> = conn.insert_batch_return(&dnas_to_insert, true)?;
 	let seeds = locations.into_iter().enumerate().map(|(i, location)| {
-		Seed::new(dnas[i].id, location, cell.id, dnas[i].setting_id, prefabs[&dnas[i].setting_id].to_owned())
-			.into_json()
+		Seed::new(dnas[i].id, location, cell_ids[i], dnas[i].setting_id, prefabs[&dnas[i].setting_id].to_owned())
 	}).collect::<Vec<_>>();
 
+	// Kept out of the world, `/seeds/pickup` takes them by their ids
+	SeedOffer::delete_expired(conn)?;
+	let expires_at = Utc::now() + Duration::minutes(SEED_OFFER_TTL_MINS);
+	let offers = seeds.into_iter().map(|seed| SeedOffer::new(user_id, seed, expires_at)).collect::<Vec<_>>();
+	let seeds = conn.insert_batch_return(&offers, true)?
+		.into_iter()
+		.map(SeedOffer::into_json)
+		.collect::<Vec<_>>();
+
 	Ok(Json(json!({
 		"seeds": seeds
 	})))
@@ -84,8 +160,18 @@ pub struct DeployPayload {
 	pub prefab: Option<String>
 }
 
+impl Schema for DeployPayload {
+	fn schema() -> Value {
+		object(&[
+			("count", integer()),
+			("cell_ids", array(integer())),
+			("prefab", string())
+		], &["count", "cell_ids"])
+	}
+}
+
 #[post("/deploy", data = "<payload>")]
-pub fn deploy(conn: DbConn, payload: Json<DeployPayload>) -> Result<Json, Failure> {
+pub fn deploy(_auth: Auth<Curator>, conn: DbConn, payload: Json<DeployPayload>) -> Result<Json, Failure> {
 	let DeployPayload { count, cell_ids, prefab } = payload.into_inner();
 
 	println!("Getting plant settings");
@@ -145,12 +231,23 @@ pub struct SpreadSeedPayload {
 	latitude: f64,
 	longitude: f64,
 	nickname: String,
-	dna_id: i32,
-	setting_id: i32
+	dna_id: i32
+}
+
+impl Schema for SpreadSeedPayload {
+	fn schema() -> Value {
+		object(&[
+			("latitude", number()),
+			("longitude", number()),
+			("nickname", string()),
+			("dna_id", integer())
+		], &["latitude", "longitude", "nickname", "dna_id"])
+	}
 }
 
 #[post("/spread", data = "<payload>")]
-pub fn spread(_auth: Auth, payload: Json<SpreadSeedPayload>, conn: DbConn) -> Result<Json, Failure> {
+pub fn spread(auth: Auth, payload: Json<SpreadSeedPayload>, conn: DbConn, metrics: State<Metrics>) -> Result<Json, Failure> {
+	let user = auth.into_user();
 	let payload = payload.into_inner();
 
 	let location = Point::new(payload.longitude, payload.latitude, Some(4326));
@@ -159,18 +256,88 @@ pub fn spread(_auth: Auth, payload: Json<SpreadSeedPayload>, conn: DbConn) -> Re
 		.map_err(|_| Failure(Status::InternalServerError))?
 		.ok_or(Failure(Status::BadRequest))?;
 
-	let dna = conn.get::<Dna>(payload.dna_id)
-		.map_err(|_| Failure(Status::InternalServerError))?
-		.ok_or(Failure(Status::BadRequest))?;
-
-	let setting = conn.get::<PlantSetting>(payload.setting_id)
+	// Only seeds in the user's inventory can be spread
+	let entity = InventorySeed::spread(&*conn, user.id, payload.dna_id, location, cell.id, payload.nickname)
 		.map_err(|_| Failure(Status::InternalServerError))?
 		.ok_or(Failure(Status::BadRequest))?;
 
-	let mut entity = Entity::new(location, cell.id, &setting, &dna);
-	entity.nickname = payload.nickname;
-
-	let entity = conn.insert(&entity).map_err(|_| Failure(Status::InternalServerError))?;
-
+	metrics.entity_spread();
 	Ok(Json(entity.to_json()))
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	use rocket;
+	use rocket::http::Header;
+	use rocket::http::ContentType;
+	use rocket::local::Client;
+	use rocket_jwt::Jwt;
+	use rocket_jwt::JwtConfig;
+	use serde_json;
+
+	use soundlines_core::db;
+	use soundlines_core::db::models::User;
+	use soundlines_core::db::models::Role;
+	use soundlines_config;
+
+	use cache::ResponseCache;
+
+	/// Needs a migrated database with cells and plant settings, from the
+	/// `[database]` configuration. Run with `cargo test -- --ignored`.
+	#[test]
+	#[ignore]
+	fn generated_seeds_can_be_picked_up() {
+		let config = soundlines_config::load().expect("Invalid configuration");
+		let pool = db::init_pool(&config.database);
+		let conn = pool.get().unwrap();
+
+		let cell = conn.first::<Cell>().unwrap().expect("No cells, run soundlines_sim gencells");
+		let (latitude, longitude) = cell.centroid();
+
+		let user = conn.insert(&User::new(Role::Player)).unwrap();
+		let user_id = user.id;
+		let jwt_config = JwtConfig::new("secret");
+		let token = Jwt::new(user).subject(user_id.to_string()).encode(&jwt_config).unwrap();
+		let auth = || Header::new("Authorization", format!("Bearer {}", token));
+
+		let rocket = rocket::ignite()
+			.mount("/seeds", routes![get, pickup])
+			.manage(pool.clone())
+			.manage(jwt_config)
+			.manage(ResponseCache::new())
+			.manage(Metrics::new());
+		let client = Client::new(rocket).unwrap();
+
+		let mut response = client.get(format!("/seeds/get/1?latitude={}&longitude={}", latitude, longitude))
+			.header(auth())
+			.dispatch();
+		assert_eq!(response.status(), Status::Ok);
+
+		let body: Value = serde_json::from_str(&response.body_string().unwrap()).unwrap();
+		let seed_id = body["seeds"][0]["id"].as_i64().expect("Generated seed without an id");
+		let on_ground = conn.get::<Seed>(seed_id as i32).unwrap().is_some();
+
+		let too_many = client.get(format!("/seeds/get/{}?latitude={}&longitude={}", MAX_SEED_COUNT + 1, latitude, longitude))
+			.header(auth())
+			.dispatch()
+			.status();
+
+		let response = client.post("/seeds/pickup")
+			.header(ContentType::JSON)
+			.header(auth())
+			.body(json!({ "id": seed_id }).to_string())
+			.dispatch();
+		let picked_up = response.status();
+
+		let held = InventorySeed::for_user(&conn, user_id).unwrap();
+		User::delete_data(&conn, user_id).unwrap();
+
+		assert!(!on_ground, "Generated seeds should stay out of the world until picked up");
+		assert_eq!(too_many, Status::BadRequest);
+		assert_eq!(picked_up, Status::Ok);
+		assert_eq!(held.len(), 1);
+		assert_eq!(Some(held[0].dna_id), body["seeds"][0]["dna_id"].as_i64().map(|id| id as i32));
+	}
+}
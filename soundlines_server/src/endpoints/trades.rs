@@ -0,0 +1,176 @@
+use std::result::Result as StdResult;
+use std::collections::HashSet;
+
+use chrono::Duration;
+use rocket::State;
+use rocket::http::Status;
+use rocket::response::status;
+use rocket_contrib::Json;
+use serde_json::Value;
+
+use soundlines_core::db::models::Trade;
+use soundlines_core::db::models::TradeStatus;
+
+use db_guard::*;
+use user::Auth;
+use openapi::*;
+use presence::Presence;
+
+/// Seeds on either side of a trade
+const MAX_TRADE_SEEDS: usize = 20;
+const HISTORY_SIZE: i64 = 100;
+
+/// How close players have to be to trade and how long offers wait for an
+/// answer, from `trade_distance_meters` and `trade_offer_ttl` (seconds) of
+/// the server configuration
+pub struct TradeConfig {
+    pub max_distance: f64,
+    pub offer_ttl: i64
+}
+
+#[derive(Deserialize)]
+pub struct TradeOffer {
+    pub to_user_id: i32,
+    #[serde(default)]
+    pub offered_seed_ids: Vec<i32>,
+    #[serde(default)]
+    pub requested_seed_ids: Vec<i32>
+}
+
+impl Schema for TradeOffer {
+    fn schema() -> Value {
+        object(&[
+            ("to_user_id", integer()),
+            ("offered_seed_ids", array(integer())),
+            ("requested_seed_ids", array(integer()))
+        ], &["to_user_id"])
+    }
+}
+
+impl TradeOffer {
+    fn validate(&self, user_id: i32) -> Option<&'static str> {
+        let seed_ids = self.offered_seed_ids.iter().chain(self.requested_seed_ids.iter()).collect::<HashSet<_>>();
+
+        if self.to_user_id == user_id {
+            Some("self_trade")
+        } else if seed_ids.is_empty() {
+            Some("no_seeds")
+        } else if self.offered_seed_ids.len() > MAX_TRADE_SEEDS || self.requested_seed_ids.len() > MAX_TRADE_SEEDS {
+            Some("too_many_seeds")
+        } else if seed_ids.len() < self.offered_seed_ids.len() + self.requested_seed_ids.len() {
+            Some("duplicate_seeds")
+        } else {
+            None
+        }
+    }
+}
+
+type TradeResult = StdResult<Json<Trade>, status::Custom<Json<Value>>>;
+
+fn error(status: Status, error: &str) -> status::Custom<Json<Value>> {
+    status::Custom(status, Json(json!({ "error": error })))
+}
+
+fn internal_error<E>(_: E) -> status::Custom<Json<Value>> {
+    status::Custom(Status::InternalServerError, Json(json!({})))
+}
+
+/// Whether both users are online and within `max_distance`, the other user
+/// also has to be shown to others
+fn nearby(presence: &Presence, config: &TradeConfig, user_id: i32, other_id: i32) -> bool {
+    match (presence.get(user_id), presence.get(other_id)) {
+        (Some(user), Some(other)) => !other.hidden && user.distance_to(&other) <= config.max_distance,
+        _ => false
+    }
+}
+
+/// Offers seeds of the user's inventory for seeds of a nearby player. Answers
+/// `409` when the players aren't close enough or don't hold the seeds.
+#[post("/", data = "<offer>")]
+pub fn offer(auth: Auth, conn: DbConn, presence: State<Presence>, config: State<TradeConfig>, offer: Json<TradeOffer>) -> TradeResult {
+    let user_id = auth.into_user().id;
+    let offer = offer.into_inner();
+
+    if let Some(invalid) = offer.validate(user_id) {
+        return Err(error(Status::BadRequest, invalid));
+    }
+
+    if !nearby(&presence, &config, user_id, offer.to_user_id) {
+        return Err(error(Status::Conflict, "not_nearby"));
+    }
+
+    let trade = Trade::new(user_id, offer.to_user_id, offer.offered_seed_ids, offer.requested_seed_ids, Duration::seconds(config.offer_ttl));
+    Trade::offer(&conn, &trade)
+        .map_err(internal_error)?
+        .map(Json)
+        .ok_or_else(|| error(Status::Conflict, "seeds_not_held"))
+}
+
+/// Pending offers made by and to the user, newest first
+#[get("/")]
+pub fn pending(auth: Auth, conn: DbConn) -> StdResult<Json<Value>, status::Custom<Json<Value>>> {
+    let trades = Trade::pending_for(&conn, auth.into_user().id).map_err(internal_error)?;
+    Ok(Json(json!({ "trades": trades })))
+}
+
+/// Last resolved trades of the user, newest first
+#[get("/history")]
+pub fn history(auth: Auth, conn: DbConn) -> StdResult<Json<Value>, status::Custom<Json<Value>>> {
+    let trades = Trade::history(&conn, auth.into_user().id, HISTORY_SIZE).map_err(internal_error)?;
+    Ok(Json(json!({ "trades": trades })))
+}
+
+#[get("/<id>", rank = 2)]
+pub fn get(auth: Auth, conn: DbConn, id: i32) -> TradeResult {
+    let user_id = auth.into_user().id;
+
+    Trade::get(&conn, id)
+        .map_err(internal_error)?
+        .filter(|trade| trade.from_user_id == user_id || trade.to_user_id == user_id)
+        .map(Json)
+        .ok_or_else(|| error(Status::NotFound, "not_found"))
+}
+
+/// Exchanges the seeds of an offer made to the user, the players have to
+/// still be nearby. Answers `409` with the failed trade when one of them
+/// doesn't hold the seeds anymore.
+#[post("/<id>/accept")]
+pub fn accept(auth: Auth, conn: DbConn, presence: State<Presence>, config: State<TradeConfig>, id: i32) -> TradeResult {
+    let user_id = auth.into_user().id;
+
+    let from_user_id = Trade::get(&conn, id)
+        .map_err(internal_error)?
+        .filter(|trade| trade.to_user_id == user_id)
+        .map(|trade| trade.from_user_id)
+        .ok_or_else(|| error(Status::NotFound, "not_found"))?;
+
+    if !nearby(&presence, &config, user_id, from_user_id) {
+        return Err(error(Status::Conflict, "not_nearby"));
+    }
+
+    let trade = Trade::accept(&conn, id, user_id)
+        .map_err(internal_error)?
+        .ok_or_else(|| error(Status::NotFound, "not_found"))?;
+
+    if trade.status == TradeStatus::Failed {
+        return Err(status::Custom(Status::Conflict, Json(json!({ "error": "seeds_not_held", "trade": trade }))));
+    }
+
+    Ok(Json(trade))
+}
+
+#[post("/<id>/decline")]
+pub fn decline(auth: Auth, conn: DbConn, id: i32) -> TradeResult {
+    Trade::decline(&conn, id, auth.into_user().id)
+        .map_err(internal_error)?
+        .map(Json)
+        .ok_or_else(|| error(Status::NotFound, "not_found"))
+}
+
+#[post("/<id>/cancel")]
+pub fn cancel(auth: Auth, conn: DbConn, id: i32) -> TradeResult {
+    Trade::cancel(&conn, id, auth.into_user().id)
+        .map_err(internal_error)?
+        .map(Json)
+        .ok_or_else(|| error(Status::NotFound, "not_found"))
+}
@@ -1,21 +1,33 @@
 use serde_json::Value;
+use serde_json::Map;
+
 use chrono::prelude::*;
 
 use rocket::State;
 use rocket::http::Status;
+use rocket::response::status;
 use rocket_contrib::Json;
 use rocket_extensions::*;
 
 use rocket_jwt::Jwt;
 use rocket_jwt::JwtConfig;
+use rocket_jwt::RefreshJwt;
 
 use soundlines_core::db::Result;
 use soundlines_core::db::models::GpsReading;
 use soundlines_core::db::models::Entity;
+use soundlines_core::db::models::DeadEntity;
 use soundlines_core::db::models::Seed;
+use soundlines_core::db::models::InventorySeed;
 use soundlines_core::db::models::Cell;
 use soundlines_core::db::models::CellNeighbours;
 use soundlines_core::db::models::User;
+use soundlines_core::db::models::Role;
+use soundlines_core::db::models::UserStats;
+use soundlines_core::db::models::UserProfile;
+use soundlines_core::db::models::UserPrivacy;
+use soundlines_core::db::models::Device;
+use soundlines_core::db::models::StatsWindow;
 use soundlines_core::db::extensions::*;
 use soundlines_core::postgis::ewkb::Point;
 
@@ -23,26 +35,281 @@ use std::result::Result as StdResult;
 
 use db_guard::*;
 use user::RegisterPayload;
+use user::is_trusted;
+use user::is_revoked_without_subject;
+use user::Auth;
+use user::Curator;
+use user::Admin;
+use client_version::SupportedClient;
+use cache::ResponseCache;
+use privacy::LocationPrivacy;
+use presence::Presence;
 
+/// Tokens of the device's user, registering the same device again returns the
+/// same user. Answers `400` for an invalid device id and `403` for an invalid
+/// link code.
 #[post("/register")]
-pub fn register(_payload: Jwt<RegisterPayload>, conn: DbConn, jwt_config: State<JwtConfig>) -> StdResult<Json, Status> {
-    let user = User { id: -1, created_at: Utc::now() };
-    let user = conn.insert(&user).map_err(|_| Status::InternalServerError)?;
+pub fn register(_client: SupportedClient, payload: Jwt<RegisterPayload>, conn: DbConn, jwt_config: State<JwtConfig>) -> StdResult<Json, Status> {
+    let RegisterPayload { device_id, link_code, .. } = payload.into_inner();
+
+    let user = match device_id {
+        Some(ref device_id) if !Device::is_valid_id(device_id) => return Err(Status::BadRequest),
+        Some(ref device_id) => Device::register(&conn, device_id, link_code.as_ref().map(String::as_str))
+            .map_err(|_| Status::InternalServerError)?
+            .ok_or(Status::Forbidden)?,
+        None => conn.insert(&User::new(Role::Player)).map_err(|_| Status::InternalServerError)?
+    };
+
+    tokens_json(user, &jwt_config)
+}
+
+/// Issues a new access token, and rotates the refresh token, for the user of
+/// a valid refresh token
+#[post("/refresh")]
+pub fn refresh(_client: SupportedClient, token: RefreshJwt<User>, conn: DbConn, jwt_config: State<JwtConfig>) -> StdResult<Json, Status> {
+    if !is_trusted(&token.0, &jwt_config) || is_revoked_without_subject(&token.0, token.0.payload.id, &jwt_config) {
+        return Err(Status::Unauthorized);
+    }
+
+    let user_id = token.into_inner().id;
+
+    // Role might have changed since the refresh token is issued
+    let user = conn.get::<User>(user_id)
+        .map_err(|_| Status::InternalServerError)?
+        .ok_or(Status::Unauthorized)?;
+
+    if user.is_deregistered() {
+        return Err(Status::Unauthorized);
+    }
+
+    tokens_json(user, &jwt_config)
+}
+
+/// Revokes the access token sent with the request, client is expected to drop
+/// its refresh token as well
+#[post("/me/logout")]
+pub fn logout(_auth: Auth, token: Jwt<User>, jwt_config: State<JwtConfig>) -> StdResult<status::NoContent, Status> {
+    token.revoke(&jwt_config).map_err(|_| Status::InternalServerError)?;
+    Ok(status::NoContent)
+}
+
+/// Revokes all tokens issued to the authenticated user so far, e.g. when a
+/// device is lost
+#[post("/me/revoke")]
+pub fn revoke_own(auth: Auth, jwt_config: State<JwtConfig>) -> StdResult<status::NoContent, Status> {
+    revoke_user_tokens(auth.into_user().id, &jwt_config)
+}
+
+#[post("/<id>/revoke", rank = 2)]
+pub fn revoke(_auth: Auth<Admin>, id: i32, jwt_config: State<JwtConfig>) -> StdResult<status::NoContent, Status> {
+    revoke_user_tokens(id, &jwt_config)
+}
+
+/// Deregisters the device, none of the tokens issued to it are accepted
+/// afterwards and it can't refresh them. `Auth` rejects deregistered users
+/// even without a revocation store.
+#[delete("/me")]
+pub fn deregister(auth: Auth, conn: DbConn, jwt_config: State<JwtConfig>, presence: State<Presence>) -> StdResult<status::NoContent, Status> {
+    let user_id = auth.into_user().id;
+
+    // Revoked before deregistering, so a failure doesn't leave the client
+    // thinking a done deregistration failed
+    if jwt_config.revocations.is_some() {
+        revoke_user_tokens(user_id, &jwt_config)?;
+    }
+
+    User::deregister(&conn, user_id).map_err(|_| Status::InternalServerError)?;
+    presence.leave(&conn, user_id).map_err(|_| Status::InternalServerError)?;
+
+    Ok(status::NoContent)
+}
+
+/// Seeds picked up by the user which can be spread
+#[get("/me/inventory")]
+pub fn inventory(auth: Auth, conn: DbConn) -> Result<Json> {
+    let seeds = InventorySeed::for_user(&conn, auth.into_user().id)?
+        .into_iter()
+        .map(InventorySeed::into_json)
+        .collect::<Vec<_>>();
+
+    Ok(Json(json!({ "seeds": seeds })))
+}
+
+/// Plants planted by the user, living ones with their current state and dead
+/// ones as they were when they died
+#[get("/me/garden")]
+pub fn own_garden(auth: Auth, conn: DbConn) -> Result<Json> {
+    garden_json(&conn, auth.into_user().id)
+}
+
+#[get("/<id>/garden", rank = 2)]
+pub fn garden(_auth: Auth, id: i32, conn: DbConn) -> Result<Json> {
+    garden_json(&conn, id)
+}
+
+fn garden_json(conn: &DbConn, user_id: i32) -> Result<Json> {
+    let living = Entity::owned_by(conn, user_id)?
+        .into_iter()
+        .map(Entity::into_json)
+        .collect::<Vec<_>>();
+
+    let dead = DeadEntity::owned_by(conn, user_id)?
+        .into_iter()
+        .map(DeadEntity::into_json)
+        .collect::<Vec<_>>();
 
     Ok(Json(json!({
-        "user_id": user.id,
-        "token": Jwt(user).encode(&jwt_config)?
+        "user_id": user_id,
+        "living": living,
+        "dead": dead
     })))
 }
 
+/// Statistics of the user for today, the last 7 days and all time
+#[get("/me/stats")]
+pub fn own_stats(auth: Auth, conn: DbConn) -> Result<Json> {
+    stats_json(&conn, auth.into_user().id)
+}
 
+#[get("/<id>/stats", rank = 2)]
+pub fn stats(_auth: Auth, id: i32, conn: DbConn) -> Result<Json> {
+    stats_json(&conn, id)
+}
+
+fn stats_json(conn: &DbConn, user_id: i32) -> Result<Json> {
+    Ok(Json(json!({
+        "user_id": user_id,
+        "daily": UserStats::for_user(conn, user_id, StatsWindow::Daily)?,
+        "weekly": UserStats::for_user(conn, user_id, StatsWindow::Weekly)?,
+        "all_time": UserStats::for_user(conn, user_id, StatsWindow::AllTime)?
+    })))
+}
+
+#[get("/me/profile")]
+pub fn own_profile(auth: Auth, conn: DbConn) -> StdResult<Json<UserProfile>, Status> {
+    profile_json(&conn, auth.into_user().id)
+}
+
+#[get("/<id>/profile", rank = 2)]
+pub fn profile(_auth: Auth, id: i32, conn: DbConn) -> StdResult<Json<UserProfile>, Status> {
+    profile_json(&conn, id)
+}
+
+fn profile_json(conn: &DbConn, user_id: i32) -> StdResult<Json<UserProfile>, Status> {
+    User::profile(conn, user_id)
+        .map_err(|_| Status::InternalServerError)?
+        .map(Json)
+        .ok_or(Status::NotFound)
+}
+
+/// Replaces the profile, fields left out are cleared
+#[put("/me/profile", data = "<profile>")]
+pub fn update_profile(auth: Auth, conn: DbConn, profile: Json<UserProfile>) -> StdResult<Json<UserProfile>, status::Custom<Json<Value>>> {
+    let user_id = auth.into_user().id;
+    let profile = profile.into_inner();
+
+    let errors = profile.validate();
+    if !errors.is_empty() {
+        return Err(status::Custom(Status::BadRequest, Json(json!({ "errors": errors }))));
+    }
+
+    let internal_error = |_| status::Custom(Status::InternalServerError, Json(json!({})));
+    User::update_profile(&conn, user_id, &profile).map_err(&internal_error)?;
+
+    User::profile(&conn, user_id)
+        .map_err(&internal_error)?
+        .map(Json)
+        .ok_or_else(|| status::Custom(Status::NotFound, Json(json!({}))))
+}
+
+#[get("/me/devices")]
+pub fn devices(auth: Auth, conn: DbConn) -> Result<Json<Vec<Device>>> {
+    Device::for_user(&conn, auth.into_user().id).map(Json)
+}
+
+/// One time code to register another device as the user, valid for 10 minutes
+#[post("/me/devices/link")]
+pub fn link_device(auth: Auth, conn: DbConn) -> Result<Json> {
+    let (code, expires_at) = Device::create_link_code(&conn, auth.into_user().id)?;
+    Ok(Json(json!({ "code": code, "expires_at": expires_at })))
+}
+
+/// Removes a device from the user, it gets a new player when it registers again
+#[delete("/me/devices/<id>")]
+pub fn unlink_device(auth: Auth, id: i32, conn: DbConn) -> StdResult<status::NoContent, Status> {
+    match Device::unlink(&conn, auth.into_user().id, id) {
+        Ok(true)  => Ok(status::NoContent),
+        Ok(false) => Err(Status::NotFound),
+        Err(_)    => Err(Status::InternalServerError)
+    }
+}
+
+fn revoke_user_tokens(user_id: i32, jwt_config: &JwtConfig) -> StdResult<status::NoContent, Status> {
+    let store = jwt_config.revocations.as_ref().ok_or(Status::NotImplemented)?;
+    store.revoke_subject(&user_id.to_string(), Utc::now().timestamp())
+        .map_err(|_| Status::InternalServerError)?;
+
+    Ok(status::NoContent)
+}
+
+fn tokens_json(user: User, jwt_config: &JwtConfig) -> StdResult<Json, Status> {
+    let user_id = user.id;
+    let token = Jwt::new(user).subject(user_id.to_string());
+
+    Ok(Json(json!({
+        "user_id": user_id,
+        "token": token.encode(jwt_config)?,
+        "refresh_token": token.encode_refresh(jwt_config)?,
+        "expires_in": jwt_config.lifetime.map(|lifetime| lifetime.as_secs())
+    })))
+}
+
+
+/// Whether the user is shown to other players
+#[get("/me/privacy")]
+pub fn own_privacy(auth: Auth, conn: DbConn) -> StdResult<Json<UserPrivacy>, Status> {
+    User::privacy(&conn, auth.into_user().id)
+        .map_err(|_| Status::InternalServerError)?
+        .map(Json)
+        .ok_or(Status::NotFound)
+}
+
+/// Hidden users are left out of the other players' `others` and of the
+/// location endpoints
+#[put("/me/privacy", data = "<privacy>")]
+pub fn update_privacy(auth: Auth, conn: DbConn, presence: State<Presence>, privacy: Json<UserPrivacy>) -> Result<Json<UserPrivacy>> {
+    let user_id = auth.into_user().id;
+    let privacy = privacy.into_inner();
+    User::update_privacy(&conn, user_id, &privacy)?;
+    presence.set_hidden(user_id, privacy.hidden);
+    Ok(Json(privacy))
+}
+
+/// Deletes the readings, position, inventory, statistics, devices and profile
+/// of the user, and takes it offline. Its plants and the world events stay,
+/// without the user. The account itself is kept, `DELETE /users/me`
+/// deregisters it.
+#[delete("/me/data")]
+pub fn delete_data(auth: Auth, conn: DbConn, cache: State<ResponseCache>, presence: State<Presence>) -> Result<status::NoContent> {
+    let user_id = auth.into_user().id;
+
+    // Before deleting, so the `leave` event is detached from the user as well
+    presence.leave(&conn, user_id)?;
+    User::delete_data(&conn, user_id)?;
+    cache.invalidate("leaderboards");
+    Ok(status::NoContent)
+}
+
+/// Positions of the online users shown to others, fuzzed by `location_privacy`,
+/// and how many of them are in each cell
 #[get("/location")]
-pub fn location(conn: DbConn) -> Result<Json> {
-    let user_locations = User::get_all_locations(&*conn, None)?;
+pub fn location(_auth: Auth<Curator>, conn: DbConn, privacy: State<LocationPrivacy>, presence: State<Presence>) -> Result<Json> {
+    let user_locations = presence.others(None);
 
     let mut locations = vec![];
 	for user_location in user_locations.into_iter() {
-	    let point = Point::new(user_location.longitude, user_location.latitude, Some(4326));
+	    let (latitude, longitude) = privacy.apply(&user_location);
+	    let point = Point::new(longitude, latitude, Some(4326));
 		let CellNeighbours { cells, entities, seeds, .. } =
 			Cell::find_neighbors(&conn, &point, 120.0)?;
 
@@ -53,8 +320,8 @@ pub fn location(conn: DbConn) -> Result<Json> {
 		locations.push(json!({
             "user_id": user_location.id,
             "location": {
-                "latitude": user_location.latitude,
-                "longitude": user_location.longitude
+                "latitude": latitude,
+                "longitude": longitude
             },
             "cell_id": user_location.cell_id,
             "neighbor_cells": neighbors,
@@ -63,13 +330,19 @@ pub fn location(conn: DbConn) -> Result<Json> {
         }));
     }
 
+	let occupancy = presence.occupancy()
+	    .into_iter()
+	    .map(|(cell_id, count)| (cell_id.to_string(), json!(count)))
+	    .collect::<Map<_, _>>();
+
 	Ok(Json(json!({
-		"locations": locations
+		"locations": locations,
+		"occupancy": occupancy
 	})))
 }
 
 #[get("/location/times")]
-pub fn location_times(conn: DbConn) -> Result<Json> {
+pub fn location_times(_auth: Auth<Curator>, conn: DbConn) -> Result<Json> {
     let gps_readings = conn.all::<GpsReading>()?;
     Ok(Json(json!({
         "times": gps_readings.into_iter().map(|r| r.created_at).collect::<Vec<_>>()
@@ -77,16 +350,17 @@ pub fn location_times(conn: DbConn) -> Result<Json> {
 }
 
 #[get("/location/<since>/<until>")]
-pub fn location_range(conn: DbConn, since: DateTimeUtc, until: DateTimeUtc) -> Result<Json> {
-    let gps_readings = GpsReading::get_time_range(&conn, &since, &until)?;
+pub fn location_range(_auth: Auth<Curator>, conn: DbConn, privacy: State<LocationPrivacy>, since: DateTimeUtc, until: DateTimeUtc) -> Result<Json> {
+    let user_locations = User::locations_between(&conn, &since, &until)?;
 
-    let mut locations: Vec<Value> = Vec::with_capacity(gps_readings.len());
-    for gps_reading in gps_readings.into_iter() {
+    let mut locations: Vec<Value> = Vec::with_capacity(user_locations.len());
+    for user_location in user_locations.iter() {
+        let (latitude, longitude) = privacy.apply(user_location);
         locations.push(json!({
-            "user_id": gps_reading.user_id,
+            "user_id": user_location.id,
             "location": {
-                "latitude": gps_reading.point.y,
-                "longitude": gps_reading.point.x
+                "latitude": latitude,
+                "longitude": longitude
             }
         }));
     }
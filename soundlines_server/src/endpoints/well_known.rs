@@ -0,0 +1,7 @@
+use rocket_jwt::Jwks;
+
+/// Public keys tokens are signed with, empty unless asymmetric keys are configured
+#[get("/jwks.json")]
+pub fn jwks() -> Jwks {
+    Jwks
+}
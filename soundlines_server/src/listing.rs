@@ -0,0 +1,177 @@
+use rocket::request;
+use rocket::Request;
+use rocket::http::Status;
+use rocket::outcome::Outcome;
+use rocket::request::FormItems;
+use rocket::request::FromForm;
+use rocket::request::FromRequest;
+use rocket::response::Failure;
+use serde_json::Value;
+
+use soundlines_core::db::filter::Filter;
+use soundlines_core::db::filter::Page;
+use soundlines_core::postgis::ewkb::Point;
+
+use openapi::*;
+
+const DEFAULT_LIMIT: i64 = 100;
+const MAX_LIMIT: i64 = 1000;
+
+/// Query parameters of the index endpoints:
+///
+/// - `cursor`: `next_cursor` of the previous page
+/// - `limit`: page size, at most 1000
+/// - `bbox`: `min_lng,min_lat,max_lng,max_lat`
+/// - `latitude`, `longitude` and `radius` in meters
+/// - `prefab`, `setting_id` and `cell_id`, not available for cells
+/// - `fields`: comma separated fields to include in each item
+#[derive(Debug, FromForm)]
+pub struct ListParams {
+    pub cursor: Option<i32>,
+    pub limit: Option<i64>,
+    pub bbox: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    pub radius: Option<f64>,
+    pub prefab: Option<String>,
+    pub setting_id: Option<i32>,
+    pub cell_id: Option<i32>,
+    pub fields: Option<String>
+}
+
+/// `ListParams` of the request, `None` without a query string. Fails with
+/// `Bad Request` on unknown or malformed parameters, a rank 2 route would
+/// silently answer those with the whole table instead.
+pub struct ListQuery(pub Option<ListParams>);
+
+impl<'a, 'r> FromRequest<'a, 'r> for ListQuery {
+    type Error = ();
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        let query = match request.uri().query() {
+            Some(query) if !query.is_empty() => query,
+            _                                => return Outcome::Success(ListQuery(None))
+        };
+
+        match ListParams::from_form(&mut FormItems::from(query), true) {
+            Ok(params) => Outcome::Success(ListQuery(Some(params))),
+            Err(_)     => Outcome::Failure((Status::BadRequest, ()))
+        }
+    }
+}
+
+impl Schema for ListParams {
+    fn schema() -> Value {
+        object(&[
+            ("cursor", integer()),
+            ("limit", integer()),
+            ("bbox", string()),
+            ("latitude", number()),
+            ("longitude", number()),
+            ("radius", number()),
+            ("prefab", string()),
+            ("setting_id", integer()),
+            ("cell_id", integer()),
+            ("fields", string())
+        ], &[])
+    }
+}
+
+impl ListParams {
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_LIMIT).max(1).min(MAX_LIMIT)
+    }
+
+    /// Filter on the cursor and the location, `geometry` is the column the
+    /// location filters apply to
+    pub fn filter(&self, geometry: &str) -> Result<Filter, Failure> {
+        let mut filter = Filter::new();
+
+        if let Some(cursor) = self.cursor {
+            filter.after(cursor);
+        }
+
+        if let Some(ref bbox) = self.bbox {
+            let (min_lng, min_lat, max_lng, max_lat) = parse_bbox(bbox).ok_or(Failure(Status::BadRequest))?;
+            filter.within_bbox(geometry, min_lng, min_lat, max_lng, max_lat);
+        }
+
+        match (self.latitude, self.longitude, self.radius) {
+            (Some(latitude), Some(longitude), Some(radius)) if radius >= 0.0 => {
+                filter.within_radius(geometry, Point::new(longitude, latitude, Some(4326)), radius);
+            },
+            (None, None, None) => {},
+            _                  => return Err(Failure(Status::BadRequest))
+        }
+
+        Ok(filter)
+    }
+
+    /// Same as `filter` with the filters on plant attributes, for entities and seeds
+    pub fn plant_filter(&self, geometry: &str) -> Result<Filter, Failure> {
+        let mut filter = self.filter(geometry)?;
+
+        if let Some(ref prefab) = self.prefab {
+            filter.equals("prefab", prefab.clone());
+        }
+
+        if let Some(setting_id) = self.setting_id {
+            filter.equals("setting_id", setting_id);
+        }
+
+        if let Some(cell_id) = self.cell_id {
+            filter.equals("cell_id", cell_id);
+        }
+
+        Ok(filter)
+    }
+
+    /// Cells don't have plant attributes
+    pub fn has_plant_filters(&self) -> bool {
+        self.prefab.is_some() || self.setting_id.is_some() || self.cell_id.is_some()
+    }
+
+    fn fields(&self) -> Option<Vec<&str>> {
+        self.fields.as_ref().map(|fields| {
+            fields.split(',').map(str::trim).filter(|field| field.len() > 0).collect()
+        })
+    }
+
+    /// Json of the page under `key`, with only the asked fields of each item
+    /// and the cursor of the next page if there is one
+    pub fn page_json<T, F, I>(&self, key: &str, page: Page<T>, id: I, into_json: F) -> Value
+        where F: Fn(T) -> Value, I: Fn(&T) -> i32
+    {
+        let next_cursor = if page.has_more { page.items.last().map(|item| id(item)) } else { None };
+        let fields = self.fields();
+
+        let items = page.items.into_iter().map(|item| {
+            let json = into_json(item);
+            match fields {
+                Some(ref fields) => select_fields(json, fields),
+                None             => json
+            }
+        }).collect::<Vec<_>>();
+
+        let mut json = json!({ "next_cursor": next_cursor });
+        json[key] = Value::Array(items);
+        json
+    }
+}
+
+fn parse_bbox(bbox: &str) -> Option<(f64, f64, f64, f64)> {
+    let values = bbox.split(',').map(|value| value.trim().parse::<f64>().ok()).collect::<Option<Vec<_>>>()?;
+    if values.len() != 4 || values[0] > values[2] || values[1] > values[3] {
+        return None;
+    }
+
+    Some((values[0], values[1], values[2], values[3]))
+}
+
+fn select_fields(json: Value, fields: &[&str]) -> Value {
+    match json {
+        Value::Object(object) => Value::Object(
+            object.into_iter().filter(|&(ref key, _)| fields.contains(&key.as_str())).collect()
+        ),
+        other => other
+    }
+}
@@ -1,4 +1,5 @@
 #![feature(plugin)]
+#![feature(custom_derive)]
 #![plugin(rocket_codegen)]
 
 extern crate rocket;
@@ -7,6 +8,7 @@ extern crate rocket_jwt;
 extern crate chrono;
 extern crate geo;
 extern crate soundlines_core;
+extern crate soundlines_config;
 extern crate soundlines_simlib;
 
 #[allow(unused_imports)]
@@ -18,13 +20,148 @@ extern crate serde_derive;
 extern crate serde;
 #[macro_use]
 extern crate serde_json;
+#[macro_use]
+extern crate clap;
+extern crate rmp_serde;
+extern crate serde_cbor;
+extern crate flate2;
+extern crate brotli;
 
 mod db_guard;
 mod endpoints;
 mod server;
 mod rocket_extensions;
 mod user;
+mod cli;
+mod revocations;
+mod listing;
+mod openapi;
+mod encoding;
+mod cache;
+mod metrics;
+mod client_version;
+mod snapshots;
+mod replay;
+mod privacy;
+mod presence;
+
+use std::process;
+
+use clap::App;
+use clap::Arg;
+use clap::SubCommand;
+
+use chrono::prelude::*;
+
+use soundlines_core::db::models::Role;
+use soundlines_config::Component;
 
 fn main() {
-    server::run()
+    let app = App::new("Soundlines Server")
+        .version("0.1")
+
+        .subcommand(SubCommand::with_name("token")
+                    .about("Prints an auth token for a new user, or for an existing one after changing its role")
+
+                    .arg(Arg::with_name("role")
+                         .long("role")
+                         .help("One of player, curator or admin")
+                         .require_equals(true)
+                         .default_value("admin"))
+
+                    .arg(Arg::with_name("user_id")
+                         .long("user_id")
+                         .help("Id of an existing user to promote instead of creating a new one")
+                         .takes_value(true)
+                         .require_equals(true)))
+
+        .subcommand(SubCommand::with_name("replay")
+                    .about("Prints the world at a past time, or streams it over a time range as newline delimited json")
+
+                    .arg(Arg::with_name("at")
+                         .long("at")
+                         .help("RFC 3339 time to print the world at")
+                         .takes_value(true)
+                         .require_equals(true)
+                         .conflicts_with_all(&["from", "until"])
+                         .required_unless("from"))
+
+                    .arg(Arg::with_name("from")
+                         .long("from")
+                         .help("RFC 3339 time to start streaming at")
+                         .takes_value(true)
+                         .require_equals(true)
+                         .requires("until"))
+
+                    .arg(Arg::with_name("until")
+                         .long("until")
+                         .help("RFC 3339 time to stop streaming at")
+                         .takes_value(true)
+                         .require_equals(true)
+                         .requires("from"))
+
+                    .arg(Arg::with_name("step")
+                         .long("step")
+                         .help("World time between frames in seconds")
+                         .require_equals(true)
+                         .default_value("60"))
+
+                    .arg(Arg::with_name("speed")
+                         .long("speed")
+                         .help("How much faster than real time the frames are printed")
+                         .require_equals(true)
+                         .default_value("1")));
+
+    let matches = app.get_matches();
+    let config = soundlines_config::load_or_exit(Component::Server);
+
+    match matches.subcommand() {
+        ("token", Some(options)) => {
+            let role = Role::from_str(options.value_of("role").unwrap_or("")).unwrap_or_else(|| {
+                eprintln!("ERROR: role should be one of player, curator or admin");
+                process::exit(1);
+            });
+
+            let user_id = match options.value_of("user_id") {
+                Some(_) => Some(value_t_or_exit!(options.value_of("user_id"), i32)),
+                None    => None
+            };
+
+            match cli::mint_token(&config, role, user_id) {
+                Ok(token) => println!("{}", token),
+                Err(err)  => {
+                    eprintln!("ERROR: {}", err);
+                    process::exit(1);
+                }
+            }
+        },
+
+        ("replay", Some(options)) => {
+            let time = |name: &str| options.value_of(name).map(|value| {
+                value.parse::<DateTime<Utc>>().unwrap_or_else(|_| {
+                    eprintln!("ERROR: {} should be an RFC 3339 time", name);
+                    process::exit(1);
+                })
+            });
+
+            let result = match (time("at"), time("from"), time("until")) {
+                (Some(at), _, _)                => cli::replay_at(&config, at),
+                (None, Some(from), Some(until)) => cli::replay_stream(
+                    &config,
+                    from,
+                    until,
+                    value_t_or_exit!(options.value_of("step"), i64),
+                    value_t_or_exit!(options.value_of("speed"), f64)
+                ),
+                _                               => unreachable!()
+            };
+
+            if let Err(err) = result {
+                eprintln!("ERROR: {}", err);
+                process::exit(1);
+            }
+        },
+
+        _ => server::run(config)
+    }
 }
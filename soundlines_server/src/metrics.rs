@@ -0,0 +1,191 @@
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+use std::collections::BTreeMap;
+
+use rocket::State;
+use rocket::Request;
+use rocket::Response;
+use rocket::Data;
+use rocket::http::Header;
+use rocket::fairing::Fairing;
+use rocket::fairing::Info;
+use rocket::fairing::Kind;
+
+use soundlines_core::db::Pool;
+
+/// Upper bounds of the latency histogram buckets, in seconds
+const LATENCY_BUCKETS: &'static [f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// Rocket 0.3 has no request local state, so the fairing passes the start
+/// time to itself in a header
+const REQUEST_START_HEADER: &'static str = "X-Soundlines-Request-Start";
+
+struct Histogram {
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64
+}
+
+impl Histogram {
+    fn new() -> Self {
+        Histogram { buckets: vec![0; LATENCY_BUCKETS.len()], sum: 0.0, count: 0 }
+    }
+
+    fn observe(&mut self, value: f64) {
+        for (bucket, bound) in self.buckets.iter_mut().zip(LATENCY_BUCKETS.iter()) {
+            if value <= *bound {
+                *bucket += 1;
+            }
+        }
+
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+/// Counters of the server, rendered in the Prometheus text format at `/metrics`
+#[derive(Default)]
+pub struct Metrics {
+    // (method, route, status)
+    requests: Mutex<BTreeMap<(String, String, u16), u64>>,
+    // (method, route)
+    latencies: Mutex<BTreeMap<(String, String), Histogram>>,
+    readings: Mutex<BTreeMap<&'static str, u64>>,
+    seeds_picked: Mutex<u64>,
+    entities_spread: Mutex<u64>
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Readings ingested by a collector, `sensor` is one of wifi, sound, light or gps
+    pub fn readings(&self, sensor: &'static str, count: u64) {
+        if let Ok(mut readings) = self.readings.lock() {
+            *readings.entry(sensor).or_insert(0) += count;
+        }
+    }
+
+    pub fn seed_picked(&self) {
+        if let Ok(mut seeds_picked) = self.seeds_picked.lock() {
+            *seeds_picked += 1;
+        }
+    }
+
+    pub fn entity_spread(&self) {
+        if let Ok(mut entities_spread) = self.entities_spread.lock() {
+            *entities_spread += 1;
+        }
+    }
+
+    fn request(&self, method: String, route: String, status: u16, seconds: Option<f64>) {
+        if let Some(seconds) = seconds {
+            if let Ok(mut latencies) = self.latencies.lock() {
+                latencies.entry((method.clone(), route.clone())).or_insert_with(Histogram::new).observe(seconds);
+            }
+        }
+
+        if let Ok(mut requests) = self.requests.lock() {
+            *requests.entry((method, route, status)).or_insert(0) += 1;
+        }
+    }
+
+    pub fn render(&self, pool: &Pool) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP soundlines_http_requests_total Requests handled by route and status\n");
+        out.push_str("# TYPE soundlines_http_requests_total counter\n");
+        if let Ok(requests) = self.requests.lock() {
+            for (&(ref method, ref route, status), count) in requests.iter() {
+                let _ = writeln!(out, "soundlines_http_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+                    method, escape(route), status, count);
+            }
+        }
+
+        out.push_str("# HELP soundlines_http_request_duration_seconds Time spent handling requests\n");
+        out.push_str("# TYPE soundlines_http_request_duration_seconds histogram\n");
+        if let Ok(latencies) = self.latencies.lock() {
+            for (&(ref method, ref route), histogram) in latencies.iter() {
+                let labels = format!("method=\"{}\",route=\"{}\"", method, escape(route));
+
+                for (bucket, bound) in histogram.buckets.iter().zip(LATENCY_BUCKETS.iter()) {
+                    let _ = writeln!(out, "soundlines_http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}", labels, bound, bucket);
+                }
+
+                let _ = writeln!(out, "soundlines_http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}", labels, histogram.count);
+                let _ = writeln!(out, "soundlines_http_request_duration_seconds_sum{{{}}} {}", labels, histogram.sum);
+                let _ = writeln!(out, "soundlines_http_request_duration_seconds_count{{{}}} {}", labels, histogram.count);
+            }
+        }
+
+        let state = pool.state();
+        out.push_str("# HELP soundlines_db_pool_connections Open database connections\n");
+        out.push_str("# TYPE soundlines_db_pool_connections gauge\n");
+        let _ = writeln!(out, "soundlines_db_pool_connections {}", state.connections);
+        out.push_str("# HELP soundlines_db_pool_idle_connections Idle database connections\n");
+        out.push_str("# TYPE soundlines_db_pool_idle_connections gauge\n");
+        let _ = writeln!(out, "soundlines_db_pool_idle_connections {}", state.idle_connections);
+
+        out.push_str("# HELP soundlines_readings_total Sensor readings ingested by the collectors\n");
+        out.push_str("# TYPE soundlines_readings_total counter\n");
+        if let Ok(readings) = self.readings.lock() {
+            for (sensor, count) in readings.iter() {
+                let _ = writeln!(out, "soundlines_readings_total{{sensor=\"{}\"}} {}", sensor, count);
+            }
+        }
+
+        out.push_str("# HELP soundlines_seeds_picked_total Seeds picked up by players\n");
+        out.push_str("# TYPE soundlines_seeds_picked_total counter\n");
+        let _ = writeln!(out, "soundlines_seeds_picked_total {}", self.seeds_picked.lock().map(|count| *count).unwrap_or(0));
+
+        out.push_str("# HELP soundlines_entities_spread_total Entities planted by players\n");
+        out.push_str("# TYPE soundlines_entities_spread_total counter\n");
+        let _ = writeln!(out, "soundlines_entities_spread_total {}", self.entities_spread.lock().map(|count| *count).unwrap_or(0));
+
+        out
+    }
+}
+
+fn escape(label: &str) -> String {
+    label.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn now_seconds() -> f64 {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    now.as_secs() as f64 + now.subsec_nanos() as f64 / 1e9
+}
+
+/// Records the count and latency of every request in the managed `Metrics`
+pub struct RequestMetrics;
+
+impl Fairing for RequestMetrics {
+    fn info(&self) -> Info {
+        Info { name: "Request metrics", kind: Kind::Request | Kind::Response }
+    }
+
+    fn on_request(&self, request: &mut Request, _: &Data) {
+        // Clients sending the header themselves would only skew their own latency
+        request.replace_header(Header::new(REQUEST_START_HEADER, now_seconds().to_string()));
+    }
+
+    fn on_response(&self, request: &Request, response: &mut Response) {
+        let metrics = match request.guard::<State<Metrics>>().succeeded() {
+            Some(metrics) => metrics,
+            None          => return
+        };
+
+        // Unmatched requests are grouped, their paths are arbitrary
+        let route = request.route()
+            .map(|route| route.uri.path().to_string())
+            .unwrap_or_else(|| "unmatched".to_string());
+
+        let seconds = request.headers().get_one(REQUEST_START_HEADER)
+            .and_then(|start| start.parse::<f64>().ok())
+            .map(|start| (now_seconds() - start).max(0.0));
+
+        metrics.request(request.method().to_string(), route, response.status().code, seconds);
+    }
+}
@@ -0,0 +1,558 @@
+use rocket::Route;
+use rocket::http::Method;
+use serde_json::Value;
+use serde_json::Map;
+
+use soundlines_core::db::models::Role;
+use soundlines_core::db::models::AppConfig;
+use soundlines_core::db::models::UserProfile;
+use soundlines_core::db::models::UserPrivacy;
+use soundlines_core::db::models::PlantSetting;
+use soundlines_core::db::models::GpsReadingJson;
+use soundlines_core::db::models::WifiReadingJson;
+
+use listing::ListParams;
+use endpoints::seeds::SeedArea;
+use endpoints::events::EventParams;
+use endpoints::trades::TradeOffer;
+use endpoints::replay::ReplayParams;
+use endpoints::seeds::PickupPayload;
+use endpoints::seeds::DeployPayload;
+use endpoints::seeds::SpreadSeedPayload;
+use endpoints::collectors::WifiReadingsPayload;
+use endpoints::collectors::SoundReadingPayload;
+use endpoints::collectors::LightReadingPayload;
+
+/// JSON schema of a payload or a query string type
+pub trait Schema {
+    fn schema() -> Value;
+}
+
+pub fn integer() -> Value { json!({ "type": "integer" }) }
+pub fn number() -> Value { json!({ "type": "number" }) }
+pub fn string() -> Value { json!({ "type": "string" }) }
+pub fn boolean() -> Value { json!({ "type": "boolean" }) }
+pub fn date_time() -> Value { json!({ "type": "string", "format": "date-time" }) }
+pub fn array(items: Value) -> Value { json!({ "type": "array", "items": items }) }
+
+/// Property set by the server, ignored in requests
+pub fn read_only(mut schema: Value) -> Value {
+    schema["readOnly"] = json!(true);
+    schema
+}
+
+pub fn object(properties: &[(&str, Value)], required: &[&str]) -> Value {
+    let properties = properties.iter()
+        .map(|&(name, ref schema)| (name.to_string(), schema.clone()))
+        .collect::<Map<_, _>>();
+
+    json!({ "type": "object", "properties": properties, "required": required })
+}
+
+/// Documentation of a route, matched to the mounted routes by method and path
+pub struct Operation {
+    method: Method,
+    path: &'static str,
+    summary: &'static str,
+    role: Option<Role>,
+    body: Option<Value>,
+    query: Option<Value>
+}
+
+impl Operation {
+    pub fn new(method: Method, path: &'static str, summary: &'static str) -> Self {
+        Operation { method, path, summary, role: None, body: None, query: None }
+    }
+
+    /// Requires a token of a user with at least the role
+    pub fn auth(mut self, role: Role) -> Self {
+        self.role = Some(role);
+        self
+    }
+
+    pub fn body<T: Schema>(mut self) -> Self {
+        self.body = Some(T::schema());
+        self
+    }
+
+    pub fn query<T: Schema>(mut self) -> Self {
+        self.query = Some(T::schema());
+        self
+    }
+
+    fn matches(&self, method: Method, path: &str) -> bool {
+        self.method == method && self.path == path
+    }
+
+    fn to_json(&self) -> Value {
+        let mut parameters = path_parameters(self.path);
+        if let Some(ref query) = self.query {
+            parameters.append(&mut query_parameters(query));
+        }
+
+        let mut operation = json!({
+            "summary": self.summary,
+            "parameters": parameters,
+            "responses": {
+                "200": { "description": "Success" }
+            }
+        });
+
+        if let Some(role) = self.role {
+            operation["security"] = json!([{ "bearer": [] }]);
+            operation["description"] = json!(format!("Requires the `{}` role", role.as_str()));
+            operation["responses"]["401"] = json!({ "description": "Missing or invalid token" });
+            operation["responses"]["426"] = json!({ "description": "Client version in `X-Client-Version` is below the minimum, requests without the header are not checked" });
+
+            if role > Role::Player {
+                operation["responses"]["403"] = json!({ "description": "Role is not enough" });
+            }
+        }
+
+        if let Some(ref body) = self.body {
+            operation["requestBody"] = json!({
+                "required": true,
+                "content": { "application/json": { "schema": body } }
+            });
+        }
+
+        operation
+    }
+}
+
+/// Every route the server mounts. Maintained by hand, as Rocket routes don't
+/// carry their payload types or a description: a mounted route missing here,
+/// or an operation which isn't mounted, fails the tests
+pub fn operations() -> Vec<Operation> {
+    use self::Method::*;
+
+    vec![
+        Operation::new(Get, "/weather", "Current weather"),
+        Operation::new(Get, "/openapi.json", "This document"),
+        Operation::new(Get, "/metrics", "Metrics in the Prometheus text format"),
+        Operation::new(Get, "/health", "Liveness, answers while the server runs"),
+        Operation::new(Get, "/ready", "Readiness of the database, migrations and background jobs, 503 when not ready"),
+        Operation::new(Get, "/config", "Client versions, feature flags and the message of the day"),
+        Operation::new(Get, "/.well-known/jwks.json", "Public keys tokens are signed with"),
+
+        Operation::new(Post, "/data/wifi", "Posts wifi readings").auth(Role::Player).body::<WifiReadingsPayload>(),
+        Operation::new(Post, "/data/sound", "Posts a sound reading").auth(Role::Player).body::<SoundReadingPayload>(),
+        Operation::new(Post, "/data/light", "Posts a light reading").auth(Role::Player).body::<LightReadingPayload>(),
+        Operation::new(Post, "/data/gps", "Posts the location, responds with what is around").auth(Role::Player).body::<GpsReadingJson>(),
+
+        Operation::new(Get, "/cells", "Lists cells, paginated when any query parameter is given").query::<ListParams>(),
+        Operation::new(Get, "/cells/{id}", "A cell"),
+        Operation::new(Get, "/cells/{latitude}/{longitude}", "Cell containing the location"),
+
+        Operation::new(Post, "/users/register", "Registers a device, the request token is signed with the shared secret and may carry a device_id and link_code"),
+        Operation::new(Post, "/users/refresh", "Exchanges a refresh token for new tokens"),
+        Operation::new(Post, "/users/me/logout", "Revokes the token sent").auth(Role::Player),
+        Operation::new(Post, "/users/me/revoke", "Revokes every token of the user").auth(Role::Player),
+        Operation::new(Post, "/users/{id}/revoke", "Revokes every token of a user").auth(Role::Admin),
+        Operation::new(Delete, "/users/me", "Deregisters the device").auth(Role::Player),
+        Operation::new(Get, "/users/me/inventory", "Seeds held by the user").auth(Role::Player),
+        Operation::new(Get, "/users/me/garden", "Plants planted by the user").auth(Role::Player),
+        Operation::new(Get, "/users/{id}/garden", "Plants planted by a user").auth(Role::Player),
+        Operation::new(Get, "/users/me/stats", "Statistics of the user for today, the week and all time").auth(Role::Player),
+        Operation::new(Get, "/users/{id}/stats", "Statistics of a user for today, the week and all time").auth(Role::Player),
+        Operation::new(Get, "/users/me/profile", "Profile of the user").auth(Role::Player),
+        Operation::new(Get, "/users/{id}/profile", "Profile of a user").auth(Role::Player),
+        Operation::new(Put, "/users/me/profile", "Replaces the profile of the user").auth(Role::Player).body::<UserProfile>(),
+        Operation::new(Get, "/users/me/devices", "Devices registered as the user").auth(Role::Player),
+        Operation::new(Post, "/users/me/devices/link", "Code for registering another device as the user").auth(Role::Player),
+        Operation::new(Delete, "/users/me/devices/{id}", "Removes a device from the user").auth(Role::Player),
+        Operation::new(Get, "/users/me/privacy", "Whether the user is hidden from others").auth(Role::Player),
+        Operation::new(Put, "/users/me/privacy", "Hides the user from others, or shows it again").auth(Role::Player).body::<UserPrivacy>(),
+        Operation::new(Delete, "/users/me/data", "Deletes the readings and personal data of the user").auth(Role::Player),
+        Operation::new(Get, "/users/location", "Online users, fuzzed, and how many are in each cell").auth(Role::Curator),
+        Operation::new(Get, "/users/location/times", "Time range of the recorded locations").auth(Role::Curator),
+        Operation::new(Get, "/users/location/{since}/{until}", "Locations of the users in the time range, fuzzed").auth(Role::Curator),
+
+        Operation::new(Get, "/leaderboards/{window}/{metric}", "Top players, window is daily, weekly or all-time and metric is readings, cells_visited, seeds_picked, seeds_spread or surviving_plants").auth(Role::Player),
+
+        Operation::new(Post, "/entities/generate", "Regenerates every entity").auth(Role::Admin),
+        Operation::new(Get, "/entities", "Lists entities, paginated when any query parameter is given").query::<ListParams>(),
+        Operation::new(Delete, "/entities/{id}", "Deletes an entity").auth(Role::Curator),
+
+        Operation::new(Get, "/seeds", "Lists seeds, paginated when any query parameter is given").query::<ListParams>(),
+        Operation::new(Post, "/seeds/pickup", "Moves a seed into the user's inventory").auth(Role::Player).body::<PickupPayload>(),
+        Operation::new(Post, "/seeds/spread", "Plants a seed from the user's inventory").auth(Role::Player).body::<SpreadSeedPayload>(),
+        Operation::new(Get, "/seeds/get/{count}", "Generates at most 20 seeds around the player, which only they can pick up for 10 minutes").auth(Role::Player).query::<SeedArea>(),
+        Operation::new(Post, "/seeds/deploy", "Generates seeds in the cells").auth(Role::Curator).body::<DeployPayload>(),
+
+        Operation::new(Post, "/trades", "Offers seeds for seeds of a nearby player").auth(Role::Player).body::<TradeOffer>(),
+        Operation::new(Get, "/trades", "Pending offers made by and to the user").auth(Role::Player),
+        Operation::new(Get, "/trades/history", "Resolved trades of the user, newest first").auth(Role::Player),
+        Operation::new(Get, "/trades/{id}", "Trade the user is part of").auth(Role::Player),
+        Operation::new(Post, "/trades/{id}/accept", "Exchanges the seeds of an offer made to the user").auth(Role::Player),
+        Operation::new(Post, "/trades/{id}/decline", "Turns down an offer made to the user").auth(Role::Player),
+        Operation::new(Post, "/trades/{id}/cancel", "Withdraws an offer made by the user").auth(Role::Player),
+        Operation::new(Get, "/events", "World events oldest first, paginated, filtered by time, cell and kind").auth(Role::Player).query::<EventParams>(),
+        Operation::new(Get, "/replay/at/{time}", "Entities, seeds and cells at a past time, from the last snapshot and the events and readings after it").auth(Role::Curator),
+        Operation::new(Get, "/replay/stream", "The world at each step of a time range as newline delimited JSON, sent at the given speed for at most an hour of real time. At most two streams are sent at once").auth(Role::Curator).query::<ReplayParams>(),
+
+        Operation::new(Get, "/dev/version", "Latest client version as text, superseded by /config"),
+        Operation::new(Put, "/dev/version", "Updates the latest client version, the body is the version as text").auth(Role::Admin),
+        Operation::new(Put, "/dev/config", "Updates the client versions, feature flags and message of the day").auth(Role::Admin).body::<AppConfig>(),
+        Operation::new(Get, "/dev/settings", "Plant settings").auth(Role::Curator),
+        Operation::new(Put, "/dev/settings/{setting_id}", "Validates and stores a new version of a plant setting").auth(Role::Curator).body::<PlantSetting>(),
+        Operation::new(Get, "/dev/settings/{setting_id}/versions", "Versions of a plant setting, newest first").auth(Role::Curator),
+        Operation::new(Get, "/dev/settings/{setting_id}/diff/{from}/{to}", "Fields changed between two versions of a plant setting").auth(Role::Curator),
+        Operation::new(Post, "/dev/settings/{setting_id}/rollback/{version}", "Restores a previous version of a plant setting").auth(Role::Curator),
+        Operation::new(Get, "/dev/snapshots", "Snapshots of the world with their times and counts, newest first").auth(Role::Curator),
+        Operation::new(Get, "/dev/snapshots/{id}", "A snapshot of the world").auth(Role::Curator),
+        Operation::new(Get, "/dev/snapshots/{from}/diff/{to}", "Entities and seeds which changed between two snapshots").auth(Role::Curator),
+        Operation::new(Get, "/dev/snapshot/{id}", "A snapshot of the world, same as /dev/snapshots/{id}").auth(Role::Curator)
+    ]
+}
+
+/// OpenAPI 3 document of the mounted routes
+pub fn spec(mounts: &[(&'static str, Vec<Route>)]) -> Value {
+    let operations = operations();
+    let mut paths = Map::new();
+
+    for (method, path) in mounted(mounts) {
+        let operation = match operations.iter().find(|op| op.matches(method, &path)) {
+            Some(operation) => operation.to_json(),
+            None            => json!({ "summary": "Undocumented", "responses": { "200": { "description": "Success" } } })
+        };
+
+        let item = paths.entry(path).or_insert_with(|| json!({}));
+        item[method.as_str().to_lowercase()] = operation;
+    }
+
+    json!({
+        "openapi": "3.0.0",
+        "info": {
+            "title": "Soundlines",
+            "version": env!("CARGO_PKG_VERSION")
+        },
+        "paths": paths,
+        "components": {
+            "securitySchemes": {
+                "bearer": { "type": "http", "scheme": "bearer", "bearerFormat": "JWT" }
+            }
+        }
+    })
+}
+
+/// Method and OpenAPI path of the routes, routes which differ only in their
+/// query string are listed once
+fn mounted(mounts: &[(&'static str, Vec<Route>)]) -> Vec<(Method, String)> {
+    let mut mounted: Vec<(Method, String)> = vec![];
+
+    for &(base, ref routes) in mounts.iter() {
+        for route in routes.iter() {
+            let path = openapi_path(base, route.uri.path());
+            if !mounted.iter().any(|&(method, ref p)| method == route.method && *p == path) {
+                mounted.push((route.method, path));
+            }
+        }
+    }
+
+    mounted
+}
+
+/// Joins the mount point and the route path, `<param>` segments become `{param}`
+fn openapi_path(base: &str, path: &str) -> String {
+    let full = match (base.trim_right_matches('/'), path) {
+        ("", path)   => path.to_string(),
+        (base, "/")  => base.to_string(),
+        (base, path) => format!("{}{}", base, path)
+    };
+
+    full.split('/')
+        .map(|segment| {
+            if segment.starts_with('<') && segment.ends_with('>') {
+                format!("{{{}}}", segment.trim_matches(|c| c == '<' || c == '>' || c == '.'))
+            } else {
+                segment.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn path_parameters(path: &str) -> Vec<Value> {
+    path.split('/')
+        .filter(|segment| segment.starts_with('{'))
+        .map(|segment| {
+            let name = segment.trim_matches(|c| c == '{' || c == '}');
+            let schema = match name {
+                "latitude" | "longitude" => number(),
+                "since" | "until"        => date_time(),
+                "time"                   => string(),
+                _                        => integer()
+            };
+
+            json!({ "name": name, "in": "path", "required": true, "schema": schema })
+        })
+        .collect()
+}
+
+fn query_parameters(schema: &Value) -> Vec<Value> {
+    let properties = match schema["properties"].as_object() {
+        Some(properties) => properties,
+        None             => return vec![]
+    };
+
+    let required = schema["required"].as_array().cloned().unwrap_or_default();
+
+    properties.iter().map(|(name, schema)| {
+        json!({
+            "name": name,
+            "in": "query",
+            "required": required.contains(&json!(name)),
+            "schema": schema
+        })
+    }).collect()
+}
+
+impl Schema for GpsReadingJson {
+    fn schema() -> Value {
+        object(&[
+            ("id", read_only(integer())),
+            ("user_id", read_only(integer())),
+            ("latitude", number()),
+            ("longitude", number()),
+            ("created_at", date_time())
+        ], &["latitude", "longitude"])
+    }
+}
+
+impl Schema for WifiReadingJson {
+    fn schema() -> Value {
+        object(&[
+            ("ssid", string()),
+            ("level", number()),
+            ("frequency", number()),
+            // Set from the location of the whole payload
+            ("latitude", read_only(number())),
+            ("longitude", read_only(number())),
+            ("created_at", date_time())
+        ], &["ssid", "level", "frequency"])
+    }
+}
+
+impl Schema for PlantSetting {
+    fn schema() -> Value {
+        object(&[
+            ("id", integer()),
+            ("name", string()),
+            ("prefab", string()),
+            ("growth_limit", number()),
+            ("life_expectancy", number()),
+            ("wifi_sensitivity", number()),
+            ("light_sensitivity", number()),
+            ("sound_sensitivity", number()),
+            ("neighbor_tolerance", number()),
+            ("birth_proba", number()),
+            ("bloom_proba", number()),
+            ("mating_freq", number()),
+            ("mating_duration", number()),
+            ("fruit_duration", number()),
+            ("mating_distance", number()),
+            ("crowd_distance", number())
+        ], &[
+            "name", "prefab", "growth_limit", "life_expectancy", "wifi_sensitivity", "light_sensitivity",
+            "sound_sensitivity", "neighbor_tolerance", "birth_proba", "bloom_proba", "mating_freq",
+            "mating_duration", "fruit_duration", "mating_distance", "crowd_distance"
+        ])
+    }
+}
+
+impl Schema for AppConfig {
+    fn schema() -> Value {
+        object(&[
+            ("min_client_version", string()),
+            ("latest_client_version", string()),
+            ("features", json!({ "type": "object", "additionalProperties": { "type": "boolean" } })),
+            ("motd", string()),
+            ("updated_at", date_time())
+        ], &["min_client_version", "latest_client_version"])
+    }
+}
+
+impl Schema for UserProfile {
+    fn schema() -> Value {
+        object(&[
+            ("display_name", string()),
+            ("avatar_color", string()),
+            ("language", string())
+        ], &[])
+    }
+}
+
+impl Schema for UserPrivacy {
+    fn schema() -> Value {
+        object(&[
+            ("hidden", boolean())
+        ], &["hidden"])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use rocket::request::FormItems;
+    use rocket::request::FromForm;
+    use serde::Serialize;
+    use serde::de::DeserializeOwned;
+    use serde_json;
+
+    use super::*;
+    use server::mounts;
+
+    #[test]
+    fn every_route_is_documented() {
+        let operations = operations();
+        let undocumented = mounted(&mounts())
+            .into_iter()
+            .filter(|&(method, ref path)| !operations.iter().any(|op| op.matches(method, path)))
+            .map(|(method, path)| format!("{} {}", method, path))
+            .collect::<Vec<_>>();
+
+        assert!(undocumented.is_empty(), "Routes missing from the OpenAPI document: {:?}", undocumented);
+    }
+
+    #[test]
+    fn every_operation_is_mounted() {
+        let mounted = mounted(&mounts());
+        let stale = operations()
+            .into_iter()
+            .filter(|op| !mounted.iter().any(|&(method, ref path)| op.matches(method, path)))
+            .map(|op| format!("{} {}", op.method, op.path))
+            .collect::<Vec<_>>();
+
+        assert!(stale.is_empty(), "Documented routes which are not mounted: {:?}", stale);
+    }
+
+    /// Value of the schema with every property set
+    fn sample(schema: &Value) -> Value {
+        match (schema["type"].as_str(), schema["format"].as_str()) {
+            (Some("object"), _) => {
+                let properties = schema["properties"].as_object().cloned().unwrap_or_default();
+                Value::Object(properties.iter().map(|(name, schema)| (name.clone(), sample(schema))).collect())
+            },
+            (Some("array"), _)                  => json!([sample(&schema["items"])]),
+            (Some("string"), Some("date-time")) => json!("2017-10-18T00:00:00Z"),
+            (Some("string"), _)                 => json!("#a0b1c2"),
+            (Some("integer"), _)                => json!(1),
+            (Some("number"), _)                 => json!(1.5),
+            (Some("boolean"), _)                => json!(true),
+            _                                   => Value::Null
+        }
+    }
+
+    fn documented(schema: &Value) -> BTreeSet<String> {
+        schema["properties"].as_object()
+            .map(|properties| properties.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    fn required(schema: &Value) -> Vec<String> {
+        schema["required"].as_array().cloned().unwrap_or_default()
+            .into_iter()
+            .filter_map(|name| name.as_str().map(str::to_string))
+            .collect()
+    }
+
+    /// Sample of the schema with only the given properties
+    fn sample_of(schema: &Value, properties: &[String]) -> Value {
+        match sample(schema) {
+            Value::Object(fields) => Value::Object(fields.into_iter().filter(|&(ref name, _)| properties.contains(name)).collect()),
+            other                 => other
+        }
+    }
+
+    /// Deserializes a sample of the schema and checks that the type
+    /// serializes back to the same fields, for types sent in responses too
+    fn round_trip<T: Schema + Serialize + DeserializeOwned>(name: &str) {
+        let schema = T::schema();
+
+        let value = serde_json::from_value::<T>(sample(&schema))
+            .unwrap_or_else(|err| panic!("{} doesn't deserialize from its schema: {}", name, err));
+        let serialized = serde_json::to_value(&value).unwrap().as_object()
+            .map(|fields| fields.keys().cloned().collect::<BTreeSet<_>>())
+            .unwrap_or_default();
+
+        assert_eq!(documented(&schema), serialized, "Schema of {} doesn't match its fields", name);
+        deserializes::<T>(name);
+    }
+
+    /// Checks that a request payload deserializes from a sample of its schema
+    /// and that exactly the required properties are needed
+    fn deserializes<T: Schema + DeserializeOwned>(name: &str) {
+        let schema = T::schema();
+        let required = required(&schema);
+        let documented = documented(&schema);
+
+        serde_json::from_value::<T>(sample(&schema))
+            .unwrap_or_else(|err| panic!("{} doesn't deserialize from its schema: {}", name, err));
+        serde_json::from_value::<T>(sample_of(&schema, &required))
+            .unwrap_or_else(|err| panic!("{} needs more than its required properties: {}", name, err));
+
+        for property in required.iter() {
+            assert!(documented.contains(property), "{} requires undocumented {}", name, property);
+
+            let others = required.iter().filter(|other| *other != property).cloned().collect::<Vec<_>>();
+            assert!(serde_json::from_value::<T>(sample_of(&schema, &others)).is_err(), "{} doesn't need {}", name, property);
+        }
+    }
+
+    /// Query string of the given properties of the schema
+    fn query_sample(schema: &Value, properties: &[String]) -> String {
+        sample_of(schema, properties).as_object().cloned().unwrap_or_default()
+            .into_iter()
+            .map(|(name, value)| match value {
+                Value::String(value) => format!("{}={}", name, value.replace('#', "%23")),
+                value                => format!("{}={}", name, value)
+            })
+            .collect::<Vec<_>>()
+            .join("&")
+    }
+
+    /// Same as `deserializes` for query strings, parsed strictly so that
+    /// documented parameters the type doesn't have fail too
+    fn parses<T: Schema + for<'f> FromForm<'f>>(name: &str) {
+        let schema = T::schema();
+        let documented = documented(&schema).into_iter().collect::<Vec<_>>();
+        let required = required(&schema);
+
+        let parse = |properties: &[String]| T::from_form(&mut FormItems::from(query_sample(&schema, properties).as_str()), true).is_ok();
+
+        assert!(parse(&documented), "{} doesn't parse from its schema", name);
+        assert!(parse(&required), "{} needs more than its required parameters", name);
+
+        for property in required.iter() {
+            let others = required.iter().filter(|other| *other != property).cloned().collect::<Vec<_>>();
+            assert!(!parse(&others), "{} doesn't need {}", name, property);
+        }
+    }
+
+    #[test]
+    fn schemas_match_the_serde_fields() {
+        round_trip::<GpsReadingJson>("GpsReadingJson");
+        round_trip::<WifiReadingJson>("WifiReadingJson");
+        round_trip::<PlantSetting>("PlantSetting");
+        round_trip::<AppConfig>("AppConfig");
+        round_trip::<UserProfile>("UserProfile");
+        round_trip::<UserPrivacy>("UserPrivacy");
+        round_trip::<WifiReadingsPayload>("WifiReadingsPayload");
+        round_trip::<SoundReadingPayload>("SoundReadingPayload");
+        round_trip::<LightReadingPayload>("LightReadingPayload");
+        deserializes::<TradeOffer>("TradeOffer");
+        deserializes::<PickupPayload>("PickupPayload");
+        deserializes::<DeployPayload>("DeployPayload");
+        deserializes::<SpreadSeedPayload>("SpreadSeedPayload");
+    }
+
+    #[test]
+    fn schemas_match_the_query_parameters() {
+        parses::<ListParams>("ListParams");
+        parses::<SeedArea>("SeedArea");
+        parses::<EventParams>("EventParams");
+        parses::<ReplayParams>("ReplayParams");
+    }
+}
@@ -0,0 +1,198 @@
+use std::thread;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration as StdDuration;
+use std::collections::HashMap;
+use std::collections::BTreeMap;
+
+use chrono::prelude::*;
+use chrono::Duration;
+
+use soundlines_core::db::Pool;
+use soundlines_core::db::Result;
+use soundlines_core::db::Connection;
+use soundlines_core::db::extensions::*;
+use soundlines_core::db::models::OnlineUser;
+use soundlines_core::db::models::UserLocation;
+use soundlines_core::db::models::WorldEvent;
+use soundlines_config::ServerConfig;
+use soundlines_config::PresenceStore;
+
+/// Users who posted their position within `presence_ttl` seconds of the
+/// server configuration. With `presence_store = "database"` positions are
+/// also written to `user_presence`, and restored from it on start.
+///
+/// Players coming online and going offline are recorded as `join` and `leave`
+/// world events, except for hidden players.
+#[derive(Clone)]
+pub struct Presence {
+    users: Arc<Mutex<HashMap<i32, OnlineUser>>>,
+    ttl: Duration,
+    persist: bool
+}
+
+impl Presence {
+    pub fn new(ttl: Duration, persist: bool) -> Self {
+        Presence { users: Arc::new(Mutex::new(HashMap::new())), ttl, persist }
+    }
+
+    pub fn from_config(config: &ServerConfig, pool: &Pool) -> Self {
+        let presence = Presence::new(Duration::seconds(config.presence_ttl), config.presence_store == PresenceStore::Database);
+
+        if presence.persist {
+            let conn = pool.get().expect("Failed to get a connection to restore presence");
+            let online = OnlineUser::since(&conn, &(Utc::now() - presence.ttl)).expect("Failed to restore presence");
+
+            if let Ok(mut users) = presence.users.lock() {
+                users.extend(online.into_iter().map(|user| (user.user_id, user)));
+            }
+        }
+
+        presence
+    }
+
+    /// Records the position of the user, writing a `join` event when it
+    /// wasn't online
+    pub fn seen(&self, conn: &Connection, user: OnlineUser) -> Result<()> {
+        let previous = match self.users.lock() {
+            Ok(mut users) => users.insert(user.user_id, user.clone()),
+            Err(_)        => return Ok(())
+        };
+
+        if self.persist {
+            user.save(conn)?;
+        }
+
+        match previous {
+            // Expired but not swept yet, it went offline in between
+            Some(ref previous) if self.is_expired(previous) => {
+                self.record_leave(conn, previous)?;
+                self.record_join(conn, &user)
+            },
+            Some(_) => Ok(()),
+            None    => self.record_join(conn, &user)
+        }
+    }
+
+    /// Takes the user offline right away, e.g. when it deregisters
+    pub fn leave(&self, conn: &Connection, user_id: i32) -> Result<()> {
+        let removed = self.users.lock().ok().and_then(|mut users| users.remove(&user_id));
+
+        if self.persist {
+            OnlineUser::remove(conn, user_id)?;
+        }
+
+        match removed {
+            Some(ref user) if !self.is_expired(user) => self.record_leave(conn, user),
+            _ => Ok(())
+        }
+    }
+
+    pub fn set_hidden(&self, user_id: i32, hidden: bool) {
+        if let Ok(mut users) = self.users.lock() {
+            if let Some(user) = users.get_mut(&user_id) {
+                user.hidden = hidden;
+            }
+        }
+    }
+
+    /// Removes the users not seen within the TTL, writing their `leave` events
+    pub fn expire(&self, conn: &Connection) -> Result<()> {
+        let expired = match self.users.lock() {
+            Ok(mut users) => {
+                let ids = users.values()
+                    .filter(|user| self.is_expired(user))
+                    .map(|user| user.user_id)
+                    .collect::<Vec<_>>();
+
+                ids.into_iter().filter_map(|id| users.remove(&id)).collect::<Vec<_>>()
+            },
+            Err(_) => return Ok(())
+        };
+
+        for user in expired.iter() {
+            if self.persist {
+                OnlineUser::remove(conn, user.user_id)?;
+            }
+
+            self.record_leave(conn, user)?;
+        }
+
+        Ok(())
+    }
+
+    /// Expires users every `interval` in a background thread, so `leave`
+    /// events are written without waiting for requests
+    pub fn sweep_every(&self, pool: Pool, interval: StdDuration) {
+        let presence = self.clone();
+
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+
+            let result = pool.get()
+                .map_err(|err| err.to_string())
+                .and_then(|conn| presence.expire(&conn).map_err(|err| err.to_string()));
+
+            if let Err(err) = result {
+                eprintln!("ERROR: Failed to expire presence: {}", err);
+            }
+        });
+    }
+
+    /// The user if it is online, hidden or not
+    pub fn get(&self, user_id: i32) -> Option<OnlineUser> {
+        let users = self.users.lock().ok()?;
+        users.get(&user_id).filter(|user| !self.is_expired(user)).cloned()
+    }
+
+    /// Locations of the online users shown to others, by user id
+    pub fn others(&self, except: Option<i32>) -> Vec<UserLocation> {
+        let mut locations = self.visible(|user| Some(user.user_id) != except)
+            .into_iter()
+            .map(|user| user.location())
+            .collect::<Vec<_>>();
+
+        locations.sort_by_key(|location| location.id);
+        locations
+    }
+
+    /// Online users shown to others by cell id, outside the grid excluded
+    pub fn occupancy(&self) -> BTreeMap<i32, usize> {
+        let mut counts = BTreeMap::new();
+        for user in self.visible(|user| user.cell_id != -1) {
+            *counts.entry(user.cell_id).or_insert(0) += 1;
+        }
+
+        counts
+    }
+
+    fn visible<F: Fn(&OnlineUser) -> bool>(&self, filter: F) -> Vec<OnlineUser> {
+        match self.users.lock() {
+            Ok(users) => users.values()
+                .filter(|user| !user.hidden && !self.is_expired(user) && filter(user))
+                .cloned()
+                .collect(),
+            Err(_) => Vec::new()
+        }
+    }
+
+    fn is_expired(&self, user: &OnlineUser) -> bool {
+        user.seen_at < Utc::now() - self.ttl
+    }
+
+    fn record_join(&self, conn: &Connection, user: &OnlineUser) -> Result<()> {
+        if user.hidden {
+            return Ok(());
+        }
+
+        conn.insert(&WorldEvent::join(user.user_id, user.cell_id)).map(|_| ())
+    }
+
+    fn record_leave(&self, conn: &Connection, user: &OnlineUser) -> Result<()> {
+        if user.hidden {
+            return Ok(());
+        }
+
+        conn.insert(&WorldEvent::leave(user.user_id, user.cell_id)).map(|_| ())
+    }
+}
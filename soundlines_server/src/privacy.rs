@@ -0,0 +1,67 @@
+use serde_json::Value;
+
+use soundlines_core::db::models::UserLocation;
+use soundlines_config::ServerConfig;
+use soundlines_config::LocationPrivacyMode;
+
+/// Meters in a degree of latitude
+const METERS_PER_DEGREE: f64 = 111_320.0;
+
+/// How positions of other users are shown, from `location_privacy` and
+/// `location_grid_meters` of the server configuration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LocationPrivacy {
+    /// Positions as reported, for development only
+    Exact,
+    /// Centers of a square grid of the given size in meters
+    Grid(f64),
+    /// Centroids of the users' cells, positions outside the grid are snapped
+    /// to a grid of the given size instead
+    Cell(f64)
+}
+
+impl LocationPrivacy {
+    pub fn from_config(config: &ServerConfig) -> Self {
+        let meters = config.location_grid_meters;
+
+        match config.location_privacy {
+            LocationPrivacyMode::Exact => LocationPrivacy::Exact,
+            LocationPrivacyMode::Grid  => LocationPrivacy::Grid(meters),
+            LocationPrivacyMode::Cell  => LocationPrivacy::Cell(meters)
+        }
+    }
+
+    /// (latitude, longitude) of the user as shown to other players
+    pub fn apply(&self, location: &UserLocation) -> (f64, f64) {
+        match (*self, location.cell_centroid) {
+            (LocationPrivacy::Exact, _)                => (location.latitude, location.longitude),
+            (LocationPrivacy::Cell(_), Some(centroid)) => centroid,
+            (LocationPrivacy::Cell(meters), None)
+                | (LocationPrivacy::Grid(meters), _)   => snap(location.latitude, location.longitude, meters)
+        }
+    }
+
+    /// Location of another user, in the shape of `UserLocation`
+    pub fn to_json(&self, location: &UserLocation) -> Value {
+        let (latitude, longitude) = self.apply(location);
+        json!({
+            "id": location.id,
+            "latitude": latitude,
+            "longitude": longitude,
+            "cell_id": location.cell_id
+        })
+    }
+}
+
+/// Center of the grid square of `meters` containing the position
+fn snap(latitude: f64, longitude: f64, meters: f64) -> (f64, f64) {
+    let latitude_step = meters / METERS_PER_DEGREE;
+    let latitude = ((latitude / latitude_step).floor() + 0.5) * latitude_step;
+
+    // Squares are narrower in degrees of longitude away from the equator,
+    // the snapped latitude keeps every square the same width
+    let longitude_step = meters / (METERS_PER_DEGREE * latitude.to_radians().cos().max(0.01));
+    let longitude = ((longitude / longitude_step).floor() + 0.5) * longitude_step;
+
+    (latitude, longitude)
+}
@@ -0,0 +1,381 @@
+use std::io;
+use std::io::Read;
+use std::io::Cursor;
+use std::thread;
+use std::sync::Arc;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::error::Error;
+use std::time::Duration as StdDuration;
+use std::collections::BTreeMap;
+
+use chrono::prelude::*;
+use chrono::Duration;
+use serde_json;
+use serde_json::Value;
+
+use soundlines_core::db::Pool;
+use soundlines_core::db::Connection;
+use soundlines_core::db::extensions::*;
+use soundlines_core::db::models::Cell;
+use soundlines_core::db::models::EventKind;
+use soundlines_core::db::models::WorldEvent;
+
+use snapshots;
+use snapshots::SnapshotsDir;
+
+/// Readings tables averaged into the cells, with the cell columns they update
+const CELL_READINGS: [(&'static str, &'static str); 3] = [
+    ("wifi_readings", "wifi"),
+    ("sound_readings", "sound"),
+    ("light_readings", "light")
+];
+
+/// Entities, seeds and cells at a past time. Starts from the last snapshot
+/// before that time and replays the events and readings after it: entities
+/// and seeds are added and removed, but their attributes are the ones of the
+/// snapshot, or of the event that added them. The wifi, sound and light
+/// aggregates of the cells are recomputed from the readings, visits are the
+/// ones of the snapshot.
+pub struct World {
+    pub at: DateTime<Utc>,
+    pub snapshot: Option<String>,
+    entities: BTreeMap<i64, Value>,
+    seeds: BTreeMap<i64, Value>,
+    cells: BTreeMap<i64, Value>
+}
+
+impl World {
+    pub fn at(conn: &Connection, dir: &SnapshotsDir, at: DateTime<Utc>) -> Result<World, Box<Error>> {
+        let snapshot = snapshots::list(dir)?.into_iter().find(|id| id.taken_at() <= at);
+
+        let mut world = match snapshot {
+            Some(id) => {
+                let json = id.read(dir)?;
+                World {
+                    at: id.taken_at(),
+                    snapshot: Some(id.as_str().to_string()),
+                    entities: by_id(&json["entities"]),
+                    seeds: by_id(&json["seeds"]),
+                    cells: by_id(&json["cells"])
+                }
+            },
+
+            // Before the first snapshot the cells are rebuilt from every
+            // reading, starting from the current grid
+            None => World {
+                at: DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp(0, 0), Utc),
+                snapshot: None,
+                entities: BTreeMap::new(),
+                seeds: BTreeMap::new(),
+                cells: conn.all::<Cell>()?.into_iter().map(|cell| (cell.id as i64, blank_cell_json(cell))).collect()
+            }
+        };
+
+        world.advance(conn, at)?;
+        Ok(world)
+    }
+
+    /// Applies the events and readings up to `until`
+    pub fn advance(&mut self, conn: &Connection, until: DateTime<Utc>) -> Result<(), Box<Error>> {
+        if until <= self.at {
+            return Ok(());
+        }
+
+        for event in WorldEvent::between(conn, self.at, until)? {
+            self.apply(event);
+        }
+
+        for &(table, column) in CELL_READINGS.iter() {
+            let query = format!(r#"
+                select cells.id, sum({table}.level)::real, count(*)
+                from {table}
+                inner join cells on st_contains(cells.geom, {table}.point)
+                where {table}.created_at > $1 and {table}.created_at <= $2
+                group by cells.id
+            "#, table = table);
+
+            for row in conn.query(&query, &[&self.at, &until])?.iter() {
+                let cell_id: i32 = row.get(0);
+                let total: f32 = row.get(1);
+                let count: i64 = row.get(2);
+
+                if let Some(cell) = self.cells.get_mut(&(cell_id as i64)) {
+                    add_readings(cell, column, total as f64, count as f64);
+                }
+            }
+        }
+
+        self.at = until;
+        Ok(())
+    }
+
+    fn apply(&mut self, event: WorldEvent) {
+        let entity_id = event.entity_id.map(|id| id as i64);
+        let seed_id = event.seed_id.map(|id| id as i64);
+
+        match event.kind {
+            EventKind::Birth | EventKind::Spread => {
+                if let Some(seed_id) = seed_id {
+                    self.seeds.remove(&seed_id);
+                }
+
+                if let Some(entity_id) = entity_id {
+                    let entity = match event.payload.get("entity") {
+                        Some(entity) => entity.clone(),
+                        None         => fallback_json(&event)
+                    };
+
+                    self.entities.insert(entity_id, entity);
+                }
+            },
+
+            EventKind::Death => {
+                if let Some(entity_id) = entity_id {
+                    self.entities.remove(&entity_id);
+                }
+            },
+
+            EventKind::SeedThrown => {
+                if let Some(seed_id) = seed_id {
+                    let seed = match event.payload.get("seed") {
+                        Some(seed) => seed.clone(),
+                        None       => fallback_json(&event)
+                    };
+
+                    self.seeds.insert(seed_id, seed);
+                }
+            },
+
+            EventKind::SeedDied | EventKind::Pickup => {
+                if let Some(seed_id) = seed_id {
+                    self.seeds.remove(&seed_id);
+                }
+            },
+
+            EventKind::Mating | EventKind::Join | EventKind::Leave => {}
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "at": self.at,
+            "snapshot": self.snapshot,
+            "entities": self.entities.values().collect::<Vec<_>>(),
+            "seeds": self.seeds.values().collect::<Vec<_>>(),
+            "cells": self.cells.values().collect::<Vec<_>>()
+        })
+    }
+}
+
+fn by_id(items: &Value) -> BTreeMap<i64, Value> {
+    items.as_array()
+        .map(|items| items.iter().filter_map(|item| item["id"].as_i64().map(|id| (id, item.clone()))).collect())
+        .unwrap_or_default()
+}
+
+/// Cell without any reading or visit
+fn blank_cell_json(cell: Cell) -> Value {
+    Cell {
+        wifi: 0.0, wifi_total: 0.0, wifi_count: 0.0,
+        light: 0.0, light_total: 0.0, light_count: 0.0,
+        sound: 0.0, sound_total: 0.0, sound_count: 0.0,
+        sns: 0,
+        visit: 0,
+        ..cell
+    }.into_json()
+}
+
+/// Adds `count` readings summing to `total` to the `column` aggregates of the
+/// cell, the same way the collectors do
+fn add_readings(cell: &mut Value, column: &str, total: f64, count: f64) {
+    let total_key = format!("{}_total", column);
+    let count_key = format!("{}_count", column);
+
+    let total = cell[total_key.as_str()].as_f64().unwrap_or(0.0) + total;
+    let count = cell[count_key.as_str()].as_f64().unwrap_or(0.0) + count;
+
+    cell[total_key.as_str()] = json!(total);
+    cell[count_key.as_str()] = json!(count);
+    cell[column] = json!(if count > 0.0 { total / count } else { 0.0 });
+}
+
+/// What is known of an item added by an event without a payload
+fn fallback_json(event: &WorldEvent) -> Value {
+    let id = if event.kind == EventKind::SeedThrown { event.seed_id } else { event.entity_id };
+
+    json!({
+        "id": id,
+        "cell_id": event.cell_id,
+        "dna_id": event.dna_id,
+        "latitude": event.point.as_ref().map(|point| point.y),
+        "longitude": event.point.as_ref().map(|point| point.x)
+    })
+}
+
+/// Longest stream, in frames
+pub const MAX_FRAMES: i64 = 10000;
+
+/// Longest stream, in seconds of real time
+pub const MAX_STREAM_SECS: i64 = 60 * 60;
+
+/// Most streams sent at once, as each one holds a worker thread until it ends
+pub const MAX_STREAMS: usize = 2;
+
+/// Count of the streams being sent
+#[derive(Default)]
+pub struct ReplayStreams(Arc<AtomicUsize>);
+
+impl ReplayStreams {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Slot for a new stream, freed when it is dropped. `None` when
+    /// `MAX_STREAMS` streams are being sent already
+    pub fn slot(&self) -> Option<StreamSlot> {
+        if self.0.fetch_add(1, Ordering::SeqCst) >= MAX_STREAMS {
+            self.0.fetch_sub(1, Ordering::SeqCst);
+            return None;
+        }
+
+        Some(StreamSlot(self.0.clone()))
+    }
+}
+
+pub struct StreamSlot(Arc<AtomicUsize>);
+
+impl Drop for StreamSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Whether the stream parameters make sense
+pub fn check_stream(from: DateTime<Utc>, until: DateTime<Utc>, step: Duration, speed: f64) -> Result<(), String> {
+    if until <= from {
+        return Err("until should be after from".to_string());
+    }
+
+    if step < Duration::seconds(1) {
+        return Err("step should be at least a second".to_string());
+    }
+
+    if !(speed > 0.0 && speed.is_finite()) {
+        return Err("speed should be positive".to_string());
+    }
+
+    if (until - from).num_seconds() / step.num_seconds() > MAX_FRAMES {
+        return Err(format!("At most {} frames can be streamed", MAX_FRAMES));
+    }
+
+    // Frames are `step / speed` apart, whatever the step
+    if (until - from).num_seconds() as f64 / speed > MAX_STREAM_SECS as f64 {
+        return Err(format!("Streams can last at most {} seconds, raise the speed", MAX_STREAM_SECS));
+    }
+
+    Ok(())
+}
+
+/// Newline delimited JSON of the world every `step` from `from` to `until`,
+/// waiting `step / speed` of real time between frames. A connection is only
+/// taken from the pool while a frame is computed.
+pub struct ReplayStream {
+    pool: Pool,
+    _slot: Option<StreamSlot>,
+    world: World,
+    until: DateTime<Utc>,
+    step: Duration,
+    delay: StdDuration,
+    buffer: Cursor<Vec<u8>>,
+    started: bool
+}
+
+impl ReplayStream {
+    pub fn new(pool: Pool, slot: Option<StreamSlot>, dir: &SnapshotsDir, from: DateTime<Utc>, until: DateTime<Utc>, step: Duration, speed: f64) -> Result<ReplayStream, Box<Error>> {
+        let world = World::at(&*pool.get()?, dir, from)?;
+        let delay_millis = step.num_milliseconds() as f64 / speed;
+
+        Ok(ReplayStream {
+            pool,
+            _slot: slot,
+            world,
+            until,
+            step,
+            delay: StdDuration::from_millis(delay_millis as u64),
+            buffer: Cursor::new(Vec::new()),
+            started: false
+        })
+    }
+
+    fn next_frame(&mut self) -> io::Result<bool> {
+        if self.started {
+            if self.world.at >= self.until {
+                return Ok(false);
+            }
+
+            thread::sleep(self.delay);
+
+            let next = ::std::cmp::min(self.world.at + self.step, self.until);
+            let conn = self.pool.get()
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))?;
+            self.world.advance(&conn, next)
+                .map_err(|err| io::Error::new(io::ErrorKind::Other, err.to_string()))?;
+        }
+
+        self.started = true;
+
+        let mut frame = serde_json::to_vec(&self.world.to_json())
+            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        frame.push(b'\n');
+        self.buffer = Cursor::new(frame);
+
+        Ok(true)
+    }
+}
+
+impl Read for ReplayStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let read = self.buffer.read(buf)?;
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+
+            if !self.next_frame()? {
+                return Ok(0);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn streams_are_limited_until_a_slot_is_freed() {
+        let streams = ReplayStreams::new();
+        let slots = (0..MAX_STREAMS).map(|_| streams.slot()).collect::<Option<Vec<_>>>();
+
+        assert!(slots.is_some());
+        assert!(streams.slot().is_none());
+
+        drop(slots);
+        assert!(streams.slot().is_some());
+    }
+
+    #[test]
+    fn readings_are_averaged_into_the_cell() {
+        let mut cell = json!({ "id": 1, "sound": 2.0, "sound_total": 4.0, "sound_count": 2.0 });
+        add_readings(&mut cell, "sound", 8.0, 2.0);
+
+        assert_eq!(cell["sound_total"], json!(12.0));
+        assert_eq!(cell["sound_count"], json!(4.0));
+        assert_eq!(cell["sound"], json!(3.0));
+
+        let mut blank = json!({ "id": 1 });
+        add_readings(&mut blank, "wifi", 0.0, 0.0);
+        assert_eq!(blank["wifi"], json!(0.0));
+    }
+}
@@ -0,0 +1,68 @@
+use chrono::prelude::*;
+
+use rocket_jwt::RegisteredClaims;
+use rocket_jwt::RevocationStore;
+use rocket_jwt::RevocationResult;
+use rocket_jwt::is_subject_revoked;
+
+use soundlines_core::db::Pool;
+use soundlines_core::db::models::User;
+use soundlines_core::db::models::RevokedToken;
+use soundlines_core::db::extensions::*;
+
+/// Keeps revoked tokens in `revoked_tokens` and revoked users in
+/// `users.tokens_revoked_at`, token subjects are user ids.
+pub struct DbRevocationStore {
+    pool: Pool
+}
+
+impl DbRevocationStore {
+    pub fn new(pool: Pool) -> Self {
+        DbRevocationStore { pool }
+    }
+}
+
+impl RevocationStore for DbRevocationStore {
+    fn is_revoked(&self, claims: &RegisteredClaims) -> bool {
+        // Fail closed, a database hiccup shouldn't let revoked tokens in
+        let conn = match self.pool.get() {
+            Ok(conn) => conn,
+            Err(_)   => return true
+        };
+
+        if let Some(ref jti) = claims.jti {
+            match RevokedToken::exists(&conn, jti) {
+                Ok(false) => {},
+                _         => return true
+            }
+        }
+
+        let user_id = match claims.sub.as_ref().and_then(|sub| sub.parse::<i32>().ok()) {
+            Some(user_id) => user_id,
+            None          => return false
+        };
+
+        match User::tokens_revoked_at(&conn, user_id) {
+            Ok(revoked_at) => is_subject_revoked(claims, revoked_at.map(|at| at.timestamp())),
+            Err(_)         => true
+        }
+    }
+
+    fn revoke_token(&self, jti: &str, expires_at: Option<i64>) -> RevocationResult {
+        let conn = self.pool.get()?;
+        let expires_at = expires_at.map(|exp| Utc.timestamp(exp, 0));
+
+        conn.insert(&RevokedToken::new(jti.to_string(), expires_at))?;
+        RevokedToken::delete_expired(&conn)?;
+
+        Ok(())
+    }
+
+    fn revoke_subject(&self, sub: &str, before: i64) -> RevocationResult {
+        let conn = self.pool.get()?;
+        let user_id = sub.parse::<i32>()?;
+
+        User::revoke_tokens(&conn, user_id, &Utc.timestamp(before, 0))?;
+        Ok(())
+    }
+}
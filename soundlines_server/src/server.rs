@@ -1,9 +1,43 @@
+use std::time::Duration;
+
 use rocket;
 use rocket::Request;
+use rocket::State;
+use rocket::Outcome;
+use rocket::Route;
+use rocket_contrib::Json;
 use rocket_jwt::JwtConfig;
+use rocket_jwt::JwtKey;
+use rocket_jwt::KeySet;
+use rocket_jwt::Algorithm;
+use rocket_jwt::TokenSource;
+use rocket_jwt::RevocationStore;
+use rocket_jwt::MemoryRevocationStore;
 use soundlines_core::db;
+use soundlines_core::db::Pool;
+use soundlines_config::Config;
+use soundlines_config::ServerConfig;
+use soundlines_config::JwtKeyConfig;
+use soundlines_config::JwtAlgorithm;
+use soundlines_config::JwtSource;
+use soundlines_config::JwtRevocations;
+use serde_json::Value as JValue;
+
+use revocations::DbRevocationStore;
+use encoding::ResponseEncoding;
+use cache::ResponseCache;
+use metrics::Metrics;
+use metrics::RequestMetrics;
+use db_guard::DbConn;
+use client_version::app_config;
+use privacy::LocationPrivacy;
+use presence::Presence;
+use snapshots::SnapshotsDir;
+use replay::ReplayStreams;
 
 use endpoints;
+use endpoints::health::ReadyConfig;
+use endpoints::trades::TradeConfig;
 
 #[error(400)]
 fn error(_: &Request) -> &'static str {
@@ -15,61 +49,227 @@ fn error_401(_: &Request) -> &'static str {
     ""
 }
 
+#[error(403)]
+fn error_403(_: &Request) -> &'static str {
+    ""
+}
+
+/// Tells outdated clients which version they need
+#[error(426)]
+fn error_426(request: &Request) -> Json<JValue> {
+    let config = match (request.guard::<State<ResponseCache>>(), request.guard::<DbConn>()) {
+        (Outcome::Success(cache), Outcome::Success(conn)) => app_config(&cache, &conn).ok(),
+        _ => None
+    };
+
+    Json(json!({
+        "error": "upgrade_required",
+        "min_client_version": config.as_ref().map(|config| &config.min_client_version),
+        "latest_client_version": config.as_ref().map(|config| &config.latest_client_version)
+    }))
+}
+
 #[error(500)]
 fn error_500(_: &Request) -> &'static str {
     ""
 }
 
-pub fn run() {
-    let db_pool = db::init_pool();
+pub fn jwt_config(config: &ServerConfig) -> JwtConfig {
+    // Clients sign their register tokens with the shared secret, without a kid
+    let secret_key = JwtKey::hmac(None, config.jwt_secret().as_bytes());
 
-    let igniter = rocket::ignite();
+    let keys = match config.jwt_keys.split_first() {
+        Some((signing_key, previous_keys)) => {
+            // First key signs new tokens, the rest are kept for validating
+            // tokens signed before the rotation
+            let mut keys = KeySet::new(load_jwt_key(signing_key));
+            for key in previous_keys.iter() {
+                keys.add(load_jwt_key(key));
+            }
 
-    let jwt_secret = igniter.config().get_str("jwt_secret").expect("jwt_secret").to_string();
-    let jwt_config = JwtConfig { secret: jwt_secret };
+            keys.add(secret_key);
+            keys
+        },
 
-    igniter
-	    .mount("/", routes![
-	        endpoints::weather::get
-	    ])
-        .mount("/data", routes![
+        None => KeySet::new(secret_key)
+    };
+
+    let mut jwt_config = JwtConfig::with_keys(keys);
+    jwt_config.issuer = config.jwt_issuer.clone();
+    jwt_config.audience = config.jwt_audience.clone();
+    jwt_config.lifetime = Some(config.jwt_lifetime).filter(|secs| *secs > 0).map(Duration::from_secs);
+    jwt_config.refresh_lifetime = Some(config.jwt_refresh_lifetime).filter(|secs| *secs > 0).map(Duration::from_secs);
+    jwt_config.sources = config.jwt_sources.iter().map(|source| match *source {
+        JwtSource::Bearer           => TokenSource::Bearer,
+        JwtSource::Cookie(ref name) => TokenSource::Cookie(name.clone()),
+        JwtSource::Query(ref name)  => TokenSource::Query(name.clone())
+    }).collect();
+
+    jwt_config
+}
+
+fn load_jwt_key(key: &JwtKeyConfig) -> JwtKey {
+    let algorithm = match key.algorithm {
+        JwtAlgorithm::Rs256 => Algorithm::RS256,
+        JwtAlgorithm::Rs384 => Algorithm::RS384,
+        JwtAlgorithm::Rs512 => Algorithm::RS512
+    };
+
+    JwtKey::from_pem_files(key.kid.clone(), algorithm, key.private_key.as_ref().map(String::as_str), &key.public_key)
+        .unwrap_or_else(|err| panic!("Failed to load jwt key {}: {}", key.kid, err))
+}
+
+fn ready_config(config: &ServerConfig) -> ReadyConfig {
+    ReadyConfig {
+        simulation_max_age: config.ready_simulation_max_age,
+        external_max_age: config.ready_external_max_age
+    }
+}
+
+fn trade_config(config: &ServerConfig) -> TradeConfig {
+    TradeConfig {
+        max_distance: config.trade_distance_meters,
+        offer_ttl: config.trade_offer_ttl
+    }
+}
+
+fn revocation_store(config: &ServerConfig, db_pool: &Pool) -> Option<Box<RevocationStore>> {
+    match config.jwt_revocations {
+        JwtRevocations::Database => Some(Box::new(DbRevocationStore::new(db_pool.clone()))),
+        JwtRevocations::Memory   => Some(Box::new(MemoryRevocationStore::new())),
+        JwtRevocations::None     => None
+    }
+}
+
+/// Routes of the server by their mount points, also used for the OpenAPI document
+pub fn mounts() -> Vec<(&'static str, Vec<Route>)> {
+    vec![
+        ("/", routes![
+            endpoints::weather::get,
+            endpoints::docs::openapi,
+            endpoints::metrics::metrics,
+            endpoints::health::health,
+            endpoints::health::ready,
+            endpoints::config::get
+        ]),
+        ("/.well-known", routes![
+            endpoints::well_known::jwks
+        ]),
+        ("/data", routes![
             endpoints::collectors::wifi,
             endpoints::collectors::sound,
             endpoints::collectors::light,
-            endpoints::collectors::gps,
-        ])
-        .mount("/cells", routes![
+            endpoints::collectors::gps
+        ]),
+        ("/cells", routes![
             endpoints::cells::index,
             endpoints::cells::show,
-            endpoints::cells::cells_at,
-        ])
-        .mount("/users", routes![
+            endpoints::cells::cells_at
+        ]),
+        ("/users", routes![
             endpoints::users::register,
+            endpoints::users::refresh,
+            endpoints::users::logout,
+            endpoints::users::revoke_own,
+            endpoints::users::revoke,
+            endpoints::users::deregister,
+            endpoints::users::inventory,
+            endpoints::users::own_garden,
+            endpoints::users::garden,
+            endpoints::users::own_stats,
+            endpoints::users::stats,
+            endpoints::users::own_profile,
+            endpoints::users::profile,
+            endpoints::users::update_profile,
+            endpoints::users::devices,
+            endpoints::users::link_device,
+            endpoints::users::unlink_device,
+            endpoints::users::own_privacy,
+            endpoints::users::update_privacy,
+            endpoints::users::delete_data,
             endpoints::users::location,
             endpoints::users::location_range,
             endpoints::users::location_times
-        ])
-        .mount("/entities", routes![
+        ]),
+        ("/entities", routes![
             endpoints::entities::generate,
             endpoints::entities::index,
             endpoints::entities::delete
-        ])
-        .mount("/seeds", routes![
+        ]),
+        ("/seeds", routes![
             endpoints::seeds::index,
             endpoints::seeds::pickup,
             endpoints::seeds::spread,
             endpoints::seeds::get,
             endpoints::seeds::deploy
-        ])
-        .mount("/dev", routes![
+        ]),
+        ("/trades", routes![
+            endpoints::trades::offer,
+            endpoints::trades::pending,
+            endpoints::trades::history,
+            endpoints::trades::get,
+            endpoints::trades::accept,
+            endpoints::trades::decline,
+            endpoints::trades::cancel
+        ]),
+        ("/events", routes![
+            endpoints::events::list,
+            endpoints::events::index
+        ]),
+        ("/leaderboards", routes![
+            endpoints::leaderboards::get
+        ]),
+        ("/replay", routes![
+            endpoints::replay::world_at,
+            endpoints::replay::stream
+        ]),
+        ("/dev", routes![
             endpoints::dev::get_version,
             endpoints::dev::update_version,
+            endpoints::config::update,
             endpoints::dev::get_settings,
             endpoints::dev::update_setting,
-            endpoints::dev::get_snapshot
+            endpoints::dev::setting_versions,
+            endpoints::dev::setting_diff,
+            endpoints::dev::rollback_setting,
+            endpoints::dev::list_snapshots,
+            endpoints::dev::get_snapshot,
+            endpoints::dev::diff_snapshots,
+            endpoints::dev::get_snapshot_legacy
         ])
-        .catch(errors![error, error_401, error_500])
+    ]
+}
+
+pub fn run(config: Config) {
+    let db_pool = db::init_pool(&config.database);
+
+    let mut igniter = rocket::ignite();
+    let mut jwt_config = jwt_config(&config.server);
+    jwt_config.revocations = revocation_store(&config.server, &db_pool);
+    let ready_config = ready_config(&config.server);
+    let trade_config = trade_config(&config.server);
+    let location_privacy = LocationPrivacy::from_config(&config.server);
+    let presence = Presence::from_config(&config.server, &db_pool);
+    let snapshots_dir = SnapshotsDir::from_config(&config.sim);
+    presence.sweep_every(db_pool.clone(), Duration::from_secs(5));
+
+    for (base, routes) in mounts() {
+        igniter = igniter.mount(base, routes);
+    }
+
+    igniter
+        .catch(errors![error, error_401, error_403, error_426, error_500])
+        .attach(RequestMetrics)
+        .attach(ResponseEncoding)
         .manage(db_pool)
         .manage(jwt_config)
+        .manage(ResponseCache::new())
+        .manage(Metrics::new())
+        .manage(ready_config)
+        .manage(location_privacy)
+        .manage(presence)
+        .manage(trade_config)
+        .manage(snapshots_dir)
+        .manage(ReplayStreams::new())
         .launch();
 }
@@ -0,0 +1,295 @@
+use std::io;
+use std::fs;
+use std::fs::File;
+use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+
+use chrono::prelude::*;
+use rocket::http::RawStr;
+use rocket::request::FromParam;
+use serde_json;
+use serde_json::Value;
+
+use soundlines_config::SimConfig;
+
+/// Directory the snapshots are read from, `snapshots_dir` of `[sim]` which
+/// `soundlines_sim snapshot` writes to
+pub struct SnapshotsDir(pub PathBuf);
+
+impl SnapshotsDir {
+    pub fn from_config(config: &SimConfig) -> SnapshotsDir {
+        SnapshotsDir(PathBuf::from(&config.snapshots_dir))
+    }
+}
+
+/// Snapshots are named after the time they were taken at, in Japan time
+const ID_FORMAT: &'static str = "%Y_%m_%d_%H_%M_%S";
+const JAPAN_OFFSET_SECS: i32 = 9 * 60 * 60;
+
+/// Name of a snapshot file without its extension, e.g. `2017_10_14_09_00_00`.
+/// Anything else is rejected, so ids can't point outside the directory.
+pub struct SnapshotId(String);
+
+impl SnapshotId {
+    pub fn parse(id: &str) -> Option<SnapshotId> {
+        let well_formed = id.len() == 19 && id.char_indices().all(|(i, c)| match i {
+            4 | 7 | 10 | 13 | 16 => c == '_',
+            _                    => c.is_digit(10)
+        });
+
+        if !well_formed || NaiveDateTime::parse_from_str(id, ID_FORMAT).is_err() {
+            return None;
+        }
+
+        Some(SnapshotId(id.to_string()))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn taken_at(&self) -> DateTime<Utc> {
+        let local = NaiveDateTime::parse_from_str(&self.0, ID_FORMAT).expect("Snapshot ids are validated");
+        let utc = local - ::chrono::Duration::seconds(JAPAN_OFFSET_SECS as i64);
+        DateTime::from_utc(utc, Utc)
+    }
+
+    pub fn path(&self, dir: &SnapshotsDir) -> PathBuf {
+        dir.0.join(&self.0).with_extension("json")
+    }
+
+    pub fn read(&self, dir: &SnapshotsDir) -> io::Result<Value> {
+        let file = File::open(self.path(dir))?;
+        serde_json::from_reader(file).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Time taken at and the number of items of each kind
+    pub fn summary(&self, dir: &SnapshotsDir) -> io::Result<Value> {
+        let snapshot = self.read(dir)?;
+        let count = |kind: &str| snapshot[kind].as_array().map(Vec::len).unwrap_or(0);
+
+        Ok(json!({
+            "id": self.as_str(),
+            "taken_at": self.taken_at(),
+            "entities": count("entities"),
+            "seeds": count("seeds"),
+            "cells": count("cells"),
+            "users": count("users")
+        }))
+    }
+}
+
+impl<'a> FromParam<'a> for SnapshotId {
+    type Error = &'a RawStr;
+
+    fn from_param(param: &'a RawStr) -> Result<Self, Self::Error> {
+        SnapshotId::parse(param.as_str()).ok_or(param)
+    }
+}
+
+/// Snapshots in the directory, newest first
+pub fn list(dir: &SnapshotsDir) -> io::Result<Vec<SnapshotId>> {
+    let entries = match fs::read_dir(&dir.0) {
+        Ok(entries) => entries,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err)
+    };
+
+    let mut ids = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+
+        if let Some(id) = path.file_stem().and_then(|stem| stem.to_str()).and_then(SnapshotId::parse) {
+            ids.push(id);
+        }
+    }
+
+    // Ids sort the same as the times they stand for
+    ids.sort_by(|a, b| b.0.cmp(&a.0));
+    Ok(ids)
+}
+
+/// Entities born, died and changed and seeds added and removed between two
+/// snapshots, items are matched by id
+pub fn diff(from: &Value, to: &Value) -> Value {
+    let (entities_from, entities_to) = (by_id(&from["entities"]), by_id(&to["entities"]));
+    let (seeds_from, seeds_to) = (by_id(&from["seeds"]), by_id(&to["seeds"]));
+
+    let changed = entities_from.iter()
+        .filter_map(|(id, before)| {
+            let after = entities_to.get(id)?;
+            let changes = changes(before, after);
+            if changes.is_empty() {
+                None
+            } else {
+                Some(json!({ "id": id, "changes": changes }))
+            }
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "entities": {
+            "born": missing_from(&entities_to, &entities_from),
+            "died": missing_from(&entities_from, &entities_to),
+            "changed": changed
+        },
+        "seeds": {
+            "added": missing_from(&seeds_to, &seeds_from),
+            "removed": missing_from(&seeds_from, &seeds_to)
+        }
+    })
+}
+
+fn by_id(items: &Value) -> BTreeMap<i64, &Value> {
+    items.as_array()
+        .map(|items| items.iter().filter_map(|item| item["id"].as_i64().map(|id| (id, item))).collect())
+        .unwrap_or_default()
+}
+
+/// Items of `items` which are not in `other`
+fn missing_from<'a>(items: &BTreeMap<i64, &'a Value>, other: &BTreeMap<i64, &Value>) -> Vec<&'a Value> {
+    items.iter()
+        .filter(|&(id, _)| !other.contains_key(id))
+        .map(|(_, item)| *item)
+        .collect()
+}
+
+fn changes(before: &Value, after: &Value) -> BTreeMap<String, Value> {
+    let (before, after) = match (before.as_object(), after.as_object()) {
+        (Some(before), Some(after)) => (before, after),
+        _                           => return BTreeMap::new()
+    };
+
+    // Fields only in one of them changed from or to null
+    let fields = before.keys().chain(after.keys()).collect::<BTreeSet<_>>();
+
+    fields.into_iter()
+        .filter_map(|field| {
+            let old_value = before.get(field).unwrap_or(&Value::Null);
+            let new_value = after.get(field).unwrap_or(&Value::Null);
+            if old_value == new_value {
+                None
+            } else {
+                Some((field.clone(), json!({ "from": old_value, "to": new_value })))
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    fn id(id: &str) -> Option<String> {
+        SnapshotId::parse(id).map(|id| id.as_str().to_string())
+    }
+
+    #[test]
+    fn ids_are_snapshot_times() {
+        assert_eq!(id("2017_10_14_09_00_00"), Some("2017_10_14_09_00_00".to_string()));
+        assert_eq!(id("2016_02_29_23_59_59"), Some("2016_02_29_23_59_59".to_string()));
+    }
+
+    #[test]
+    fn ids_outside_the_directory_are_rejected() {
+        assert_eq!(id("../../../etc/passwd"), None);
+        assert_eq!(id("../2017_10_14_09_00_00"), None);
+        assert_eq!(id("2017_10_14_09_00_00/.."), None);
+        assert_eq!(id("..%2F2017_10_14_09_00"), None);
+        assert_eq!(id("/2017_10_14_09_00_0"), None);
+    }
+
+    #[test]
+    fn ids_of_the_wrong_length_are_rejected() {
+        assert_eq!(id(""), None);
+        assert_eq!(id("2017_10_14_09_00"), None);
+        assert_eq!(id("2017_10_14_09_00_000"), None);
+        assert_eq!(id("2017_10_14_09_00_00.json"), None);
+        assert_eq!(id("2017-10-14T09:00:00"), None);
+    }
+
+    #[test]
+    fn ids_of_invalid_dates_are_rejected() {
+        assert_eq!(id("2017_02_30_09_00_00"), None);
+        assert_eq!(id("2017_02_29_09_00_00"), None);
+        assert_eq!(id("2017_13_01_09_00_00"), None);
+        assert_eq!(id("2017_10_14_24_00_00"), None);
+    }
+
+    #[test]
+    fn ids_are_in_japan_time() {
+        let taken_at = SnapshotId::parse("2017_10_14_09_00_00").unwrap().taken_at();
+        assert_eq!(taken_at, Utc.ymd(2017, 10, 14).and_hms(0, 0, 0));
+    }
+
+    #[test]
+    fn snapshots_are_listed_newest_first() {
+        let dir = env::temp_dir().join(format!("soundlines_snapshots_{}", Utc::now().timestamp_nanos()));
+        fs::create_dir_all(&dir).unwrap();
+        for name in &["2017_10_14_09_00_00.json", "2017_10_15_09_00_00.json", "2017_10_16_09_00_00.txt", "notes.json"] {
+            File::create(dir.join(name)).unwrap();
+        }
+
+        let ids = list(&SnapshotsDir(dir.clone())).unwrap().iter().map(|id| id.as_str().to_string()).collect::<Vec<_>>();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(ids, vec!["2017_10_15_09_00_00", "2017_10_14_09_00_00"]);
+        assert!(list(&SnapshotsDir(dir)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn diff_lists_born_died_and_changed_entities() {
+        let from = json!({
+            "entities": [
+                { "id": 1, "size": 1.0, "cell_id": 3 },
+                { "id": 2, "size": 2.0 },
+                { "id": 3, "size": 1.0 }
+            ],
+            "seeds": [{ "id": 10 }, { "id": 11 }]
+        });
+        let to = json!({
+            "entities": [
+                { "id": 1, "size": 1.5, "cell_id": 3 },
+                { "id": 3, "size": 1.0, "owner_id": 7 },
+                { "id": 4, "size": 0.1 }
+            ],
+            "seeds": [{ "id": 11 }, { "id": 12 }]
+        });
+
+        let diff = diff(&from, &to);
+
+        assert_eq!(diff["entities"]["born"], json!([{ "id": 4, "size": 0.1 }]));
+        assert_eq!(diff["entities"]["died"], json!([{ "id": 2, "size": 2.0 }]));
+        assert_eq!(diff["entities"]["changed"], json!([
+            { "id": 1, "changes": { "size": { "from": 1.0, "to": 1.5 } } },
+            { "id": 3, "changes": { "owner_id": { "from": null, "to": 7 } } }
+        ]));
+        assert_eq!(diff["seeds"]["added"], json!([{ "id": 12 }]));
+        assert_eq!(diff["seeds"]["removed"], json!([{ "id": 10 }]));
+    }
+
+    #[test]
+    fn diff_of_the_same_snapshot_is_empty() {
+        let snapshot = json!({ "entities": [{ "id": 1, "size": 1.0 }], "seeds": [{ "id": 2 }] });
+        let diff = diff(&snapshot, &snapshot);
+
+        assert_eq!(diff["entities"], json!({ "born": [], "died": [], "changed": [] }));
+        assert_eq!(diff["seeds"], json!({ "added": [], "removed": [] }));
+    }
+
+    #[test]
+    fn items_without_an_id_are_ignored() {
+        let from = json!({ "entities": [{ "size": 1.0 }], "seeds": "none" });
+        let to = json!({ "entities": [{ "id": "1" }] });
+        let diff = diff(&from, &to);
+
+        assert_eq!(diff["entities"], json!({ "born": [], "died": [], "changed": [] }));
+        assert_eq!(diff["seeds"], json!({ "added": [], "removed": [] }));
+    }
+}
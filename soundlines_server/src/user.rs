@@ -1,5 +1,8 @@
+use std::marker::PhantomData;
+
 use rocket::request;
 use rocket::Request;
+use rocket::State;
 use rocket::http::Status;
 use rocket::data;
 use rocket::Data;
@@ -8,32 +11,152 @@ use rocket::outcome::Outcome;
 use rocket::request::FromRequest;
 use rocket_contrib::Json;
 
+use serde::Serialize;
 use serde::de::DeserializeOwned;
 use serde_json::from_value;
 use serde_json::Value as JValue;
 
 use rocket_jwt::Jwt;
+use rocket_jwt::JwtConfig;
+use rocket_jwt::OptionalJwt;
+use rocket_jwt::RegisteredClaims;
 
 use soundlines_core::db::models::User;
+use soundlines_core::db::models::Role;
+use soundlines_core::db::extensions::*;
+
+use db_guard::DbConn;
+use client_version::SupportedClient;
 
+/// Claims of the register token. Without a `device_id` every registration
+/// creates a new player, as older clients expect.
 #[derive(Deserialize, Serialize)]
 pub struct RegisterPayload {
-    action: String
+    action: String,
+    #[serde(default)]
+    pub device_id: Option<String>,
+    /// Code from `/users/me/devices/link`, registers the device as that user
+    #[serde(default)]
+    pub link_code: Option<String>
+}
+
+/// Tokens signed with the shared secret can be forged by anyone holding the
+/// client app, they are only trusted while the server itself signs with it.
+pub fn is_trusted<T: Serialize + DeserializeOwned>(token: &Jwt<T>, jwt_config: &JwtConfig) -> bool {
+    token.kid.is_some() || jwt_config.keys.signing_key().kid.is_none()
+}
+
+/// Tokens issued before they carried a `sub` claim are missed by subject
+/// revocations when decoded, they are checked as tokens of the user they are
+/// for instead
+pub fn is_revoked_without_subject<T: Serialize + DeserializeOwned>(token: &Jwt<T>, user_id: i32, jwt_config: &JwtConfig) -> bool {
+    let store = match jwt_config.revocations {
+        Some(ref store) if token.claims.sub.is_none() => store,
+        _                                             => return false
+    };
+
+    store.is_revoked(&RegisteredClaims { sub: Some(user_id.to_string()), ..token.claims.clone() })
+}
+
+/// Minimum role required by an `Auth` guard
+pub trait Permission {
+    fn role() -> Role;
+}
+
+pub struct Player;
+pub struct Curator;
+pub struct Admin;
+
+impl Permission for Player {
+    fn role() -> Role { Role::Player }
+}
+
+impl Permission for Curator {
+    fn role() -> Role { Role::Curator }
 }
 
-pub struct Auth(User);
+impl Permission for Admin {
+    fn role() -> Role { Role::Admin }
+}
+
+/// Authenticated user with at least the role of `P`. Fails with `Unauthorized`
+/// when the token is missing or invalid, with `Forbidden` when the user's role
+/// is not enough, and with `Upgrade Required` for outdated clients.
+pub struct Auth<P: Permission = Player>(User, PhantomData<P>);
 
-impl Auth {
+impl<P: Permission> Auth<P> {
     pub fn into_user(self) -> User {
         self.0
     }
+
+    /// Checks a decoded token of the request against the user it is for
+    fn from_token(request: &Request, token: Jwt<User>) -> request::Outcome<Self, ()> {
+        // Tokens issued before expiry was introduced would never expire
+        let jwt_config = request.guard::<State<JwtConfig>>()?;
+        if jwt_config.lifetime.is_some() && token.claims.exp.is_none() {
+            return Outcome::Failure((Status::Unauthorized, ()));
+        }
+
+        if !is_trusted(&token, &jwt_config) || is_revoked_without_subject(&token, token.payload.id, &jwt_config) {
+            return Outcome::Failure((Status::Unauthorized, ()));
+        }
+
+        let user = token.into_inner();
+        let conn = request.guard::<DbConn>()?;
+
+        // Tokens of deregistered users are rejected even without a revocation
+        // store, players are only checked for that to keep the lookup cheap
+        if P::role() == Role::Player {
+            return match User::is_active(&conn, user.id) {
+                Ok(true)  => Outcome::Success(Auth(user, PhantomData)),
+                Ok(false) => Outcome::Failure((Status::Unauthorized, ())),
+                Err(_)    => Outcome::Failure((Status::InternalServerError, ()))
+            };
+        }
+
+        // Clients share the token secret to be able to register, so the role in
+        // the claims can't be trusted for anything above a player
+        let user = match conn.get::<User>(user.id) {
+            Ok(Some(ref user)) if user.is_deregistered() => return Outcome::Failure((Status::Unauthorized, ())),
+            Ok(Some(user)) => user,
+            Ok(None)       => return Outcome::Failure((Status::Unauthorized, ())),
+            Err(_)         => return Outcome::Failure((Status::InternalServerError, ()))
+        };
+
+        if user.role < P::role() {
+            return Outcome::Failure((Status::Forbidden, ()));
+        }
+
+        Outcome::Success(Auth(user, PhantomData))
+    }
 }
 
-impl<'a, 'r> FromRequest<'a, 'r> for Auth {
+impl<'a, 'r, P: Permission> FromRequest<'a, 'r> for Auth<P> {
     type Error = ();
     fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        request.guard::<SupportedClient>()?;
         let token = request.guard::<Jwt<User>>()?;
-        Outcome::Success(Auth(token.into_inner()))
+
+        Auth::from_token(request, token)
+    }
+}
+
+/// Authenticated user if the request has a token, anonymous otherwise. Invalid
+/// tokens still fail with `Unauthorized`.
+pub struct OptionalAuth(pub Option<User>);
+
+impl<'a, 'r> FromRequest<'a, 'r> for OptionalAuth {
+    type Error = ();
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        // The token is only decoded once, as decoding looks it up in the
+        // revocation store
+        let token = match request.guard::<OptionalJwt<User>>()? {
+            OptionalJwt(Some(token)) => token,
+            OptionalJwt(None)        => return Outcome::Success(OptionalAuth(None))
+        };
+
+        request.guard::<SupportedClient>()?;
+        Auth::<Player>::from_token(request, token).map(|auth| OptionalAuth(Some(auth.into_user())))
     }
 }
 
@@ -42,13 +165,11 @@ pub struct JsonUserIdCheck<T: DeserializeOwned>(pub T);
 impl<T: DeserializeOwned> FromData for JsonUserIdCheck<T> {
     type Error = ();
     fn from_data(request: &Request, data: Data) -> data::Outcome<Self, Self::Error> {
-        let token = match request.guard::<Jwt<User>>() {
-            Outcome::Success(token) => token,
-            _                       => return Outcome::Failure((Status::Unauthorized, ()))
+        let user = match request.guard::<Auth>() {
+            Outcome::Success(auth) => auth.into_user(),
+            _                      => return Outcome::Failure((Status::Unauthorized, ()))
         };
 
-        let user = token.into_inner();
-
         let json: JValue = Json::<JValue>::from_data(request, data).map_failure(|_| (Status::BadRequest, ()))?.into_inner();
 
         if json["user_id"] != user.id {
@@ -61,3 +182,39 @@ impl<T: DeserializeOwned> FromData for JsonUserIdCheck<T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rocket_jwt::RevocationStore;
+    use rocket_jwt::MemoryRevocationStore;
+
+    use super::*;
+
+    fn token(sub: Option<&str>, iat: i64) -> Jwt<JValue> {
+        let mut token = Jwt::new(json!({ "id": 1 }));
+        token.claims.sub = sub.map(str::to_string);
+        token.claims.iat = Some(iat);
+        token
+    }
+
+    #[test]
+    fn tokens_without_a_subject_are_revoked_as_their_user() {
+        let store = MemoryRevocationStore::new();
+        store.revoke_subject("1", 1000).unwrap();
+
+        let mut jwt_config = JwtConfig::new("secret");
+        jwt_config.revocations = Some(Box::new(store));
+
+        assert!(is_revoked_without_subject(&token(None, 1000), 1, &jwt_config));
+        assert!(!is_revoked_without_subject(&token(None, 1001), 1, &jwt_config));
+        assert!(!is_revoked_without_subject(&token(None, 999), 2, &jwt_config));
+
+        // Checked against their own subject when decoded
+        assert!(!is_revoked_without_subject(&token(Some("1"), 999), 1, &jwt_config));
+    }
+
+    #[test]
+    fn nothing_is_revoked_without_a_store() {
+        assert!(!is_revoked_without_subject(&token(None, 999), 1, &JwtConfig::new("secret")));
+    }
+}
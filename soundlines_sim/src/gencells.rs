@@ -13,12 +13,12 @@ use soundlines_core::postgis::ewkb::LineString;
 use soundlines_core::postgis::ewkb::Polygon;
 use soundlines_core::postgis::ewkb::Point;
 
-use soundlines_core::db::init_connection;
+use soundlines_core::db::Pool;
 use soundlines_core::db::models::Cell;
 use soundlines_core::db::extensions::*;
 
-pub fn run(cell_size: f64) -> Result<(), Box<Error>> {
-    let conn = init_connection();
+pub fn run(connection_pool: Pool, cell_size: f64) -> Result<(), Box<Error>> {
+    let conn = connection_pool.get()?;
     let cells = generate_cells(cell_size);
 
     conn.insert_batch(&cells)?;
@@ -6,6 +6,7 @@ use soundlines_core::db::models::Dna;
 use soundlines_core::db::models::Cell;
 use soundlines_core::db::models::Seed;
 use soundlines_core::db::models::Entity;
+use soundlines_core::db::models::InventorySeed;
 use soundlines_core::db::models::PlantSetting;
 use soundlines_core::db::extensions::*;
 
@@ -26,6 +27,7 @@ pub fn run(connection_pool: Pool, clear: bool) -> Result<(), Box<Error>> {
         conn.delete_all::<Dna>()?;
         conn.delete_all::<Entity>()?;
         conn.delete_all::<Seed>()?;
+        conn.delete_all::<InventorySeed>()?;
     }
 
     let mut dnas_to_create = Vec::with_capacity(cells.len() * 2);
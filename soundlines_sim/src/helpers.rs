@@ -45,6 +45,31 @@ pub fn random_choice_with<T, F>(elems: &[T], predicate: F) -> Option<&T>
     None
 }
 
+/// Picks an element with a probability proportional to its weight, elements
+/// with a weight of zero or less are never picked
+pub fn random_weighted_choice<T, F>(elems: &[T], weight: F) -> Option<&T>
+    where F: Fn(&T) -> f32
+{
+    let weights = elems.iter().map(|elem| weight(elem).max(0.0)).collect::<Vec<_>>();
+    let total: f32 = weights.iter().sum();
+
+    if total <= 0.0 {
+        return None;
+    }
+
+    let mut target = random(0.0, total);
+    for (elem, weight) in elems.iter().zip(weights.iter()) {
+        if target < *weight {
+            return Some(elem);
+        }
+
+        target -= *weight;
+    }
+
+    // Rounding errors might leave a little bit over, fall to the last possible one
+    elems.iter().zip(weights.iter()).rev().find(|&(_, weight)| *weight > 0.0).map(|(elem, _)| elem)
+}
+
 pub fn clamp(val: f32, lower: f32, upper: f32) -> f32 {
     val.max(lower).min(upper)
 }
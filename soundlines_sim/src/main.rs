@@ -13,6 +13,7 @@ extern crate noise;
 #[macro_use] extern crate clap;
 
 extern crate soundlines_core;
+extern crate soundlines_config;
 
 mod helpers;
 mod constants;
@@ -39,6 +40,7 @@ use clap::SubCommand;
 use clap::AppSettings;
 
 use soundlines_core::db;
+use soundlines_config::Component;
 
 fn main() {
     let app = App::new("Soundlines Simulation")
@@ -48,9 +50,8 @@ fn main() {
         .subcommand(SubCommand::with_name("gencells")
                     .about("Creates initial cells")
                     .arg(Arg::with_name("cell_size")
-                         .help("Sets the cell size")
+                         .help("Sets the cell size, overrides sim.cell_size")
                          .takes_value(true)
-                         .default_value("50.0")
                          .require_equals(true)))
 
         .subcommand(SubCommand::with_name("genworld")
@@ -68,56 +69,73 @@ fn main() {
 
                     .arg(Arg::with_name("directory")
                          .long("directory")
-                         .help("Output directory where the snapshot json files going to be written")
-                         .require_equals(true)
-                         .default_value("snapshots"))
+                         .help("Output directory where the snapshot json files going to be written, overrides sim.snapshots_dir")
+                         .takes_value(true)
+                         .require_equals(true))
 
                     .arg(Arg::with_name("interval")
                          .long("interval")
-                         .help("Any integer in units of minutes")
-                         .require_equals(true)
-                         .default_value("60")))
+                         .help("Any integer in units of minutes, overrides sim.snapshot_interval")
+                         .takes_value(true)
+                         .require_equals(true)))
 
         .subcommand(SubCommand::with_name("simulate")
                     .about("Starts the simulation")
 
                     .arg(Arg::with_name("time_scale")
                          .long("time_scale")
-                         .help("Any integer > 0 to determine how fast the simulation going to run")
-                         .require_equals(true)
-                         .default_value("1.0"))
+                         .help("Any integer > 0 to determine how fast the simulation going to run, overrides sim.time_scale")
+                         .takes_value(true)
+                         .require_equals(true))
 
                     .arg(Arg::with_name("seed_max_age")
                          .long("seed_max_age")
-                         .help("Any float > 0 to determine how long a seed can survive without being bloomed")
-                         .require_equals(true)
-                         .default_value("250.0")));
+                         .help("Any float > 0 to determine how long a seed can survive without being bloomed, overrides sim.seed_max_age")
+                         .takes_value(true)
+                         .require_equals(true)));
 
 
     let matches = app.get_matches();
-    let connection_pool = db::init_pool();
+    let config = soundlines_config::load_or_exit(Component::Sim);
+    let connection_pool = db::init_pool(&config.database);
 
     let mut context = context::SimContext::default();
 
     let res: Result<_, _> = match matches.subcommand() {
         ("simulate", Some(options)) => {
-            context.time_scale = value_t_or_exit!(options.value_of("time_scale"), f32).floor();
-            context.seed_max_age = value_t_or_exit!(options.value_of("seed_max_age"), f32).floor();
+            context.time_scale = match options.value_of("time_scale") {
+                Some(_) => value_t_or_exit!(options.value_of("time_scale"), f32),
+                None    => config.sim.time_scale
+            }.floor();
+            context.seed_max_age = match options.value_of("seed_max_age") {
+                Some(_) => value_t_or_exit!(options.value_of("seed_max_age"), f32),
+                None    => config.sim.seed_max_age
+            }.floor();
             simulation::run(connection_pool, context)
         },
 
         ("randomize", _) =>
             randomizer::run(connection_pool),
 
-        ("snapshot", Some(options)) =>
-            snapshot::run(value_t_or_exit!(options.value_of("interval"), u32),
-                          value_t_or_exit!(options.value_of("directory"), String)),
+        ("snapshot", Some(options)) => {
+            let interval = match options.value_of("interval") {
+                Some(_) => value_t_or_exit!(options.value_of("interval"), u32),
+                None    => config.sim.snapshot_interval
+            };
+            let directory = options.value_of("directory").unwrap_or(config.sim.snapshots_dir.as_str()).to_string();
+            snapshot::run(connection_pool, interval, directory)
+        },
 
         ("genworld", Some(options)) => 
             genworld::run(connection_pool,  options.is_present("clear")),
 
-        ("gencells", Some(options)) =>
-            gencells::run(value_t_or_exit!(options.value_of("cell_size"), f64)),
+        ("gencells", Some(options)) => {
+            let cell_size = match options.value_of("cell_size") {
+                Some(_) => value_t_or_exit!(options.value_of("cell_size"), f64),
+                None    => config.sim.cell_size
+            };
+            gencells::run(connection_pool, cell_size)
+        },
 
         _ => unreachable!()
     };
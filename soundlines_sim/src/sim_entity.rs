@@ -31,10 +31,10 @@ impl<'s: 'd, 'd, 'c> DerefMut for SimEntity<'s, 'd, 'c> {
     }
 }
 
-impl<'a: 'd, 'd, 'c> SimEntity<'a, 'd, 'c> {
-	const WIFI_LOW: f32 = -50.0;
-	const WIFI_HIGH: f32 = 50.0;
+pub const WIFI_LOW: f32 = -50.0;
+pub const WIFI_HIGH: f32 = 50.0;
 
+impl<'a: 'd, 'd, 'c> SimEntity<'a, 'd, 'c> {
 	const LIGHT_LOW: f32 = 0.0;
 	const LIGHT_HIGH: f32 = 0.0;
 
@@ -73,37 +73,7 @@ impl<'a: 'd, 'd, 'c> SimEntity<'a, 'd, 'c> {
     }
 
     fn calculate_sensitivity(&self, cell: &Cell) -> f32 {
-	    let cell_wifi = clamp_map(cell.wifi, Self::WIFI_LOW, Self::WIFI_HIGH, 0.0, 1.0);
-
-        if cell_wifi > 0.5 {
-       		let wifi =  
-       			(map(self.setting.wifi_sensitivity, -1.0, 1.0, 0.2, 5.0) *
-       		     map(cell_wifi, 0.0, 1.0, 0.5, 1.0)) / 2.6;
-
-        	let light = 
-        		(map(self.setting.light_sensitivity, -1.0, 1.0, 0.2, 5.0) *
-        		 map(cell.light, 0.0, 1.0, 0.5, 1.0) ) / 2.6;
-
-        	let sound = 
-        		(map(self.setting.sound_sensitivity, -1.0, 1.0, 0.2, 5.0) *
-        		 map(cell.sound, 0.0, 1.0, 0.5, 1.0) ) / 2.6;
-
-        	wifi * light * sound
-     	} else {
-       		let wifi = 
-       			(map(self.setting.wifi_sensitivity , -1.0, 1.0, 0.2, 5.0) *
-       			 map(cell_wifi, 0.0, 1.0, 0.5, 1.0) ) / 1.3  ;
-
-       		let light = 
-       			(map(self.setting.light_sensitivity, -1.0, 1.0, 0.2, 5.0) *
-       			 map(cell.light, 0.0, 1.0, 0.5, 1.0) ) / 1.3;
-
-       		let sound = 
-       			(map(self.setting.sound_sensitivity, -1.0, 1.0, 0.2, 5.0) *
-       			 map(cell.sound, 0.0, 1.0, 0.5, 1.0) ) / 1.3 ;
-
-        	wifi * light * sound
-     	}
+        cell_sensitivity(self.setting, cell)
     }
 
     pub fn update_by_neighbors(&mut self, cell: &Cell, neighbor_count: i32) {
@@ -130,3 +100,26 @@ impl<'a: 'd, 'd, 'c> SimEntity<'a, 'd, 'c> {
         self.entity.last_seed_at = self.entity.age;
     }
 }
+
+/// How well plants of the setting do in the cell, they grow faster and age
+/// slower the higher it is
+pub fn cell_sensitivity(setting: &PlantSetting, cell: &Cell) -> f32 {
+    let cell_wifi = clamp_map(cell.wifi, WIFI_LOW, WIFI_HIGH, 0.0, 1.0);
+
+    // Sensitivities matter less in cells with strong wifi
+    let divider = if cell_wifi > 0.5 { 2.6 } else { 1.3 };
+
+    let wifi =
+        (map(setting.wifi_sensitivity, -1.0, 1.0, 0.2, 5.0) *
+         map(cell_wifi, 0.0, 1.0, 0.5, 1.0)) / divider;
+
+    let light =
+        (map(setting.light_sensitivity, -1.0, 1.0, 0.2, 5.0) *
+         map(cell.light, 0.0, 1.0, 0.5, 1.0)) / divider;
+
+    let sound =
+        (map(setting.sound_sensitivity, -1.0, 1.0, 0.2, 5.0) *
+         map(cell.sound, 0.0, 1.0, 0.5, 1.0)) / divider;
+
+    wifi * light * sound
+}
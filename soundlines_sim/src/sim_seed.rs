@@ -11,6 +11,9 @@ use geo::Point as GPoint;
 
 use helpers::*;
 use sim_dna::SimDna;
+use sim_entity::WIFI_LOW;
+use sim_entity::WIFI_HIGH;
+use sim_entity::cell_sensitivity;
 use context::SimContext;
 
 pub struct SeedDraft {
@@ -19,27 +22,59 @@ pub struct SeedDraft {
     pub cell_id: i32
 }
 
+/// Draft of a seed in the cell, of a species which suits the cell's environment
 pub fn generate(plant_settings: &[PlantSetting], cell: &Cell) -> SeedDraft {
-    let location = get_random_location(cell, 50.0);
-    let setting = random_choice(plant_settings);
+    let location = get_random_location(cell);
+    let setting = choose_setting(plant_settings, cell);
     let dna = SimDna::random_from_setting(setting);
 
     SeedDraft { dna: dna.dna, location, cell_id: cell.id }
 }
 
-fn get_random_location(cell: &Cell, cell_size: f64) -> Point {
+/// How likely seeds are to be generated in the cell, cells with richer
+/// environments get more seeds but quiet ones still get some
+pub fn cell_weight(cell: &Cell) -> f32 {
+    let wifi = clamp_map(cell.wifi, WIFI_LOW, WIFI_HIGH, 0.0, 1.0);
+    let light = clamp(cell.light, 0.0, 1.0);
+    let sound = clamp(cell.sound, 0.0, 1.0);
+
+    0.2 + (wifi + light + sound) / 3.0
+}
+
+/// Picks a cell weighted by its environment, `None` only when there are no cells
+pub fn choose_cell(cells: &[Cell]) -> Option<&Cell> {
+    random_weighted_choice(cells, cell_weight)
+}
+
+/// Picks a setting weighted by how well its plants would do in the cell
+pub fn choose_setting<'s>(plant_settings: &'s [PlantSetting], cell: &Cell) -> &'s PlantSetting {
+    random_weighted_choice(plant_settings, |setting| cell_sensitivity(setting, cell).abs())
+        .unwrap_or_else(|| random_choice(plant_settings))
+}
+
+const MAX_LOCATION_TRIES: usize = 100;
+
+/// Uniformly random point inside the cell's polygon, sampled within its
+/// bounding box until one falls inside
+pub fn get_random_location(cell: &Cell) -> Point {
     use geo::boundingbox::BoundingBox;
-    use geo::haversine_destination::HaversineDestination;
+    use geo::centroid::Centroid;
+    use geo::contains::Contains;
 
     let points: Vec<_> = cell.geom.rings[0].points.iter().map(into_geo_point).collect();
     let polygon = GPolygon::new(points.into(), vec![]);
     let bbox = polygon.bbox().expect("Bounding box of the cell");
 
-    let mut location1 = GPoint::new(bbox.xmin, bbox.ymin);
-    location1 = location1.haversine_destination(90.0, random(0.0, cell_size));
-    location1 = location1.haversine_destination(0.0, random(0.0, cell_size));
+    for _ in 0..MAX_LOCATION_TRIES {
+        let location = GPoint::new(random(bbox.xmin, bbox.xmax), random(bbox.ymin, bbox.ymax));
+        if polygon.contains(&location) {
+            return into_core_point(&location);
+        }
+    }
 
-    Point::new(location1.x(), location1.y(), Some(4326))
+    // Only degenerate cells end up here
+    let center = polygon.centroid().unwrap_or(GPoint::new(bbox.xmin, bbox.ymin));
+    into_core_point(&center)
 }
 
 pub struct SimSeed<'d, 's: 'd, 'c> {
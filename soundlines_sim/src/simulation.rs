@@ -1,5 +1,6 @@
 use std::thread;
 use std::time::Duration;
+use std::time::Instant;
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::error::Error;
@@ -18,9 +19,12 @@ use soundlines_core::db::Pool;
 use soundlines_core::db::extensions::*;
 use soundlines_core::db::models::Cell;
 use soundlines_core::db::models::Entity;
+use soundlines_core::db::models::DeadEntity;
 use soundlines_core::db::models::PlantSetting;
 use soundlines_core::db::models::Dna;
 use soundlines_core::db::models::Seed;
+use soundlines_core::db::models::Heartbeat;
+use soundlines_core::db::models::WorldEvent;
 
 use helpers::*;
 use context::SimContext;
@@ -29,9 +33,12 @@ use sim_dna::SimDna;
 use sim_seed::SimSeed;
 use sim_geo::get_seed_location;
 
+const HEARTBEAT_INTERVAL_SECS: u64 = 10;
+
 pub fn run(connection_pool: Pool, ctx: SimContext) -> Result<(), Box<Error>> {
 	let connection_pool = Arc::new(connection_pool);
 	let conn = connection_pool.get()?;
+	let mut last_heartbeat: Option<Instant> = None;
 
 	loop {
 		let plant_settings = conn.all::<PlantSetting>()?
@@ -81,6 +88,7 @@ pub fn run(connection_pool: Pool, ctx: SimContext) -> Result<(), Box<Error>> {
 			.collect::<HashMap<_, _>>();
 
 		let mut new_seeds = vec![];
+		let mut mating_events = vec![];
 		for _ in 0..ctx.time_scale as u32 {
 			let mut possible_mate_candidates: Vec<(i32, Vec<i32>)> = vec![];
 			for (id, entity) in entities.iter_mut() {
@@ -134,6 +142,7 @@ pub fn run(connection_pool: Pool, ctx: SimContext) -> Result<(), Box<Error>> {
 
 					let entity1 = &entities[&entity_id];
 					let entity2 = &entities[matched_id];
+					mating_events.push(WorldEvent::mating(&entity1.entity, *matched_id));
 
 					let wind = Vector2::<f64>::rand(&mut rng).normalize_to(30.0);
 					let seed_location = get_seed_location(entity1, entity2, wind);
@@ -150,6 +159,8 @@ pub fn run(connection_pool: Pool, ctx: SimContext) -> Result<(), Box<Error>> {
 			}
 		}
 
+		conn.insert_batch(&mating_events)?;
+
 		// create seeds
 		new_seeds
 			.into_par_iter()
@@ -165,6 +176,7 @@ pub fn run(connection_pool: Pool, ctx: SimContext) -> Result<(), Box<Error>> {
 					let dna = conn.insert(&dna.dna).expect("Failed to write new seed's dna to the db");
 					let seed = Seed::new(dna.id, into_core_point(&loc), cell.id, dna.setting_id, prefab);
 					let seed = conn.insert(&seed).expect("Failed to write new seed to db");
+					conn.insert(&WorldEvent::seed_thrown(&seed)).expect("Failed to write seed thrown event");
 					println!("New seed is thrown at {:?}", seed.point);
 				} else {
 					println!("Seed out of grid!");
@@ -174,9 +186,10 @@ pub fn run(connection_pool: Pool, ctx: SimContext) -> Result<(), Box<Error>> {
 		// destroy dead seeds
 		let (dead_seeds, other_seeds): (Vec<_>, Vec<_>) = seeds.into_iter().partition(|&(_, ref s)| s.is_dead());
 		dead_seeds.into_par_iter()
-			.for_each_with(connection_pool.clone(), |pool, (id, _)| {
+			.for_each_with(connection_pool.clone(), |pool, (id, s)| {
 				let conn = pool.get().expect("Failed to get connection in parallel seed destroying");
 				conn.delete::<Seed>(id).expect("Failed to delete dead seed");
+				conn.insert(&WorldEvent::seed_died(&s.seed)).expect("Failed to write seed died event");
 
 				println!("A seed is died...");
 			});
@@ -189,7 +202,8 @@ pub fn run(connection_pool: Pool, ctx: SimContext) -> Result<(), Box<Error>> {
 				conn.delete::<Seed>(id).expect("Failed to delete bloomed seed");
 
 				let entity = Entity::new(s.seed.point, s.seed.cell_id, s.setting, s.dna);
-				conn.insert(&entity).expect("Failed to insert new entity for blooming seed");
+				let entity = conn.insert(&entity).expect("Failed to insert new entity for blooming seed");
+				conn.insert(&WorldEvent::birth(&s.seed, &entity)).expect("Failed to write birth event");
 
 				println!("A seed is bloomed...");
 			});
@@ -212,9 +226,16 @@ pub fn run(connection_pool: Pool, ctx: SimContext) -> Result<(), Box<Error>> {
 				let dna_id = entity.dna_id;
 
 				let conn = pool.get().expect("Failed to get connection in parallel destroying dead entities");
+
+				// Players can still see the plants they planted after they die
+				if let Some(dead_entity) = DeadEntity::from_entity(&entity) {
+					conn.insert(&dead_entity).expect("Failed to archive dead entity");
+				}
+
 				conn.delete::<Entity>(id).expect("Failed to delete entity");
+				conn.insert(&WorldEvent::death(&entity.entity)).expect("Failed to write death event");
 
-				conn.delete::<Dna>(dna_id).expect("Failed to delete dead entity's dna");d
+				conn.delete::<Dna>(dna_id).expect("Failed to delete dead entity's dna");
 
 				println!("An entity is died...");
 			});
@@ -226,6 +247,15 @@ pub fn run(connection_pool: Pool, ctx: SimContext) -> Result<(), Box<Error>> {
 				conn.update(id, &e.entity).expect("Failed to update entity");
 			});
 
+		// Lets the server report the simulation as stalled, no need to write every tick
+		if last_heartbeat.map(|beat| beat.elapsed() >= Duration::from_secs(HEARTBEAT_INTERVAL_SECS)).unwrap_or(true) {
+			// A missed heartbeat only makes the server report the simulation as stale
+			if let Err(err) = Heartbeat::beat(&conn, Heartbeat::SIMULATION) {
+				println!("Failed to write {} heartbeat: {}", Heartbeat::SIMULATION, err);
+			}
+			last_heartbeat = Some(Instant::now());
+		}
+
 		thread::sleep(Duration::from_millis(300));
 	}
 
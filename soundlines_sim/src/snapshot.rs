@@ -16,9 +16,9 @@ use chrono_tz::Japan;
 
 use soundlines_core::db::models::*;
 use soundlines_core::db::extensions::*;
-use soundlines_core::db::init_connection;
+use soundlines_core::db::Pool;
 
-pub fn run(interval: u32, snapshots_dir: String) -> Result<(), Box<Error>> {
+pub fn run(connection_pool: Pool, interval: u32, snapshots_dir: String) -> Result<(), Box<Error>> {
     let mut sched = JobScheduler::new();
 
     // Every hour
@@ -32,7 +32,7 @@ pub fn run(interval: u32, snapshots_dir: String) -> Result<(), Box<Error>> {
     println!();
 
     sched.add(Job::new(schedule, || {
-        if let Err(err) = snapshot(&snapshots_dir) {
+        if let Err(err) = snapshot(&connection_pool, &snapshots_dir) {
             on_error(&err);
             process::exit(1);
         }
@@ -48,11 +48,11 @@ pub fn run(interval: u32, snapshots_dir: String) -> Result<(), Box<Error>> {
     Ok(())
 }
 
-fn snapshot(output_directory: &str) -> Result<(), Box<Error>> {
+fn snapshot(connection_pool: &Pool, output_directory: &str) -> Result<(), Box<Error>> {
     let now = Utc::now().with_timezone(&Japan);
     println!("Taking snapshot for {}", now);
 
-    let conn = init_connection();
+    let conn = connection_pool.get()?;
 
     // Taking snapshot of entities
     let (entities, seeds, cells, weather, users) = (